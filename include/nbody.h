@@ -0,0 +1,58 @@
+/*
+ * C API of rs-nbody, from building the library with the `ffi` feature. Kept in step with
+ * src/ffi.rs by hand.
+ *
+ * Vectors are passed as flat arrays of their components, {x0, y0, z0, x1, y1, z1, ...}, and
+ * masses as Gm. Bodies interact by Newtonian gravity.
+ */
+
+#ifndef NBODY_H
+#define NBODY_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define NBODY_FORWARD_EULER 0
+#define NBODY_SYMPLECTIC_EULER 1
+#define NBODY_LEAPFROG 2
+
+typedef struct NbodyWorld NbodyWorld;
+
+/*
+ * Creates a world of `count` bodies integrated with `integrator`, one of the NBODY_* constants,
+ * returning NULL if the integrator is unknown or an array is NULL. `positions` and `velocities`
+ * must point to 3 * count doubles and `masses` to count doubles. Free it with nbody_world_free.
+ */
+NbodyWorld *nbody_world_new(int integrator, size_t count, const double *positions,
+                            const double *velocities, const double *masses);
+
+/* Frees a world. NULL is ignored. */
+void nbody_world_free(NbodyWorld *world);
+
+/* Advances the world by a tick of `tick_duration`. */
+void nbody_tick(NbodyWorld *world, double tick_duration);
+
+size_t nbody_count(const NbodyWorld *world);
+
+double nbody_time(const NbodyWorld *world);
+
+/* Total energy, in units of G times energy like the masses. */
+double nbody_total_energy(const NbodyWorld *world);
+
+/*
+ * Positions of the bodies, as 3 * nbody_count(world) doubles which stay valid until the world is
+ * next ticked or freed or this is next called.
+ */
+const double *nbody_positions_ptr(NbodyWorld *world);
+
+/* Velocities of the bodies, as nbody_positions_ptr gives the positions. */
+const double *nbody_velocities_ptr(NbodyWorld *world);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
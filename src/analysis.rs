@@ -0,0 +1,276 @@
+use std::{
+    collections::HashSet,
+    f64::consts::TAU,
+    fmt::{Display, Formatter, Result},
+};
+
+use crate::{
+    body::Body,
+    boundary::{self, PeriodicBox},
+    vec3::{Point3, Vec3},
+};
+
+/// Keplerian elements of a two-body orbit. Angles are in radians, measured from the x axis in the
+/// xy plane.
+#[derive(Clone, Copy, Debug)]
+pub struct OrbitalElements {
+    /// Semi-major axis, negative for hyperbolic orbits.
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    pub inclination: f64,
+    /// Longitude of the ascending node Ω, zero for orbits in the xy plane.
+    pub ascending_node: f64,
+    /// Argument of periapsis ω, zero for circular orbits and measured from the x axis for orbits in
+    /// the xy plane.
+    pub periapsis: f64,
+    /// True anomaly ν, measured from the ascending node for circular orbits.
+    pub true_anomaly: f64,
+}
+
+/// Eccentricities and node vector lengths below which orbits are treated as circular or planar.
+const TOLERANCE: f64 = 1e-12;
+
+impl OrbitalElements {
+    /// Elements of an orbit with relative `position` and `velocity` about a total Gm of `mu`.
+    pub fn from_relative(position: Vec3, velocity: Vec3, mu: f64) -> Self {
+        let r = position.length();
+        let h = Vec3::cross(&position, &velocity);
+        let normal = Vec3::unitv(&h);
+        let e = Vec3::cross(&velocity, &h) / mu - position / r;
+        let node = Vec3::new(-h.y(), h.x(), 0.);
+        let energy = velocity.length_squared() / 2. - mu / r;
+
+        let eccentricity = e.length();
+        let circular = eccentricity < TOLERANCE;
+        let planar = node.length() < TOLERANCE * h.length();
+        // Reference direction in the orbital plane from which the true anomaly is measured
+        let reference = match (circular, planar) {
+            (false, _) => e,
+            (true, false) => node,
+            (true, true) => Vec3::new(1., 0., 0.),
+        };
+        let periapsis = match (circular, planar) {
+            (true, _) => 0.,
+            (false, false) => angle(&node, &e, &normal),
+            (false, true) => angle(&Vec3::new(1., 0., 0.), &e, &normal),
+        };
+        Self {
+            semi_major_axis: -mu / (2. * energy),
+            eccentricity,
+            inclination: (h.z() / h.length()).clamp(-1., 1.).acos(),
+            ascending_node: if planar {
+                0.
+            } else {
+                node.y().atan2(node.x()).rem_euclid(TAU)
+            },
+            periapsis,
+            true_anomaly: angle(&reference, &position, &normal),
+        }
+    }
+
+    /// Elements of the orbit of the body at index `i` about the body at index `j`, or about the
+    /// barycentre of every other active body if there is no `j`. None if either is out of range or
+    /// inactive.
+    pub fn of(bodies: &[Body], i: usize, j: Option<usize>) -> Option<Self> {
+        let body = bodies.get(i).filter(|body| body.is_active())?;
+        let (position, velocity, mass) = match j {
+            Some(j) => {
+                let other = bodies.get(j).filter(|other| other.is_active() && j != i)?;
+                (other.position, other.velocity, other.mass)
+            }
+            None => {
+                let others = bodies
+                    .iter()
+                    .enumerate()
+                    .filter(|&(k, other)| k != i && other.is_active());
+                let mass: f64 = others.clone().map(|(_, other)| other.mass).sum();
+                let moment: Point3 = others.clone().map(|(_, o)| o.mass * o.position).sum();
+                let momentum: Vec3 = others.map(|(_, o)| o.mass * o.velocity).sum();
+                (moment / mass, momentum / mass, mass)
+            }
+        };
+        Some(Self::from_relative(
+            body.position - position,
+            body.velocity - velocity,
+            body.mass + mass,
+        ))
+    }
+}
+
+/// Mobile active bodies further than `radius` from the barycentre of the active bodies with a
+/// positive specific energy relative to it, counting only the gravity of the other bodies, with
+/// their specific energies.
+pub fn escapers(bodies: &[Body], radius: f64) -> Vec<(usize, f64)> {
+    let active = bodies.iter().filter(|body| body.is_active());
+    let mass: f64 = active.clone().map(|body| body.mass).sum();
+    let center = active.clone().map(|b| b.mass * b.position).sum::<Point3>() / mass;
+    let velocity = active.map(|b| b.mass * b.velocity).sum::<Vec3>() / mass;
+    bodies
+        .iter()
+        .enumerate()
+        .filter(|(_, body)| body.is_mobile() && (body.position - center).length() > radius)
+        .filter_map(|(i, body)| {
+            let potential: f64 = bodies
+                .iter()
+                .enumerate()
+                .filter(|&(j, from)| j != i && from.is_source())
+                .map(|(_, from)| -from.mass / (body.position - from.position).length())
+                .sum();
+            let energy = (body.velocity - velocity).length_squared() / 2. + potential;
+            (energy > 0.).then_some((i, energy))
+        })
+        .collect()
+}
+
+/// Number of nearest neighbours within which local densities are estimated.
+const DENSITY_NEIGHBOURS: usize = 6;
+
+/// Density centre of the active bodies (Casertano & Hut 1985), the mean of their positions weighted
+/// by the local density within each one's sixth nearest neighbour. It follows the core of a cluster
+/// rather than being pulled away by its halo and escapers like the centre of mass.
+pub fn density_center(bodies: &[Body]) -> Point3 {
+    let active: Vec<&Body> = bodies.iter().filter(|body| body.is_active()).collect();
+    let neighbours = DENSITY_NEIGHBOURS.min(active.len().saturating_sub(1));
+    let mut weight = 0.;
+    let mut moment = Vec3::ZERO;
+    for (i, body) in active.iter().enumerate() {
+        let mut others: Vec<(f64, f64)> = (active.iter().enumerate())
+            .filter(|&(j, _)| j != i)
+            .map(|(_, other)| ((body.position - other.position).length(), other.mass))
+            .collect();
+        others.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let Some(&(radius, _)) = others.get(neighbours.wrapping_sub(1)) else {
+            continue;
+        };
+        // Mass strictly within the kth neighbour over the volume of its sphere
+        let mass: f64 = others[..neighbours - 1].iter().map(|(_, m)| m).sum();
+        let density = mass / radius.powi(3);
+        if density.is_finite() {
+            weight += density;
+            moment += density * body.position;
+        }
+    }
+    if weight > 0. {
+        moment / weight
+    } else {
+        let mass: f64 = active.iter().map(|body| body.mass).sum();
+        active.iter().map(|b| b.mass * b.position).sum::<Point3>() / mass
+    }
+}
+
+/// Radii about `center` enclosing each of the `fractions` of the total mass of the active bodies.
+pub fn lagrangian_radii(bodies: &[Body], center: Point3, fractions: &[f64]) -> Vec<f64> {
+    let mut shells: Vec<(f64, f64)> = bodies
+        .iter()
+        .filter(|body| body.is_active())
+        .map(|body| ((body.position - center).length(), body.mass))
+        .collect();
+    shells.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let total: f64 = shells.iter().map(|(_, mass)| mass).sum();
+    fractions
+        .iter()
+        .map(|fraction| {
+            let mut enclosed = 0.;
+            shells
+                .iter()
+                .find(|(_, mass)| {
+                    enclosed += mass;
+                    enclosed >= fraction * total
+                })
+                .map_or(f64::NAN, |&(radius, _)| radius)
+        })
+        .collect()
+}
+
+/// Angle from `from` to `to` about `normal`, between 0 and 2π.
+fn angle(from: &Vec3, to: &Vec3, normal: &Vec3) -> f64 {
+    let sin = Vec3::dot(&Vec3::cross(from, to), normal);
+    let cos = Vec3::dot(from, to);
+    sin.atan2(cos).rem_euclid(TAU)
+}
+
+impl Display for OrbitalElements {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "a = {:e}, e = {}, i = {}°, Ω = {}°, ω = {}°, ν = {}°",
+            self.semi_major_axis,
+            self.eccentricity,
+            self.inclination.to_degrees(),
+            self.ascending_node.to_degrees(),
+            self.periapsis.to_degrees(),
+            self.true_anomaly.to_degrees()
+        )
+    }
+}
+
+/// Tracks the minimum separation reached between any pair of active bodies over a run.
+#[derive(Clone, Debug)]
+pub struct ClosestApproach {
+    pub separation: f64,
+    pub first: usize,
+    pub second: usize,
+    pub time: f64,
+    /// Separation below which pairs are reported as they come together.
+    threshold: Option<f64>,
+    /// Pairs of bodies currently within the threshold.
+    close: HashSet<(usize, usize)>,
+}
+
+impl ClosestApproach {
+    pub fn new(threshold: Option<f64>) -> Self {
+        Self {
+            separation: f64::INFINITY,
+            first: 0,
+            second: 0,
+            time: 0.,
+            threshold,
+            close: HashSet::new(),
+        }
+    }
+
+    /// Updates the closest approach with the bodies at `time`, returning the pairs which have come
+    /// within the threshold since the last update, with their separations.
+    pub fn update(
+        &mut self,
+        time: f64,
+        bodies: &[Body],
+        periodic: Option<PeriodicBox>,
+    ) -> Vec<(usize, usize, f64)> {
+        let mut close = HashSet::new();
+        let mut new = Vec::new();
+        for (i, a) in bodies.iter().enumerate().filter(|(_, a)| a.is_active()) {
+            for (j, b) in bodies.iter().enumerate().skip(i + 1) {
+                if !b.is_active() {
+                    continue;
+                }
+                let separation = boundary::separation(periodic, a.position, b.position).length();
+                if separation < self.separation {
+                    self.separation = separation;
+                    (self.first, self.second, self.time) = (i, j, time);
+                }
+                if self
+                    .threshold
+                    .is_some_and(|threshold| separation < threshold)
+                {
+                    close.insert((i, j));
+                    if !self.close.contains(&(i, j)) {
+                        new.push((i, j, separation));
+                    }
+                }
+            }
+        }
+        self.close = close;
+        new
+    }
+}
+
+impl Display for ClosestApproach {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "{:e} between bodies {} and {} at t = {}",
+            self.separation, self.first, self.second, self.time
+        )
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::vec3::{Point3, Vec3};
+
+/// What has happened to a body over the run. Bodies are never deleted, so that their indices
+/// stay stable and outputs always show what became of each one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum State {
+    #[default]
+    Active,
+    /// Merged into the body with id `into` in a collision.
+    Merged { into: usize },
+    /// Lost all of its mass.
+    Removed,
+    /// Escaped from the system at `time` and was removed from the simulation.
+    Escaped { time: f64 },
+}
+
+/// Serialised with masses as Gm, under `gm` and `gm_rate`, and with properties which are zero,
+/// false or missing left out.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Body {
+    /// Identifier which stays the same as other bodies are added to and removed from a world,
+    /// unlike the body's index. Worlds give their initial bodies ids of their indices.
+    #[serde(default)]
+    pub id: usize,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub name: Option<String>,
+    /// Colour for plotting, such as `#ffcc00`, which is written to trajectories for `plot` and
+    /// `render` and passed through to JSON output.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub color: Option<String>,
+    /// Radius in pixels of the body's dot in `plot` and `render`, which is written to trajectories
+    /// along with its colour.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub size: Option<u32>,
+    /// Number of previous frames over which `render` draws the body's path behind it, instead of the
+    /// `--trail` of the animation.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub trail: Option<u32>,
+    pub position: Point3,
+    pub velocity: Vec3,
+    #[serde(rename = "gm")]
+    pub mass: f64,
+    /// Radius used for collision detection. Zero for point masses.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub radius: f64,
+    /// Test particles feel forces from other bodies but exert none.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub test: bool,
+    /// Fixed bodies are never moved by the integrators, but still exert forces on others.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub fixed: bool,
+    /// Rate of change of mass, in Gm units per unit time. Mass is lost or accreted isotropically in
+    /// the body's frame, so its velocity is unaffected.
+    #[serde(rename = "gm_rate", default, skip_serializing_if = "is_zero")]
+    pub mass_rate: f64,
+    /// Electric charge, for Coulomb interactions.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub charge: f64,
+    /// Plummer softening length of the body's gravity. Pairs of bodies are softened by the mean of
+    /// their lengths, so that forces stay symmetric.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub softening: f64,
+    /// Inactive bodies keep their state at the moment they became inactive, and neither move nor
+    /// exert forces.
+    #[serde(default)]
+    pub state: State,
+}
+
+impl Body {
+    pub fn new(position: Point3, velocity: Vec3, mass: f64) -> Self {
+        Self {
+            id: 0,
+            position,
+            velocity,
+            mass,
+            radius: 0.,
+            test: false,
+            fixed: false,
+            mass_rate: 0.,
+            charge: 0.,
+            softening: 0.,
+            state: State::Active,
+            name: None,
+            color: None,
+            size: None,
+            trail: None,
+        }
+    }
+
+    /// Evolves the mass of the body over a tick, never letting it become negative. Bodies which
+    /// lose all of their mass are removed.
+    pub fn evolve_mass(&mut self, tick_duration: f64) {
+        if !self.is_active() || self.mass_rate == 0. {
+            return;
+        }
+        let mass = self.mass + self.mass_rate * tick_duration;
+        if mass <= 0. && self.mass > 0. {
+            self.state = State::Removed;
+        }
+        self.mass = mass.max(0.);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state == State::Active
+    }
+
+    /// Whether this body is moved by the integrators.
+    pub fn is_mobile(&self) -> bool {
+        self.is_active() && !self.fixed
+    }
+
+    /// Whether this body exerts a gravitational force on others.
+    pub fn is_source(&self) -> bool {
+        self.is_active() && !self.test && self.mass != 0.
+    }
+}
+
+pub(crate) fn is_zero(x: &f64) -> bool {
+    *x == 0.
+}
+
+pub(crate) fn is_false(b: &bool) -> bool {
+    !b
+}
+
+/// Gives the bodies ids of their indices, returning the next unused id.
+pub fn assign_ids(bodies: &mut [Body]) -> usize {
+    for (i, body) in bodies.iter_mut().enumerate() {
+        body.id = i;
+    }
+    bodies.len()
+}
+
+impl Display for Body {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "r = [{:e}], v = [{:e}], Gm = {:e}",
+            self.position, self.velocity, self.mass
+        )?;
+        match self.state {
+            State::Active => Ok(()),
+            State::Merged { into } => write!(f, ", merged into {}", into),
+            State::Removed => write!(f, ", removed"),
+            State::Escaped { time } => write!(f, ", escaped at t = {}", time),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{
+    body::Body,
+    vec3::{Point3, Vec3},
+};
+
+/// What happens to bodies at the walls of a box.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    /// Bodies leaving through a wall re-enter through the opposite one
+    Periodic,
+    /// Bodies bounce elastically off the walls
+    Reflect,
+}
+
+/// Cubic box of side `size` centred on the origin, which confines the bodies.
+#[derive(Clone, Copy, Debug)]
+pub struct SimulationBox {
+    pub size: f64,
+    pub boundary: Boundary,
+}
+
+impl SimulationBox {
+    /// The box, if its boundaries are periodic.
+    pub fn periodic(&self) -> Option<PeriodicBox> {
+        match self.boundary {
+            Boundary::Periodic => Some(PeriodicBox { size: self.size }),
+            Boundary::Reflect => None,
+        }
+    }
+
+    /// Moves `body` back into the box if it has left it. Fixed and inactive bodies are left in
+    /// place.
+    pub fn confine(&self, body: &mut Body) {
+        if !body.is_mobile() {
+            return;
+        }
+        match self.boundary {
+            Boundary::Periodic => {
+                body.position = PeriodicBox { size: self.size }.wrap(body.position)
+            }
+            Boundary::Reflect => {
+                let half = self.size / 2.;
+                for k in 0..3 {
+                    // Fold the overshoot back inside and point the velocity away from the wall
+                    if body.position[k] > half {
+                        body.position[k] = self.size - body.position[k];
+                        body.velocity[k] = -body.velocity[k].abs();
+                    } else if body.position[k] < -half {
+                        body.position[k] = -self.size - body.position[k];
+                        body.velocity[k] = body.velocity[k].abs();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Cubic box of side `size` centred on the origin, with periodic boundaries, as seen by pairwise
+/// interactions.
+#[derive(Clone, Copy, Debug)]
+pub struct PeriodicBox {
+    pub size: f64,
+}
+
+impl PeriodicBox {
+    /// Wraps `v` back into the box. Applied to a separation, this gives its minimum image, the
+    /// shortest separation between any periodic images of the two bodies.
+    pub fn wrap(&self, v: Vec3) -> Vec3 {
+        let wrap = |x: f64| x - self.size * (x / self.size).round();
+        Vec3::new(wrap(v.x()), wrap(v.y()), wrap(v.z()))
+    }
+}
+
+/// Position of the periodic image of `b` nearest to `a`, which is `b` itself unless the box is
+/// periodic.
+pub fn nearest_image(periodic: Option<PeriodicBox>, a: Point3, b: Point3) -> Point3 {
+    match periodic {
+        Some(periodic) => a + periodic.wrap(b - a),
+        None => b,
+    }
+}
+
+/// Separation from `a` to `b`, using the minimum image if the box is periodic.
+pub fn separation(periodic: Option<PeriodicBox>, a: Point3, b: Point3) -> Vec3 {
+    match periodic {
+        Some(periodic) => periodic.wrap(b - a),
+        None => b - a,
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::HashMap, fmt};
+
+use crate::body::Body;
+
+/// Headers which every CSV file of initial conditions must have.
+pub const REQUIRED_HEADERS: [&str; 7] =
+    ["pos_x", "pos_y", "pos_z", "vel_x", "vel_y", "vel_z", "mass"];
+
+/// Headers which CSV files of initial conditions may have.
+pub const OPTIONAL_HEADERS: [&str; 7] = [
+    "radius",
+    "test",
+    "fixed",
+    "mass_rate",
+    "charge",
+    "softening",
+    "name",
+];
+
+/// Fraction of the shortest timescale of any pair of bodies which is suggested as the tick.
+const TICK_FRACTION: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Problem with initial conditions, of the body with the given index if it concerns one.
+#[derive(Debug)]
+pub struct Problem {
+    pub body: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Problems with the headers of a CSV file: missing required headers are errors, and unknown ones,
+/// which are ignored when reading the file, are warnings.
+pub fn headers<'a>(headers: impl IntoIterator<Item = &'a str>) -> Vec<Problem> {
+    let headers: Vec<&str> = headers.into_iter().collect();
+    let missing = (REQUIRED_HEADERS.iter())
+        .filter(|required| !headers.contains(required))
+        .map(|required| Problem {
+            body: None,
+            severity: Severity::Error,
+            message: format!("missing required header `{}`", required),
+        });
+    let unknown = (headers.iter())
+        .filter(|header| !REQUIRED_HEADERS.contains(header) && !OPTIONAL_HEADERS.contains(header))
+        .map(|header| Problem {
+            body: None,
+            severity: Severity::Warning,
+            message: format!("unknown header `{}` is ignored", header),
+        });
+    missing.chain(unknown).collect()
+}
+
+/// Problems with bodies: non-finite values, negative masses, radii and softening lengths, and
+/// bodies at the same position as an earlier one, which is named in the message by `label`.
+pub fn bodies(bodies: &[Body], label: impl Fn(usize) -> String) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut positions: HashMap<[u64; 3], usize> = HashMap::new();
+    for (i, body) in bodies.iter().enumerate() {
+        let mut problem = |severity, message: String| {
+            problems.push(Problem {
+                body: Some(i),
+                severity,
+                message,
+            })
+        };
+        for (name, value) in values(body) {
+            if !value.is_finite() {
+                problem(Severity::Error, format!("{} is not finite", name));
+            }
+        }
+        for (name, value) in [
+            ("mass", body.mass),
+            ("radius", body.radius),
+            ("softening", body.softening),
+        ] {
+            if value < 0. {
+                problem(Severity::Error, format!("{} {} is negative", name, value));
+            }
+        }
+        if body.mass == 0. && !body.test {
+            problem(
+                Severity::Warning,
+                "mass is zero but the body isn't a test particle".to_string(),
+            );
+        }
+        let p = body.position;
+        let key = [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+        if let Some(&j) = positions.get(&key) {
+            problem(
+                Severity::Error,
+                format!("position is the same as {}", label(j)),
+            );
+        } else {
+            positions.insert(key, i);
+        }
+    }
+    problems
+}
+
+/// Name of the first value of a body which is infinite or NaN, if any.
+pub fn non_finite(body: &Body) -> Option<&'static str> {
+    (values(body).into_iter())
+        .find(|(_, value)| !value.is_finite())
+        .map(|(name, _)| name)
+}
+
+/// Named values of a body which must be finite, with vectors represented by their lengths.
+fn values(body: &Body) -> [(&'static str, f64); 7] {
+    [
+        ("position", body.position.length()),
+        ("velocity", body.velocity.length()),
+        ("mass", body.mass),
+        ("radius", body.radius),
+        ("mass_rate", body.mass_rate),
+        ("charge", body.charge),
+        ("softening", body.softening),
+    ]
+}
+
+/// Closest pair of bodies in time, with a tick which resolves their motion.
+#[derive(Debug)]
+pub struct Tightest {
+    pub bodies: (usize, usize),
+    pub separation: f64,
+    /// Shortest of the free-fall time and the time to close their separation at their relative
+    /// speed.
+    pub timescale: f64,
+    pub tick: f64,
+}
+
+/// Finds the pair of active bodies with the shortest timescale, comparing every pair. Softening
+/// lengths are included in separations.
+pub fn tightest(bodies: &[Body]) -> Option<Tightest> {
+    let mut tightest: Option<Tightest> = None;
+    for (i, a) in bodies.iter().enumerate() {
+        for (j, b) in bodies.iter().enumerate().skip(i + 1) {
+            if !a.is_active() || !b.is_active() || (a.test && b.test) {
+                continue;
+            }
+            let separation = (a.position - b.position).length();
+            let softened = separation.hypot(a.softening.max(b.softening));
+            let mass = (if a.test { 0. } else { a.mass }) + (if b.test { 0. } else { b.mass });
+            let free_fall = (softened.powi(3) / mass).sqrt();
+            let crossing = softened / (a.velocity - b.velocity).length();
+            let timescale = free_fall.min(crossing);
+            if timescale > 0.
+                && timescale.is_finite()
+                && tightest.as_ref().is_none_or(|t| timescale < t.timescale)
+            {
+                tightest = Some(Tightest {
+                    bodies: (i, j),
+                    separation,
+                    timescale,
+                    tick: TICK_FRACTION * timescale,
+                });
+            }
+        }
+    }
+    tightest
+}
@@ -0,0 +1,283 @@
+//! Checkpoints of a run in progress, from which it can be resumed exactly as if it had never
+//! stopped. Besides the bodies, they hold the state of the world which isn't in them, such as
+//! whether leapfrog velocities are offset by half a tick, and the quantities which the final report
+//! compares against.
+//!
+//! Checkpoints can also be serialised with serde, as they are in JSON by the CLI.
+//!
+//! All values are little-endian, and optional values are preceded by a byte which is 1 if they are
+//! present, in which case they follow, and 0 otherwise. A checkpoint has
+//!
+//! | Bytes    | Contents                                                                 |
+//! |----------|--------------------------------------------------------------------------|
+//! | 8        | Magic `NBODYCKP`                                                         |
+//! | 4        | Format version (u32), currently 3                                        |
+//! | 4 + n    | Name of the integrator, as a length (u32) and UTF-8                      |
+//! | 8        | Tick duration (f64)                                                      |
+//! | 1 + 8    | Optional gravitational constant (f64)                                    |
+//! | 8        | Simulation time (f64)                                                    |
+//! | 1        | Whether the integrator has started (u8)                                  |
+//! | 8        | Duration of the last tick (f64), from version 3                          |
+//! | 8        | Id of the next body to be added (u64), from version 2                    |
+//! | 1 + 32   | Optional cosmology: Hubble constant, Ωm, ΩΛ and scale factor (f64 each)  |
+//! | 8 + 32 n | Number of generators of stochastic forces (u64) and their states (4 u64) |
+//! | 8        | Number of ticks so far (u64)                                             |
+//! | 8        | Time the run started at (f64)                                            |
+//! | 16       | Initial energy and the energy correction (f64 each)                      |
+//! | 72       | Initial momentum, centre of mass and angular momentum (3 f64 each)       |
+//! | 8 + 8 n  | Number of escapers reported (u64) and their indices (u64 each)           |
+//!
+//! followed by the bodies as a [snapshot](crate::snapshot). Version 1 checkpoints have no next
+//! id, which is then the number of bodies, and versions before 3 have no last tick, which is then
+//! the tick duration.
+
+use std::io::{self, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::Body,
+    cosmology::Cosmology,
+    rng::Rng,
+    simulation::WorldState,
+    snapshot::{self, invalid, read_bytes, read_f64, read_text},
+    vec3::{Point3, Vec3},
+};
+
+pub const MAGIC: &[u8; 8] = b"NBODYCKP";
+const VERSION: u32 = 3;
+
+/// State of the run loop which isn't part of the world.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunState {
+    pub ticks: u64,
+    /// Time the run started at, before any checkpoint it was resumed from.
+    pub start: f64,
+    pub initial_energy: f64,
+    /// Energy changes due to adapting softening lengths and removing escapers rather than motion.
+    pub energy_correction: f64,
+    pub initial_momentum: Vec3,
+    pub initial_center: Point3,
+    pub initial_angular_momentum: Vec3,
+    /// Ids of escapers which have been reported, which aren't reported again.
+    pub escaped: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Name of the integrator, which a run must use to resume from the checkpoint.
+    pub integrator: String,
+    pub tick: f64,
+    pub g: Option<f64>,
+    pub world: WorldState,
+    pub run: RunState,
+    pub bodies: Vec<Body>,
+}
+
+impl Checkpoint {
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        let integrator = self.integrator.as_bytes();
+        writer.write_all(&(integrator.len() as u32).to_le_bytes())?;
+        writer.write_all(integrator)?;
+        writer.write_all(&self.tick.to_le_bytes())?;
+        write_optional(&mut writer, self.g.map(|g| [g]))?;
+
+        let world = &self.world;
+        writer.write_all(&world.time.to_le_bytes())?;
+        writer.write_all(&[world.started as u8])?;
+        writer.write_all(&world.tick.to_le_bytes())?;
+        writer.write_all(&(world.next_id as u64).to_le_bytes())?;
+        let cosmology = world
+            .cosmology
+            .map(|c| [c.hubble, c.omega_m, c.omega_lambda, c.scale_factor()]);
+        write_optional(&mut writer, cosmology)?;
+        writer.write_all(&(world.rngs.len() as u64).to_le_bytes())?;
+        for rng in &world.rngs {
+            for word in rng.state() {
+                writer.write_all(&word.to_le_bytes())?;
+            }
+        }
+
+        let run = &self.run;
+        writer.write_all(&run.ticks.to_le_bytes())?;
+        for value in [run.start, run.initial_energy, run.energy_correction] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        for v in [
+            run.initial_momentum,
+            run.initial_center,
+            run.initial_angular_momentum,
+        ] {
+            for k in 0..3 {
+                writer.write_all(&v[k].to_le_bytes())?;
+            }
+        }
+        writer.write_all(&(run.escaped.len() as u64).to_le_bytes())?;
+        for &i in &run.escaped {
+            writer.write_all(&(i as u64).to_le_bytes())?;
+        }
+
+        snapshot::write(writer, world.time, &self.bodies)
+    }
+
+    pub fn read(mut reader: impl Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a checkpoint"));
+        }
+        let version = u32::from_le_bytes(read_bytes(&mut reader)?);
+        if !(1..=VERSION).contains(&version) {
+            return Err(invalid(&format!(
+                "unsupported checkpoint version {}",
+                version
+            )));
+        }
+        let integrator = read_text(&mut reader)?.unwrap_or_default();
+        let tick = read_f64(&mut reader)?;
+        let g = read_optional::<1>(&mut reader)?.map(|[g]| g);
+
+        let time = read_f64(&mut reader)?;
+        let [started] = read_bytes(&mut reader)?;
+        let last_tick = match version {
+            1 | 2 => tick,
+            _ => read_f64(&mut reader)?,
+        };
+        let next_id = match version {
+            1 => None,
+            _ => Some(u64::from_le_bytes(read_bytes(&mut reader)?) as usize),
+        };
+        let cosmology = read_optional(&mut reader)?.map(|[hubble, omega_m, omega_lambda, a]| {
+            Cosmology::new(hubble, omega_m, omega_lambda, a)
+        });
+        let count = u64::from_le_bytes(read_bytes(&mut reader)?);
+        let rngs = (0..count)
+            .map(|_| {
+                let mut state = [0; 4];
+                for word in &mut state {
+                    *word = u64::from_le_bytes(read_bytes(&mut reader)?);
+                }
+                Ok(Rng::from_state(state))
+            })
+            .collect::<io::Result<_>>()?;
+
+        let ticks = u64::from_le_bytes(read_bytes(&mut reader)?);
+        let [start, initial_energy, energy_correction] = read_array(&mut reader)?;
+        let mut vector = || -> io::Result<Vec3> {
+            let [x, y, z] = read_array(&mut reader)?;
+            Ok(Vec3::new(x, y, z))
+        };
+        let (initial_momentum, initial_center, initial_angular_momentum) =
+            (vector()?, vector()?, vector()?);
+        let count = u64::from_le_bytes(read_bytes(&mut reader)?);
+        let escaped = (0..count)
+            .map(|_| Ok(u64::from_le_bytes(read_bytes(&mut reader)?) as usize))
+            .collect::<io::Result<_>>()?;
+        let run = RunState {
+            ticks,
+            start,
+            initial_energy,
+            energy_correction,
+            initial_momentum,
+            initial_center,
+            initial_angular_momentum,
+            escaped,
+        };
+
+        let (_, bodies) = snapshot::read(reader)?;
+        let world = WorldState {
+            time,
+            started: started != 0,
+            tick: last_tick,
+            next_id: next_id.unwrap_or(bodies.len()),
+            cosmology,
+            rngs,
+        };
+        Ok(Self {
+            integrator,
+            tick,
+            g,
+            world,
+            run,
+            bodies,
+        })
+    }
+}
+
+fn write_optional<const N: usize>(
+    writer: &mut impl Write,
+    values: Option<[f64; N]>,
+) -> io::Result<()> {
+    writer.write_all(&[values.is_some() as u8])?;
+    for value in values.into_iter().flatten() {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_optional<const N: usize>(reader: &mut impl Read) -> io::Result<Option<[f64; N]>> {
+    let [present] = read_bytes(reader)?;
+    match present {
+        0 => Ok(None),
+        _ => read_array(reader).map(Some),
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[f64; N]> {
+    let mut values = [0.; N];
+    for value in &mut values {
+        *value = read_f64(reader)?;
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_byte_for_byte() {
+        let mut bodies = vec![
+            Body::new(Point3::new(1., 2., 3.), Vec3::new(-0.1, 0.2, 0.), 1.5),
+            Body::new(Point3::new(-4., 0., 1e-300), Vec3::new(0., 0., 7.), 0.),
+        ];
+        bodies[0].id = 3;
+        bodies[1].id = 5;
+        bodies[1].name = Some("Probe".to_string());
+        let checkpoint = Checkpoint {
+            integrator: "leapfrog".to_string(),
+            tick: 1e-3,
+            g: Some(6.6743e-11),
+            world: WorldState {
+                time: 12.5,
+                started: true,
+                tick: 5e-4,
+                next_id: 6,
+                cosmology: Some(Cosmology::new(0.07, 0.3, 0.7, 0.5)),
+                rngs: vec![Rng::new(42)],
+            },
+            run: RunState {
+                ticks: 12_000,
+                start: 0.5,
+                initial_energy: -0.25,
+                energy_correction: 1e-9,
+                initial_momentum: Vec3::new(0., 1., 0.),
+                initial_center: Point3::new(0.1, 0., 0.),
+                initial_angular_momentum: Vec3::new(0., 0., 2.),
+                escaped: vec![4],
+            },
+            bodies,
+        };
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        let read = Checkpoint::read(bytes.as_slice()).unwrap();
+        assert_eq!(read.world.next_id, 6);
+        assert_eq!(read.run.escaped, [4]);
+        assert_eq!(read.bodies[1].id, 5);
+        let mut rewritten = Vec::new();
+        read.write(&mut rewritten).unwrap();
+        assert_eq!(rewritten, bytes);
+    }
+}
@@ -0,0 +1,278 @@
+use std::fmt::Display;
+
+use crate::{
+    body::{Body, State},
+    boundary::{self, PeriodicBox},
+    vec3::{Point3, Vec3},
+};
+
+/// How overlapping bodies are resolved.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum CollisionMode {
+    /// Merge into a single body, conserving mass and momentum
+    Merge,
+    /// Merge, also conserving angular momentum about the barycentre by displacing the merged body
+    /// perpendicular to its velocity. Any part which can't be conserved is reported.
+    MergeAngular,
+    /// Hard-sphere collision with a coefficient of restitution
+    Bounce,
+}
+
+/// A collision between the bodies with ids `first` and `second`.
+#[derive(Debug)]
+pub struct Collision {
+    pub mode: CollisionMode,
+    pub time: f64,
+    pub first: usize,
+    pub second: usize,
+    /// Angular momentum about the barycentre lost in an angular momentum conserving merger, in
+    /// units of G times angular momentum like the Gm masses.
+    pub residual: Option<Vec3>,
+}
+
+impl Display for Collision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let outcome = match self.mode {
+            CollisionMode::Merge | CollisionMode::MergeAngular => "merged",
+            CollisionMode::Bounce => "bounced",
+        };
+        write!(
+            f,
+            "Collision at t = {}: bodies {} and {} {}",
+            self.time, self.first, self.second, outcome
+        )?;
+        match self.residual {
+            Some(residual) => write!(f, " (angular momentum residual = [{:e}])", residual),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Resolves all pairs of overlapping active bodies, returning the collisions which occurred. The
+/// `restitution` coefficient only applies to bouncing collisions. Merged bodies are kept, but
+/// marked as merged into the body which absorbed them. In a periodic box, bodies collide with the
+/// nearest image of each other, so they can touch across a wall.
+pub fn resolve(
+    mode: CollisionMode,
+    restitution: f64,
+    bodies: &mut [Body],
+    time: f64,
+    periodic: Option<PeriodicBox>,
+) -> Vec<Collision> {
+    let mut collisions = Vec::new();
+    let barycentre = barycentre(bodies);
+    for j in 1..bodies.len() {
+        let (left, right) = bodies.split_at_mut(j);
+        let other = &mut right[0];
+        for body in left.iter_mut() {
+            if !body.is_active() || !other.is_active() {
+                continue;
+            }
+            // Bouncing bodies which are already separating are left to move apart
+            let separation = boundary::separation(periodic, body.position, other.position);
+            let separating =
+                matches!(mode, CollisionMode::Bounce) && !approaching(body, other, separation);
+            if separation.length() < body.radius + other.radius && !separating {
+                let mut residual = None;
+                match mode {
+                    CollisionMode::Merge => merge(body, other, periodic),
+                    CollisionMode::MergeAngular => {
+                        residual = Some(merge_angular(body, other, barycentre, periodic));
+                    }
+                    CollisionMode::Bounce => bounce(body, other, separation, restitution),
+                }
+                if let CollisionMode::Merge | CollisionMode::MergeAngular = mode {
+                    other.state = State::Merged { into: body.id };
+                }
+                collisions.push(Collision {
+                    mode,
+                    time,
+                    first: body.id,
+                    second: other.id,
+                    residual,
+                });
+            }
+        }
+    }
+    collisions
+}
+
+/// Whether `b` is moving towards `a`, given the `separation` from `a` to `b`.
+fn approaching(a: &Body, b: &Body, separation: Vec3) -> bool {
+    Vec3::dot(&(b.velocity - a.velocity), &separation) < 0.
+}
+
+/// Merges `other` into `body` at their centre of mass, conserving mass, momentum, charge and the
+/// rate of change of mass. The merged body keeps the combined volume and the larger softening
+/// length, and is a source of gravity if either body was. If either body is fixed, the merged
+/// body stays fixed in its place. In a periodic box, the centre of mass is that of `body` and the
+/// nearest image of `other`, wrapped back into the box.
+fn merge(body: &mut Body, other: &Body, periodic: Option<PeriodicBox>) {
+    let mass = body.mass + other.mass;
+    let other_position = boundary::nearest_image(periodic, body.position, other.position);
+    if body.fixed || other.fixed {
+        if !body.fixed {
+            body.position = other.position;
+            body.velocity = other.velocity;
+            body.fixed = true;
+        }
+    } else if mass > 0. {
+        body.position = (body.mass * body.position + other.mass * other_position) / mass;
+        body.velocity = (body.mass * body.velocity + other.mass * other.velocity) / mass;
+    } else {
+        body.position = (body.position + other_position) / 2.;
+        body.velocity = (body.velocity + other.velocity) / 2.;
+    }
+    if let Some(periodic) = periodic {
+        body.position = periodic.wrap(body.position);
+    }
+    if other.is_source() {
+        body.test = false;
+    }
+    body.mass = mass;
+    body.mass_rate += other.mass_rate;
+    body.charge += other.charge;
+    body.softening = body.softening.max(other.softening);
+    body.radius = (body.radius.powi(3) + other.radius.powi(3)).cbrt();
+}
+
+/// Position and velocity of the barycentre of the active bodies.
+fn barycentre(bodies: &[Body]) -> (Point3, Vec3) {
+    let active = || bodies.iter().filter(|body| body.is_active());
+    let mass: f64 = active().map(|body| body.mass).sum();
+    if mass == 0. {
+        return (Point3::ZERO, Vec3::ZERO);
+    }
+    let position: Point3 = active().map(|body| body.mass * body.position).sum();
+    let velocity: Vec3 = active().map(|body| body.mass * body.velocity).sum();
+    (position / mass, velocity / mass)
+}
+
+fn angular_momentum(body: &Body, (position, velocity): (Point3, Vec3)) -> Vec3 {
+    body.mass * Vec3::cross(&(body.position - position), &(body.velocity - velocity))
+}
+
+/// Merges `other` into `body` like `merge`, then displaces the merged body perpendicular to its
+/// velocity relative to the `barycentre` so that the angular momentum of the pair about it is
+/// conserved. Returns the angular momentum which couldn't be conserved: the part along that
+/// velocity, or all of it for fixed, massless or stationary merged bodies.
+fn merge_angular(
+    body: &mut Body,
+    other: &Body,
+    barycentre: (Point3, Vec3),
+    periodic: Option<PeriodicBox>,
+) -> Vec3 {
+    let before = angular_momentum(body, barycentre) + angular_momentum(other, barycentre);
+    merge(body, other, periodic);
+    let velocity = body.velocity - barycentre.1;
+    if !body.fixed && body.mass != 0. && velocity.length_squared() != 0. {
+        let lost = before - angular_momentum(body, barycentre);
+        body.position += Vec3::cross(&velocity, &lost) / (body.mass * velocity.length_squared());
+    }
+    before - angular_momentum(body, barycentre)
+}
+
+/// Applies the impulse of a hard-sphere collision with coefficient of restitution `restitution`
+/// along the line of centres, given by the `separation` from `a` to `b`, conserving momentum.
+/// Fixed bodies act as if infinitely massive.
+fn bounce(a: &mut Body, b: &mut Body, separation: Vec3, restitution: f64) {
+    let normal = Vec3::unitv(&separation);
+    let approach_speed = Vec3::dot(&(b.velocity - a.velocity), &normal);
+    let (a_share, b_share) = if a.fixed || b.fixed {
+        (f64::from(!a.fixed), f64::from(!b.fixed))
+    } else if a.mass + b.mass > 0. {
+        (b.mass / (a.mass + b.mass), a.mass / (a.mass + b.mass))
+    } else {
+        (0.5, 0.5)
+    };
+    a.velocity += ((1. + restitution) * approach_speed * a_share) * normal;
+    b.velocity -= ((1. + restitution) * approach_speed * b_share) * normal;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two bodies on either side of the wall of a unit box at x = ±1/2, moving towards it.
+    fn across_wall() -> Vec<Body> {
+        let mut bodies = vec![
+            Body::new(Point3::new(0.49, 0., 0.), Vec3::new(1., 0., 0.), 1.),
+            Body::new(Point3::new(-0.49, 0., 0.), Vec3::new(-1., 0., 0.), 3.),
+        ];
+        for (id, body) in bodies.iter_mut().enumerate() {
+            body.id = id;
+            body.radius = 0.015;
+        }
+        bodies
+    }
+
+    /// Two overlapping bodies of unequal masses moving obliquely towards each other.
+    fn head_on() -> Vec<Body> {
+        let mut bodies = vec![
+            Body::new(Point3::new(0., 0., 0.), Vec3::new(0.5, 0.2, -0.1), 2.),
+            Body::new(Point3::new(0.08, 0.03, 0.), Vec3::new(-1., 0.3, 0.4), 0.5),
+        ];
+        for (id, body) in bodies.iter_mut().enumerate() {
+            body.id = id;
+            body.radius = 0.05;
+        }
+        bodies
+    }
+
+    fn momentum(bodies: &[Body]) -> Vec3 {
+        (bodies.iter().filter(|body| body.is_active()))
+            .map(|body| body.mass * body.velocity)
+            .sum()
+    }
+
+    #[test]
+    fn merging_conserves_mass_and_momentum() {
+        let mut bodies = head_on();
+        let before = momentum(&bodies);
+        let collisions = resolve(CollisionMode::Merge, 1., &mut bodies, 0., None);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(bodies[1].state, State::Merged { into: 0 });
+        assert_eq!(bodies[0].mass, 2.5);
+        assert!((momentum(&bodies) - before).length() < 1e-15);
+    }
+
+    fn kinetic_energy(bodies: &[Body]) -> f64 {
+        (bodies.iter())
+            .map(|body| 0.5 * body.mass * body.velocity.length_squared())
+            .sum()
+    }
+
+    #[test]
+    fn bouncing_loses_the_energy_of_restitution() {
+        for restitution in [1., 0.6, 0.] {
+            let mut bodies = head_on();
+            let (momentum_before, energy_before) = (momentum(&bodies), kinetic_energy(&bodies));
+            let normal = Vec3::unitv(&(bodies[1].position - bodies[0].position));
+            let approach_speed = Vec3::dot(&(bodies[1].velocity - bodies[0].velocity), &normal);
+            let reduced_mass = bodies[0].mass * bodies[1].mass / (bodies[0].mass + bodies[1].mass);
+            resolve(CollisionMode::Bounce, restitution, &mut bodies, 0., None);
+            // Only the approach along the line of centres loses energy, by a fraction 1 - e²
+            let lost = 0.5 * reduced_mass * (1. - restitution.powi(2)) * approach_speed.powi(2);
+            assert!((momentum(&bodies) - momentum_before).length() < 1e-15);
+            assert!((kinetic_energy(&bodies) - (energy_before - lost)).abs() < 1e-15);
+        }
+    }
+
+    #[test]
+    fn bodies_collide_across_a_periodic_wall() {
+        let periodic = Some(PeriodicBox { size: 1. });
+        let mut bodies = across_wall();
+        assert!(resolve(CollisionMode::Merge, 1., &mut bodies, 0., None).is_empty());
+        let collisions = resolve(CollisionMode::Merge, 1., &mut bodies, 0., periodic);
+        assert_eq!(collisions.len(), 1);
+        // The centre of mass of 0.49 and the image of -0.49 at 0.51, wrapped into the box
+        assert!((bodies[0].position.x() - -0.495).abs() < 1e-12);
+        assert_eq!(bodies[1].state, State::Merged { into: 0 });
+
+        let mut bodies = across_wall();
+        resolve(CollisionMode::Bounce, 1., &mut bodies, 0., periodic);
+        // An elastic bounce along the line through the wall, which stops the heavier body
+        assert!((bodies[0].velocity - Vec3::new(-2., 0., 0.)).length() < 1e-12);
+        assert!(bodies[1].velocity.length() < 1e-12);
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{bufread::MultiGzDecoder, write::GzEncoder};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression of output files.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        }
+    }
+}
+
+/// Creates a file for writing, compressed if `compression` is given, in which case its extension
+/// is appended to the path. The file is written as an [`AtomicFile`], so it only replaces any file
+/// at the path once finished, and is synced to disk first if `sync`. Each flush ends a gzip member
+/// or zstd frame, so that the temporary file can be decompressed up to the last flush even if it
+/// is never finished.
+pub fn create(
+    path: impl AsRef<Path>,
+    compression: Option<Compression>,
+    sync: bool,
+) -> io::Result<OutputFile> {
+    let path = path.as_ref();
+    let Some(compression) = compression else {
+        return Ok(OutputFile::Plain(AtomicFile::create(path, sync)?));
+    };
+    let file = AtomicFile::create(compressed_path(path, Some(compression)), sync)?;
+    Ok(OutputFile::Compressed(Framed {
+        compression,
+        encoder: Some(Encoder::new(compression, file)?),
+        written: false,
+    }))
+}
+
+/// Path of the file written by [`create`] for `path`, with the extension of any `compression`
+/// appended.
+pub fn compressed_path(path: impl AsRef<Path>, compression: Option<Compression>) -> PathBuf {
+    let mut name = OsString::from(path.as_ref());
+    if let Some(compression) = compression {
+        name.push(".");
+        name.push(compression.extension());
+    }
+    PathBuf::from(name)
+}
+
+/// File created by [`create`], which must be finished to appear at its path.
+pub enum OutputFile {
+    Plain(AtomicFile),
+    Compressed(Framed),
+}
+
+impl OutputFile {
+    /// Finishes any compressed frame and replaces the file at the path with this one.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.finish(),
+            OutputFile::Compressed(mut framed) => match framed.encoder.take() {
+                Some(encoder) => encoder.finish()?.finish(),
+                None => Err(io::Error::other(
+                    "compressed file failed to start a new frame",
+                )),
+            },
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Compressed(framed) => framed.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Compressed(framed) => framed.flush(),
+        }
+    }
+}
+
+/// File written under a temporary name beside its path, which replaces any file at the path once
+/// finished. A run which stops or crashes while writing the file leaves the previous one intact,
+/// and what it had written in the temporary file, rather than a truncated file at the path.
+pub struct AtomicFile {
+    file: BufWriter<File>,
+    temporary: PathBuf,
+    path: PathBuf,
+    /// Whether to sync the file to disk before renaming it, so that it also survives a crash of
+    /// the machine.
+    sync: bool,
+}
+
+impl AtomicFile {
+    /// Creates the temporary file for `path`, which is named after it with `.tmp` appended.
+    pub fn create(path: impl AsRef<Path>, sync: bool) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let temporary = temporary_path(&path);
+        Ok(Self {
+            file: BufWriter::new(File::create(&temporary)?),
+            temporary,
+            path,
+            sync,
+        })
+    }
+
+    /// Flushes the file and renames it to its path.
+    pub fn finish(self) -> io::Result<()> {
+        let file = self.file.into_inner().map_err(|e| e.into_error())?;
+        if self.sync {
+            file.sync_all()?;
+        }
+        drop(file);
+        rename(&self.temporary, &self.path, self.sync)
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Path of the temporary file which is written in place of `path` and then renamed to it.
+pub fn temporary_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut temporary = OsString::from(path.as_ref());
+    temporary.push(".tmp");
+    PathBuf::from(temporary)
+}
+
+/// Replaces `path` with the finished file at `temporary`. If `sync`, the rename is synced to disk
+/// along with the directory, where the platform allows it.
+pub fn rename(temporary: &Path, path: &Path, sync: bool) -> io::Result<()> {
+    fs::rename(temporary, path)?;
+    #[cfg(unix)]
+    if sync {
+        let directory = path.parent().filter(|p| !p.as_os_str().is_empty());
+        File::open(directory.unwrap_or(Path::new(".")))?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Opens a file for reading, decompressing it if it starts with a gzip or zstd header.
+pub fn open(path: impl AsRef<Path>) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let header = reader.fill_buf()?;
+    Ok(if header.starts_with(GZIP_MAGIC) {
+        Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+    } else if header.starts_with(ZSTD_MAGIC) {
+        Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Box::new(reader)
+    })
+}
+
+/// Compressed writer which starts a new gzip member or zstd frame after each flush. Concatenated
+/// members and frames decompress to the concatenation of their contents.
+pub struct Framed {
+    compression: Compression,
+    encoder: Option<Encoder>,
+    /// Whether anything has been written since the last frame was finished.
+    written: bool,
+}
+
+enum Encoder {
+    Gzip(GzEncoder<AtomicFile>),
+    Zstd(zstd::Encoder<'static, AtomicFile>),
+}
+
+impl Encoder {
+    fn new(compression: Compression, file: AtomicFile) -> io::Result<Self> {
+        Ok(match compression {
+            Compression::Gzip => {
+                Encoder::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    fn finish(self) -> io::Result<AtomicFile> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl Write for Framed {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written = true;
+        match self.encoder.as_mut() {
+            Some(Encoder::Gzip(encoder)) => encoder.write(buf),
+            Some(Encoder::Zstd(encoder)) => encoder.write(buf),
+            None => Err(io::Error::other(
+                "compressed file failed to start a new frame",
+            )),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.written {
+            return Ok(());
+        }
+        let Some(encoder) = self.encoder.take() else {
+            return Ok(());
+        };
+        let mut file = encoder.finish()?;
+        file.flush()?;
+        self.encoder = Some(Encoder::new(self.compression, file)?);
+        self.written = false;
+        Ok(())
+    }
+}
+
+impl Drop for Framed {
+    fn drop(&mut self) {
+        // Errors are ignored, as for BufWriter, so writers should be flushed before being dropped
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, process};
+
+    use super::*;
+
+    #[test]
+    fn files_appear_only_once_finished() {
+        let directory = std::env::temp_dir().join(format!("rs-nbody-compress-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("output.csv");
+        let compressed = directory.join("output.csv.zst");
+        fs::write(&compressed, "previous").unwrap();
+
+        let mut file = create(&path, Some(Compression::Zstd), false).unwrap();
+        file.write_all(b"first\n").unwrap();
+        file.flush().unwrap();
+        file.write_all(b"second\n").unwrap();
+        file.flush().unwrap();
+        assert_eq!(fs::read_to_string(&compressed).unwrap(), "previous");
+
+        file.finish().unwrap();
+        assert!(!temporary_path(&compressed).exists());
+        let mut contents = String::new();
+        open(&compressed)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "first\nsecond\n");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
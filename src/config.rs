@@ -0,0 +1,57 @@
+use std::iter::repeat_n;
+
+use clap::{ArgAction, Command};
+use toml::{Table, Value};
+
+/// Converts a TOML table of options into command-line arguments for `command`, so that every flag
+/// can be given in a config file. Keys are long flag names, with `-` or `_` between words, e.g.
+/// `tick = 0.01`, `sim = "leapfrog"` or `potential = ["kepler:mass=1", "harmonic:omega=2"]`.
+/// Options for which `given` is true, because they were given on the command line, are skipped so
+/// that the command line overrides the file.
+pub fn arguments(
+    command: &Command,
+    config: &Table,
+    given: impl Fn(&str) -> bool,
+) -> Result<Vec<String>, String> {
+    let mut arguments = Vec::new();
+    for (key, value) in config {
+        let id = key.replace('-', "_");
+        let arg = (command.get_arguments())
+            .find(|arg| arg.get_id() == id.as_str() || arg.get_long() == Some(key.as_str()))
+            .filter(|arg| arg.get_long().is_some() && arg.get_id() != "config")
+            .ok_or_else(|| format!("unknown option `{}`", key))?;
+        if given(arg.get_id().as_str()) {
+            continue;
+        }
+        let flag = format!("--{}", arg.get_long().unwrap());
+        let invalid = |expected: &str| format!("`{}` must be {}", key, expected);
+        match arg.get_action() {
+            ArgAction::SetTrue => match value {
+                Value::Boolean(true) => arguments.push(flag),
+                Value::Boolean(false) => {}
+                _ => return Err(invalid("true or false")),
+            },
+            ArgAction::Count => match value {
+                Value::Integer(n) if *n >= 0 => arguments.extend(repeat_n(flag, *n as usize)),
+                _ => return Err(invalid("a non-negative integer")),
+            },
+            _ => {
+                let values = match value {
+                    Value::Array(values) => values.iter().collect(),
+                    value => vec![value],
+                };
+                for value in values {
+                    let value = match value {
+                        Value::String(s) => s.clone(),
+                        Value::Integer(n) => n.to_string(),
+                        Value::Float(x) => x.to_string(),
+                        Value::Boolean(b) => b.to_string(),
+                        _ => return Err(invalid("a string, number, boolean or array of them")),
+                    };
+                    arguments.push(format!("{}={}", flag, value));
+                }
+            }
+        }
+    }
+    Ok(arguments)
+}
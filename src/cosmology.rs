@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{body::Body, vec3::Vec3};
+
+/// Friedmann background for integrating in comoving coordinates x = r / a(t). Velocities are
+/// comoving, dx/dt, so that bodies obey ẍ = g / a³ - 2Hẋ, where g is the acceleration from the
+/// forces evaluated at comoving positions and -2Hẋ is the Hubble drag.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Cosmology {
+    /// Hubble constant, the Hubble parameter at a = 1.
+    pub hubble: f64,
+    /// Matter density parameter at a = 1.
+    pub omega_m: f64,
+    /// Cosmological constant density parameter at a = 1.
+    pub omega_lambda: f64,
+    scale_factor: f64,
+}
+
+impl Cosmology {
+    pub fn new(hubble: f64, omega_m: f64, omega_lambda: f64, scale_factor: f64) -> Self {
+        Self {
+            hubble,
+            omega_m,
+            omega_lambda,
+            scale_factor,
+        }
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Hubble parameter H = ȧ/a at scale factor `a`, including any curvature.
+    pub fn hubble_parameter(&self, a: f64) -> f64 {
+        let omega_k = 1. - self.omega_m - self.omega_lambda;
+        self.hubble * (self.omega_m / (a * a * a) + omega_k / (a * a) + self.omega_lambda).sqrt()
+    }
+
+    /// Advances the scale factor by `duration` with a fourth order Runge-Kutta step of ȧ = aH.
+    pub fn advance(&mut self, duration: f64) {
+        let rate = |a: f64| a * self.hubble_parameter(a);
+        let a = self.scale_factor;
+        let k1 = rate(a);
+        let k2 = rate(a + duration / 2. * k1);
+        let k3 = rate(a + duration / 2. * k2);
+        let k4 = rate(a + duration * k3);
+        self.scale_factor = a + duration / 6. * (k1 + 2. * k2 + 2. * k3 + k4);
+    }
+
+    /// Converts `accelerations` from the forces into comoving accelerations, including the Hubble
+    /// drag. Bodies which don't move are left unaccelerated.
+    pub fn comoving_accelerations(&self, bodies: &[Body], accelerations: &mut [Vec3]) {
+        let a = self.scale_factor;
+        let drag = 2. * self.hubble_parameter(a);
+        for (body, acceleration) in bodies.iter().zip(accelerations) {
+            if body.is_mobile() {
+                *acceleration = *acceleration / (a * a * a) - drag * body.velocity;
+            }
+        }
+    }
+}
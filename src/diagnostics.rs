@@ -0,0 +1,99 @@
+use std::{io::Read, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analysis,
+    compress::{self, Compression, OutputFile},
+    simulation::Simulation,
+};
+
+/// Writes a history of the conserved quantities and structure of a simulation to a CSV file, one
+/// row per logging interval. The file only appears at its path once finished.
+pub struct DiagnosticsLog {
+    writer: csv::Writer<OutputFile>,
+    /// Value of G by which quantities in Gm units are divided.
+    g: f64,
+}
+
+/// Row of a diagnostics file, with quantities in Gm units unless G was known.
+#[derive(Serialize, Deserialize)]
+pub struct DiagnosticsRow {
+    pub time: f64,
+    pub energy: f64,
+    pub momentum_x: f64,
+    pub momentum_y: f64,
+    pub momentum_z: f64,
+    pub angular_momentum_x: f64,
+    pub angular_momentum_y: f64,
+    pub angular_momentum_z: f64,
+    pub virial_ratio: f64,
+    pub density_center_x: f64,
+    pub density_center_y: f64,
+    pub density_center_z: f64,
+    /// Radii about the density centre enclosing 10%, 50% and 90% of the mass.
+    pub lagrangian_radius_10: f64,
+    pub lagrangian_radius_50: f64,
+    pub lagrangian_radius_90: f64,
+}
+
+impl DiagnosticsLog {
+    pub fn create(
+        path: impl AsRef<Path>,
+        g: f64,
+        compression: Option<Compression>,
+        sync: bool,
+    ) -> Result<Self, csv::Error> {
+        Ok(Self {
+            writer: csv::Writer::from_writer(compress::create(path, compression, sync)?),
+            g,
+        })
+    }
+
+    /// Appends the current state of `world`. `energy_correction` is subtracted from the total
+    /// energy, as for the final report.
+    pub fn record(
+        &mut self,
+        world: &impl Simulation,
+        energy_correction: f64,
+    ) -> Result<(), csv::Error> {
+        let momentum = world.total_momentum() / self.g;
+        let angular_momentum = world.angular_momentum() / self.g;
+        let center = analysis::density_center(world.bodies());
+        let radii = analysis::lagrangian_radii(world.bodies(), center, &[0.1, 0.5, 0.9]);
+        self.writer.serialize(DiagnosticsRow {
+            time: world.time(),
+            energy: (world.total_energy() - energy_correction) / self.g,
+            momentum_x: momentum.x(),
+            momentum_y: momentum.y(),
+            momentum_z: momentum.z(),
+            angular_momentum_x: angular_momentum.x(),
+            angular_momentum_y: angular_momentum.y(),
+            angular_momentum_z: angular_momentum.z(),
+            virial_ratio: world.virial_ratio(),
+            density_center_x: center.x(),
+            density_center_y: center.y(),
+            density_center_z: center.z(),
+            lagrangian_radius_10: radii[0],
+            lagrangian_radius_50: radii[1],
+            lagrangian_radius_90: radii[2],
+        })?;
+        // Keep the temporary file complete so far in case the run is interrupted
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Flushes the file and replaces its path with it.
+    pub fn finish(self) -> Result<(), csv::Error> {
+        self.writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .finish()?;
+        Ok(())
+    }
+}
+
+/// Reads a diagnostics file written by a `DiagnosticsLog`.
+pub fn read(reader: impl Read) -> Result<Vec<DiagnosticsRow>, csv::Error> {
+    csv::Reader::from_reader(reader).deserialize().collect()
+}
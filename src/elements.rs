@@ -0,0 +1,105 @@
+use crate::vec3::{Point3, Vec3};
+
+/// Keplerian orbital elements of a body relative to its primary, with angles in radians.
+#[derive(Clone, Copy, Debug)]
+pub struct Elements {
+    /// Semi-major axis, negative for hyperbolic orbits.
+    pub a: f64,
+    /// Eccentricity, which may be anything but 1 since parabolic orbits have no semi-major axis.
+    pub e: f64,
+    /// Inclination.
+    pub i: f64,
+    /// Longitude of the ascending node, Ω.
+    pub node: f64,
+    /// Argument of periapsis, ω.
+    pub periapsis: f64,
+    /// Mean anomaly, M.
+    pub anomaly: f64,
+}
+
+impl Elements {
+    /// Checks that the semi-major axis and eccentricity describe an elliptic or hyperbolic orbit.
+    pub fn check(&self) -> Result<(), String> {
+        let Elements { a, e, .. } = *self;
+        if !(e >= 0. && e != 1.) {
+            return Err(format!("eccentricity {} is not supported", e));
+        }
+        if (e < 1.) != (a > 0.) {
+            return Err(format!(
+                "semi-major axis {} must be {} for an eccentricity of {}",
+                a,
+                if e < 1. { "positive" } else { "negative" },
+                e
+            ));
+        }
+        Ok(())
+    }
+
+    /// Position and velocity relative to the primary, where `mu` is the sum of the Gm of the body
+    /// and its primary.
+    pub fn to_cartesian(&self, mu: f64) -> Result<(Point3, Vec3), String> {
+        self.check()?;
+        let Elements { a, e, .. } = *self;
+
+        // Position and velocity in the perifocal frame, with x towards periapsis
+        let (position, velocity) = if e < 1. {
+            let anomaly = solve_kepler(self.anomaly.rem_euclid(std::f64::consts::TAU), e);
+            let (sin, cos) = anomaly.sin_cos();
+            let b = a * (1. - e * e).sqrt();
+            let r = a * (1. - e * cos);
+            let speed = (mu * a).sqrt() / r;
+            (
+                Vec3::new(a * (cos - e), b * sin, 0.),
+                Vec3::new(-speed * sin, speed * b / a * cos, 0.),
+            )
+        } else {
+            let a = -a;
+            let anomaly = solve_hyperbolic_kepler(self.anomaly, e);
+            let (sinh, cosh) = (anomaly.sinh(), anomaly.cosh());
+            let b = a * (e * e - 1.).sqrt();
+            let r = a * (e * cosh - 1.);
+            let speed = (mu * a).sqrt() / r;
+            (
+                Vec3::new(a * (e - cosh), b * sinh, 0.),
+                Vec3::new(-speed * sinh, speed * b / a * cosh, 0.),
+            )
+        };
+
+        let rotate = |v: Vec3| {
+            let (sin_w, cos_w) = self.periapsis.sin_cos();
+            let (sin_i, cos_i) = self.i.sin_cos();
+            let (sin_o, cos_o) = self.node.sin_cos();
+            // Rotation by ω about z, then i about x, then Ω about z
+            let (x, y) = (cos_w * v.x() - sin_w * v.y(), sin_w * v.x() + cos_w * v.y());
+            let (y, z) = (cos_i * y, sin_i * y);
+            Vec3::new(cos_o * x - sin_o * y, sin_o * x + cos_o * y, z)
+        };
+        Ok((rotate(position), rotate(velocity)))
+    }
+}
+
+/// Eccentric anomaly E for which E - e sin E = `mean`, by Newton's method.
+fn solve_kepler(mean: f64, e: f64) -> f64 {
+    let mut anomaly = if e < 0.8 { mean } else { std::f64::consts::PI };
+    for _ in 0..100 {
+        let step = (anomaly - e * anomaly.sin() - mean) / (1. - e * anomaly.cos());
+        anomaly -= step;
+        if step.abs() < 1e-15 {
+            break;
+        }
+    }
+    anomaly
+}
+
+/// Hyperbolic anomaly H for which e sinh H - H = `mean`, by Newton's method.
+fn solve_hyperbolic_kepler(mean: f64, e: f64) -> f64 {
+    let mut anomaly = (mean / e).asinh();
+    for _ in 0..100 {
+        let step = (e * anomaly.sinh() - anomaly - mean) / (e * anomaly.cosh() - 1.);
+        anomaly -= step;
+        if step.abs() < 1e-15 * anomaly.abs().max(1.) {
+            break;
+        }
+    }
+    anomaly
+}
@@ -0,0 +1,247 @@
+use std::thread;
+
+use crate::{
+    body::Body,
+    rng::Rng,
+    sensitivity::{deviation, rms},
+    simulation::Simulation,
+    trajectory::OutputSchedule,
+};
+
+/// Copies of a system with their initial conditions perturbed at random, for measuring how quickly
+/// nearby trajectories diverge.
+#[derive(Clone, Copy, Debug)]
+pub struct Ensemble {
+    pub members: usize,
+    /// Standard deviation of the perturbation of each position component, relative to the RMS
+    /// initial distance of the bodies.
+    pub position: f64,
+    /// Standard deviation of the perturbation of each velocity component, relative to the RMS
+    /// initial speed of the bodies.
+    pub velocity: f64,
+    /// Simulation time between rows of statistics.
+    pub every: f64,
+    /// Number of members run at once.
+    pub threads: usize,
+}
+
+impl Ensemble {
+    /// Runs the simulation unperturbed and then once per member with its initial positions and
+    /// velocities perturbed, and prints statistics of how far the members have diverged from the
+    /// unperturbed run at each output, along with an estimate of the largest Lyapunov exponent.
+    ///
+    /// Divergences are measured in units of the RMS initial distance and speed of the bodies, as
+    /// for [`analyze`](crate::sensitivity::analyze). A member in which a collision is resolved
+    /// differently has diverged completely, and is counted separately rather than in the
+    /// statistics.
+    pub fn run<S: Simulation>(
+        &self,
+        new_world: impl Fn(Vec<Body>) -> S + Sync,
+        bodies: &[Body],
+        rng: &mut Rng,
+        duration: f64,
+        tick_duration: f64,
+    ) {
+        let Ensemble {
+            members,
+            position,
+            velocity,
+            ..
+        } = *self;
+        let (times, divergences) =
+            self.divergences(new_world, bodies, rng, duration, tick_duration);
+        println!(
+            "Divergence of {} members from the unperturbed run with relative perturbations of {:e} in \
+             position and {:e} in velocity",
+            members, position, velocity
+        );
+        println!(
+            "{:>14} {:>14} {:>14} {:>14} {:>14} {:>9}",
+            "time", "geometric mean", "min", "median", "max", "collided"
+        );
+        let mut growth = Vec::new();
+        for (j, time) in times.iter().enumerate() {
+            let mut sample: Vec<f64> = (divergences.iter())
+                .map(|member| member[j])
+                .filter(|divergence| divergence.is_finite())
+                .collect();
+            sample.sort_by(f64::total_cmp);
+            let collided = members - sample.len();
+            if sample.is_empty() {
+                println!(
+                    "{:>14} {:>14} {:>14} {:>14} {:>14} {:>9}",
+                    time, "-", "-", "-", "-", collided
+                );
+                continue;
+            }
+            let geometric_mean = (sample
+                .iter()
+                .map(|d| d.max(f64::MIN_POSITIVE).ln())
+                .sum::<f64>()
+                / sample.len() as f64)
+                .exp();
+            growth.push((*time, geometric_mean.ln()));
+            println!(
+                "{:>14} {:>14.6e} {:>14.6e} {:>14.6e} {:>14.6e} {:>9}",
+                time,
+                geometric_mean,
+                sample[0],
+                sample[sample.len() / 2],
+                sample[sample.len() - 1],
+                collided
+            );
+        }
+        if let Some(exponent) = slope(&growth) {
+            println!(
+                "Lyapunov exponent estimate: {:.6e} (e-folding time {:.6e})",
+                exponent,
+                1. / exponent
+            );
+        }
+    }
+
+    /// Times of the outputs, and the divergence of each member from the unperturbed run at each
+    /// of them, which is infinite once a collision has been resolved differently. These are the
+    /// same whatever the number of threads.
+    pub fn divergences<S: Simulation>(
+        &self,
+        new_world: impl Fn(Vec<Body>) -> S + Sync,
+        bodies: &[Body],
+        rng: &mut Rng,
+        duration: f64,
+        tick_duration: f64,
+    ) -> (Vec<f64>, Vec<Vec<f64>>) {
+        let Ensemble {
+            members,
+            position,
+            velocity,
+            every,
+            threads,
+        } = *self;
+        // Calls `observe` with the world at each output
+        let simulate = |bodies: Vec<Body>, observe: &mut dyn FnMut(&S)| {
+            let mut world = new_world(bodies);
+            let mut schedule = OutputSchedule::new(Some(every));
+            loop {
+                if schedule.due(world.time(), tick_duration).is_some() {
+                    observe(&world);
+                }
+                if world.time() >= duration {
+                    break;
+                }
+                world.tick(tick_duration);
+            }
+        };
+        let length = rms(bodies, |body| body.position.length());
+        let speed = rms(bodies, |body| body.velocity.length());
+
+        let mut reference = Vec::new();
+        simulate(bodies.to_vec(), &mut |world| {
+            reference.push((world.time(), world.bodies().clone()))
+        });
+
+        // Perturbations are drawn up front so that they don't depend on the number of threads
+        let initial: Vec<Vec<Body>> = (0..members)
+            .map(|_| {
+                let mut perturbed = bodies.to_vec();
+                for body in &mut perturbed {
+                    for k in 0..3 {
+                        body.position[k] += position * length * rng.normal();
+                        body.velocity[k] += velocity * speed * rng.normal();
+                    }
+                }
+                perturbed
+            })
+            .collect();
+        let diverge = |bodies: Vec<Body>| {
+            let mut divergences = Vec::with_capacity(reference.len());
+            simulate(bodies, &mut |world| {
+                let (_, reference) = &reference[divergences.len()];
+                divergences.push(deviation(world.bodies(), reference, length, speed));
+            });
+            divergences
+        };
+        let threads = threads.clamp(1, members.max(1));
+        let mut divergences = vec![Vec::new(); members];
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|worker| {
+                    let (initial, diverge) = (&initial, &diverge);
+                    scope.spawn(move || {
+                        (worker..members)
+                            .step_by(threads)
+                            .map(|i| (i, diverge(initial[i].clone())))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            for worker in workers {
+                for (i, member) in worker.join().unwrap() {
+                    divergences[i] = member;
+                }
+            }
+        });
+        let times = reference.iter().map(|(time, _)| *time).collect();
+        (times, divergences)
+    }
+}
+
+/// Least squares slope of `points`, or None if there are fewer than two distinct abscissae.
+fn slope(points: &[(f64, f64)]) -> Option<f64> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = (points.iter())
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    (sxx > 0.).then(|| sxy / sxx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        force::{Force, Gravity},
+        leapfrog,
+        vec3::{Point3, Vec3},
+    };
+
+    /// Divergences of a deterministic three-body ensemble run on `threads` threads, as bit
+    /// patterns so that they can be compared exactly.
+    fn divergences(threads: usize) -> (Vec<u64>, Vec<Vec<u64>>) {
+        let bodies = vec![
+            Body::new(Point3::new(1., 0., 0.), Vec3::new(0., 0.3, 0.1), 1.),
+            Body::new(Point3::new(-0.5, 0.8, 0.), Vec3::new(-0.2, 0., 0.), 2.),
+            Body::new(Point3::new(0., -1., 0.5), Vec3::new(0.1, -0.1, 0.), 0.5),
+        ];
+        let ensemble = Ensemble {
+            members: 5,
+            position: 1e-6,
+            velocity: 1e-6,
+            every: 0.25,
+            threads,
+        };
+        let new_world = |bodies| {
+            let gravity = Gravity {
+                deterministic: true,
+                ..Default::default()
+            };
+            let forces: Vec<Box<dyn Force>> = vec![Box::new(gravity)];
+            leapfrog::World::new(bodies, forces)
+        };
+        let (times, divergences) =
+            ensemble.divergences(new_world, &bodies, &mut Rng::new(7), 1., 1e-3);
+        let bits = |values: &[f64]| values.iter().map(|v| v.to_bits()).collect();
+        (bits(&times), divergences.iter().map(|d| bits(d)).collect())
+    }
+
+    #[test]
+    fn divergences_do_not_depend_on_threads() {
+        let single = divergences(1);
+        assert_eq!(single.1.len(), 5);
+        for threads in [2, 3, 8] {
+            assert_eq!(divergences(threads), single);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    error, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+/// Errors which stop the command line program, each with its own exit status.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Options which are invalid or can't be used together.
+    #[error("{0}")]
+    InvalidArguments(String),
+    /// A file to read which doesn't exist.
+    #[error("{} does not exist", .0.display())]
+    MissingFile(PathBuf),
+    /// Initial conditions which couldn't be parsed.
+    #[error(
+        "unable to parse {}: {message}\n\nRun `rs-nbody check {}` to find every problem with it.",
+        path.display(),
+        path.display()
+    )]
+    Malformed { path: PathBuf, message: String },
+    /// A value of a body in the initial conditions which is infinite or NaN.
+    #[error("the {value} of body {body} in {} is not finite", path.display())]
+    NonFinite {
+        path: PathBuf,
+        body: usize,
+        value: &'static str,
+    },
+    /// A `--rest-frame` which doesn't name a body in the initial conditions.
+    #[error("invalid --rest-frame: {0}")]
+    RestFrame(String),
+    /// Failure to read or write a file or stream, or to fetch data.
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        source: Box<dyn error::Error>,
+    },
+    /// A final state hash which differs from the one given to `--verify-hash`.
+    #[error("state hash {actual:016x} does not match the expected {expected:016x}")]
+    HashMismatch { expected: u64, actual: u64 },
+}
+
+impl Error {
+    /// Error for failing to read the file at `path`, which is `MissingFile` if it doesn't exist.
+    pub fn reading(path: &Path, error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => Error::MissingFile(path.to_path_buf()),
+            _ => Error::Io {
+                context: format!("unable to read {}", path.display()),
+                source: error.into(),
+            },
+        }
+    }
+
+    pub fn malformed(path: &Path, error: impl ToString) -> Self {
+        Error::Malformed {
+            path: path.to_path_buf(),
+            message: error.to_string(),
+        }
+    }
+
+    /// Exit status of the process: 2 for invalid arguments, as for those rejected while parsing
+    /// the command line, 3 for a missing file, 4 for malformed initial conditions, 5 for non-finite
+    /// values in them, 6 for an invalid rest frame, 7 for failures to read or write and 8 for a
+    /// state hash which doesn't match the expected one.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::InvalidArguments(_) => 2,
+            Error::MissingFile(_) => 3,
+            Error::Malformed { .. } => 4,
+            Error::NonFinite { .. } => 5,
+            Error::RestFrame(_) => 6,
+            Error::Io { .. } => 7,
+            Error::HashMismatch { .. } => 8,
+        }
+    }
+}
+
+/// Conversion of other errors into `Error::Io`, with the context in which they occurred.
+pub trait Context<T> {
+    fn context(self, context: &str) -> Result<T, Error>;
+}
+
+impl<T, E: Into<Box<dyn error::Error>>> Context<T> for Result<T, E> {
+    fn context(self, context: &str) -> Result<T, Error> {
+        self.map_err(|e| Error::Io {
+            context: context.to_string(),
+            source: e.into(),
+        })
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    analysis,
+    body::Body,
+    boundary::{self, PeriodicBox},
+    collision::Collision,
+};
+
+/// Something which happened to bodies during a tick.
+#[derive(Debug)]
+pub enum Event {
+    Collision(Collision),
+    Escape(Escape),
+    CloseEncounter(CloseEncounter),
+}
+
+/// Kinds of event, for choosing which ones stop a run.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    Collision,
+    Escape,
+    CloseEncounter,
+}
+
+/// A body which was found to be escaping, as for [`analysis::escapers`].
+#[derive(Debug)]
+pub struct Escape {
+    pub time: f64,
+    /// Id of the body.
+    pub body: usize,
+    /// Specific energy of the body relative to the barycentre of the others.
+    pub energy: f64,
+}
+
+/// Two bodies, given by their ids, which came within the encounter distance of each other.
+#[derive(Debug)]
+pub struct CloseEncounter {
+    pub time: f64,
+    pub first: usize,
+    pub second: usize,
+    pub separation: f64,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::Collision(_) => EventKind::Collision,
+            Event::Escape(_) => EventKind::Escape,
+            Event::CloseEncounter(_) => EventKind::CloseEncounter,
+        }
+    }
+
+    pub fn time(&self) -> f64 {
+        match self {
+            Event::Collision(collision) => collision.time,
+            Event::Escape(escape) => escape.time,
+            Event::CloseEncounter(encounter) => encounter.time,
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Collision(collision) => collision.fmt(f),
+            Event::Escape(escape) => escape.fmt(f),
+            Event::CloseEncounter(encounter) => encounter.fmt(f),
+        }
+    }
+}
+
+impl Display for Escape {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Escape at t = {}: body {}, specific energy {:e}",
+            self.time, self.body, self.energy
+        )
+    }
+}
+
+impl Display for CloseEncounter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Encounter at t = {}: bodies {} and {} at separation {:e}",
+            self.time, self.first, self.second, self.separation
+        )
+    }
+}
+
+/// Which escapers and close encounters have been reported, and whether a run has been stopped, as
+/// kept by snapshots so that events after a snapshot are detected again once a world has been
+/// rewound to it.
+#[derive(Clone, Debug, Default)]
+pub struct Detected {
+    escaped: HashSet<usize>,
+    close: HashSet<(usize, usize)>,
+    stopped: bool,
+}
+
+/// Closure called with each event as it occurs.
+pub type Subscriber = Box<dyn FnMut(&Event)>;
+
+/// Queue of the events of a world, with the settings for detecting escapes and close encounters
+/// and the subscribers called as each event occurs.
+#[derive(Default)]
+pub struct Events {
+    queue: Vec<Event>,
+    subscribers: Vec<Subscriber>,
+    /// Kinds of event which stop a run.
+    stop_on: Vec<EventKind>,
+    stopped: bool,
+    escape_radius: Option<f64>,
+    /// Ids of escapers which have been reported, which aren't reported again.
+    escaped: HashSet<usize>,
+    encounter_distance: Option<f64>,
+    /// Ids of pairs of bodies currently within the encounter distance.
+    close: HashSet<(usize, usize)>,
+}
+
+impl Events {
+    /// Enables detection of escapers further than `radius` from the barycentre, or disables it.
+    pub fn set_escape_radius(&mut self, radius: Option<f64>) {
+        self.escape_radius = radius;
+    }
+
+    /// Enables detection of pairs of bodies coming within `distance` of each other, or disables
+    /// it.
+    pub fn set_encounter_distance(&mut self, distance: Option<f64>) {
+        self.encounter_distance = distance;
+    }
+
+    /// Registers a closure to be called with each event as it occurs.
+    pub fn subscribe(&mut self, f: impl FnMut(&Event) + 'static) {
+        self.subscribers.push(Box::new(f));
+    }
+
+    /// Makes events of `kind` stop runs, so that `stopped` is true once one has occurred.
+    pub fn stop_on(&mut self, kind: EventKind) {
+        self.stop_on.push(kind);
+    }
+
+    /// Whether an event of a kind which stops runs has occurred.
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Ids of escapers which have been reported, in order.
+    pub fn escaped(&self) -> Vec<usize> {
+        let mut escaped: Vec<usize> = self.escaped.iter().copied().collect();
+        escaped.sort_unstable();
+        escaped
+    }
+
+    /// Marks escapers as already reported, as when resuming a run.
+    pub fn set_escaped(&mut self, escaped: impl IntoIterator<Item = usize>) {
+        self.escaped = escaped.into_iter().collect();
+    }
+
+    pub fn detected(&self) -> Detected {
+        Detected {
+            escaped: self.escaped.clone(),
+            close: self.close.clone(),
+            stopped: self.stopped,
+        }
+    }
+
+    pub fn restore_detected(&mut self, detected: Detected) {
+        self.escaped = detected.escaped;
+        self.close = detected.close;
+        self.stopped = detected.stopped;
+    }
+
+    /// Takes the events which occurred since this was last called.
+    pub fn take(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.queue)
+    }
+
+    /// Queues `event`, calling the subscribers with it.
+    pub fn emit(&mut self, event: Event) {
+        for subscriber in &mut self.subscribers {
+            subscriber(&event);
+        }
+        if self.stop_on.contains(&event.kind()) {
+            self.stopped = true;
+        }
+        self.queue.push(event);
+    }
+
+    /// Emits events for the escapers and close encounters among `bodies` at `time` which haven't
+    /// been reported yet.
+    pub fn detect(&mut self, time: f64, bodies: &[Body], periodic: Option<PeriodicBox>) {
+        if let Some(radius) = self.escape_radius {
+            for (i, energy) in analysis::escapers(bodies, radius) {
+                let body = bodies[i].id;
+                if self.escaped.insert(body) {
+                    self.emit(Event::Escape(Escape { time, body, energy }));
+                }
+            }
+        }
+        if let Some(distance) = self.encounter_distance {
+            let mut close = HashSet::new();
+            for (i, a) in bodies.iter().enumerate().filter(|(_, a)| a.is_active()) {
+                for b in bodies.iter().skip(i + 1) {
+                    if !b.is_active() {
+                        continue;
+                    }
+                    let separation =
+                        boundary::separation(periodic, a.position, b.position).length();
+                    if separation < distance {
+                        close.insert((a.id, b.id));
+                        if !self.close.contains(&(a.id, b.id)) {
+                            self.emit(Event::CloseEncounter(CloseEncounter {
+                                time,
+                                first: a.id,
+                                second: b.id,
+                                separation,
+                            }));
+                        }
+                    }
+                }
+            }
+            self.close = close;
+        }
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Events")
+            .field("queue", &self.queue)
+            .field("subscribers", &self.subscribers.len())
+            .field("stop_on", &self.stop_on)
+            .field("stopped", &self.stopped)
+            .field("escape_radius", &self.escape_radius)
+            .field("escaped", &self.escaped)
+            .field("encounter_distance", &self.encounter_distance)
+            .finish()
+    }
+}
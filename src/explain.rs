@@ -0,0 +1,48 @@
+use crate::{force::Gravity, simulation::Simulation, vec3::Vec3};
+
+/// Ticks `world`, printing the accelerations acting on each body and how the integrator updates
+/// them, as a teaching aid for small systems.
+pub fn explain_tick(world: &mut impl Simulation, tick_duration: f64) {
+    println!(
+        "Tick from t = {} to t = {}",
+        world.time(),
+        world.time() + tick_duration
+    );
+    println!("  {}", world.scheme());
+
+    let gravity = Gravity {
+        periodic: world.simulation_box().and_then(|b| b.periodic()),
+        ..Default::default()
+    };
+    let bodies = world.bodies();
+    println!("  Pairwise gravitational accelerations a[i <- j] at the start of the tick:");
+    for (i, body) in bodies.iter().enumerate() {
+        for (j, from) in bodies.iter().enumerate() {
+            if i != j && from.is_source() {
+                println!(
+                    "    a[{} <- {}] = [{:e}]",
+                    i,
+                    j,
+                    gravity.pairwise(body, from)
+                );
+            }
+        }
+    }
+    println!("  Accelerations by force at the start of the tick:");
+    for force in world.forces() {
+        println!("    {:?}", force);
+        for i in 0..bodies.len() {
+            println!("      a[{}] = [{:e}]", i, force.acceleration(i, bodies));
+        }
+    }
+
+    let before: Vec<(Vec3, Vec3)> = bodies.iter().map(|b| (b.position, b.velocity)).collect();
+    world.tick(tick_duration);
+    println!("  Updates:");
+    for (i, (body, (position, velocity))) in world.bodies().iter().zip(before).enumerate() {
+        println!(
+            "    body {}: x = [{:e}] -> [{:e}], v = [{:e}] -> [{:e}]",
+            i, position, body.position, velocity, body.velocity
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! C API for embedding the integrators in other programs, built with the `ffi` feature and declared
+//! in `include/nbody.h`, which must be kept in step with this module. Vectors are passed as flat
+//! arrays of their components, `[x₀, y₀, z₀, x₁, y₁, z₁, …]`, and masses as Gm.
+
+use std::{ffi::c_int, ptr, slice};
+
+use crate::{
+    body::Body,
+    force::{Force, Gravity},
+    forward_euler, leapfrog,
+    simulation::Simulation,
+    symplectic_euler,
+    vec3::Vec3,
+};
+
+pub const NBODY_FORWARD_EULER: c_int = 0;
+pub const NBODY_SYMPLECTIC_EULER: c_int = 1;
+pub const NBODY_LEAPFROG: c_int = 2;
+
+/// World under Newtonian gravity, opaque to C, with buffers for the components it hands out.
+pub struct NbodyWorld {
+    world: Box<dyn Simulation>,
+    positions: Vec<f64>,
+    velocities: Vec<f64>,
+}
+
+/// Creates a world of `count` bodies integrated with `integrator`, one of the `NBODY_*`
+/// constants, returning null if the integrator is unknown or an array is null. It must be freed
+/// with `nbody_world_free`.
+///
+/// # Safety
+///
+/// `positions` and `velocities` must point to `3 * count` doubles and `masses` to `count`
+/// doubles.
+#[no_mangle]
+pub unsafe extern "C" fn nbody_world_new(
+    integrator: c_int,
+    count: usize,
+    positions: *const f64,
+    velocities: *const f64,
+    masses: *const f64,
+) -> *mut NbodyWorld {
+    if positions.is_null() || velocities.is_null() || masses.is_null() {
+        return ptr::null_mut();
+    }
+    let positions = slice::from_raw_parts(positions, 3 * count);
+    let velocities = slice::from_raw_parts(velocities, 3 * count);
+    let masses = slice::from_raw_parts(masses, count);
+    let bodies: Vec<Body> = (0..count)
+        .map(|i| Body::new(vector(positions, i), vector(velocities, i), masses[i]))
+        .collect();
+    let forces: Vec<Box<dyn Force>> = vec![Box::new(Gravity::default())];
+    let world: Box<dyn Simulation> = match integrator {
+        NBODY_FORWARD_EULER => Box::new(forward_euler::World::new(bodies, forces)),
+        NBODY_SYMPLECTIC_EULER => Box::new(symplectic_euler::World::new(bodies, forces)),
+        NBODY_LEAPFROG => Box::new(leapfrog::World::new(bodies, forces)),
+        _ => return ptr::null_mut(),
+    };
+    Box::into_raw(Box::new(NbodyWorld {
+        world,
+        positions: Vec::new(),
+        velocities: Vec::new(),
+    }))
+}
+
+/// Frees a world. Null is ignored.
+///
+/// # Safety
+///
+/// `world` must be null or have come from `nbody_world_new`, and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn nbody_world_free(world: *mut NbodyWorld) {
+    if !world.is_null() {
+        drop(Box::from_raw(world));
+    }
+}
+
+/// Advances the world by a tick of `tick_duration`.
+///
+/// # Safety
+///
+/// `world` must have come from `nbody_world_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn nbody_tick(world: *mut NbodyWorld, tick_duration: f64) {
+    (*world).world.tick(tick_duration);
+}
+
+/// # Safety
+///
+/// `world` must have come from `nbody_world_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn nbody_count(world: *const NbodyWorld) -> usize {
+    (*world).world.bodies().len()
+}
+
+/// # Safety
+///
+/// `world` must have come from `nbody_world_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn nbody_time(world: *const NbodyWorld) -> f64 {
+    (*world).world.time()
+}
+
+/// Total energy, in units of G times energy like the masses.
+///
+/// # Safety
+///
+/// `world` must have come from `nbody_world_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn nbody_total_energy(world: *const NbodyWorld) -> f64 {
+    (*world).world.total_energy()
+}
+
+/// Positions of the bodies, as `3 * nbody_count(world)` doubles which stay valid until the world
+/// is next ticked or freed or this is next called.
+///
+/// # Safety
+///
+/// `world` must have come from `nbody_world_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn nbody_positions_ptr(world: *mut NbodyWorld) -> *const f64 {
+    let world = &mut *world;
+    fill(&mut world.positions, world.world.bodies(), |body| {
+        body.position
+    });
+    world.positions.as_ptr()
+}
+
+/// Velocities of the bodies, as `nbody_positions_ptr` gives the positions.
+///
+/// # Safety
+///
+/// `world` must have come from `nbody_world_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn nbody_velocities_ptr(world: *mut NbodyWorld) -> *const f64 {
+    let world = &mut *world;
+    fill(&mut world.velocities, world.world.bodies(), |body| {
+        body.velocity
+    });
+    world.velocities.as_ptr()
+}
+
+fn vector(components: &[f64], i: usize) -> Vec3 {
+    Vec3::new(
+        components[3 * i],
+        components[3 * i + 1],
+        components[3 * i + 2],
+    )
+}
+
+/// Refills `buffer` with the components of a vector of each body, reusing its allocation.
+fn fill(buffer: &mut Vec<f64>, bodies: &[Body], f: impl Fn(&Body) -> Vec3) {
+    buffer.clear();
+    buffer.extend(bodies.iter().flat_map(|body| <[f64; 3]>::from(f(body))));
+}
@@ -0,0 +1,979 @@
+use std::{f64::consts::PI, str::FromStr};
+
+use crate::{
+    body::Body,
+    boundary::{self, PeriodicBox},
+    parameters::Parameters,
+    rng::Rng,
+    summation::kahan_sum,
+    vec3::Vec3,
+};
+
+/// A contribution to the acceleration of the bodies in a world.
+pub trait Force: std::fmt::Debug {
+    /// Acceleration of the body at index `i` due to this force.
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3;
+
+    /// Adds the acceleration of every body due to this force to `accelerations`. Forces may
+    /// override this when some work can be shared between bodies.
+    fn accumulate(&self, bodies: &[Body], accelerations: &mut [Vec3]) {
+        for (i, acceleration) in accelerations.iter_mut().enumerate() {
+            *acceleration += self.acceleration(i, bodies);
+        }
+    }
+
+    /// Instantaneous change in velocity of the body at index `i` at the end of a tick, for forces
+    /// with a stochastic component.
+    fn kick(&mut self, _i: usize, _bodies: &[Body], _tick_duration: f64) -> Vec3 {
+        Vec3::ZERO
+    }
+
+    /// Potential energy of the bodies due to this force, in units of G times energy like the Gm
+    /// masses. Forces without a potential contribute none.
+    fn potential_energy(&self, _bodies: &[Body]) -> f64 {
+        0.
+    }
+
+    /// Whether the force varies slowly enough in space and time to be evaluated only once per tick
+    /// by multiple-timestep integrators.
+    fn is_slow(&self) -> bool {
+        false
+    }
+
+    /// Whether the force is calculated with only arithmetic which IEEE 754 rounds exactly, so that
+    /// it gives bit-identical results on every conforming machine. Functions such as `powf`,
+    /// `powi`, `exp` and `sin` are left to the platform's maths library, which may round them
+    /// differently.
+    fn is_deterministic(&self) -> bool {
+        false
+    }
+
+    /// The force as a magnetic field, for integrators which rotate velocities with the Boris push
+    /// instead of treating the Lorentz force as an acceleration.
+    fn as_magnetic_field(&self) -> Option<&MagneticField> {
+        None
+    }
+
+    /// Generator of the random numbers of a stochastic force, which is saved in checkpoints.
+    fn rng(&self) -> Option<&Rng> {
+        None
+    }
+
+    fn rng_mut(&mut self) -> Option<&mut Rng> {
+        None
+    }
+}
+
+/// Calculates the total acceleration of every body due to all `forces`. Fixed and inactive bodies
+/// never accelerate.
+pub fn accelerations<'a>(
+    forces: impl IntoIterator<Item = &'a Box<dyn Force>>,
+    bodies: &[Body],
+) -> Vec<Vec3> {
+    let mut accelerations = Vec::new();
+    accelerations_into(forces, bodies, &mut accelerations);
+    accelerations
+}
+
+/// Calculates the accelerations as for `accelerations` into a buffer, which only allocates if the
+/// buffer has to grow.
+pub fn accelerations_into<'a>(
+    forces: impl IntoIterator<Item = &'a Box<dyn Force>>,
+    bodies: &[Body],
+    accelerations: &mut Vec<Vec3>,
+) {
+    accelerations.clear();
+    accelerations.resize(bodies.len(), Vec3::ZERO);
+    for force in forces {
+        force.accumulate(bodies, accelerations);
+    }
+    for (body, acceleration) in bodies.iter().zip(accelerations) {
+        if !body.is_mobile() {
+            *acceleration = Vec3::ZERO;
+        }
+    }
+}
+
+/// Generators of the stochastic forces among `forces`, in order.
+pub fn rngs(forces: &[Box<dyn Force>]) -> Vec<Rng> {
+    forces.iter().filter_map(|f| f.rng()).cloned().collect()
+}
+
+/// Replaces the generators of the stochastic forces among `forces` with `rngs`, in order.
+pub fn restore_rngs(forces: &mut [Box<dyn Force>], rngs: Vec<Rng>) {
+    for (rng, restored) in forces.iter_mut().filter_map(|f| f.rng_mut()).zip(rngs) {
+        *rng = restored;
+    }
+}
+
+/// Index of the body with `id` among `bodies`. Forces refer to bodies by id, so that they keep
+/// acting on the same bodies as others are added and removed.
+fn index_of(bodies: &[Body], id: usize) -> Option<usize> {
+    bodies.iter().position(|body| body.id == id)
+}
+
+/// Calculates the total velocity kick of every body due to all `forces`. Fixed and inactive bodies
+/// are never kicked.
+pub fn kicks(forces: &mut [Box<dyn Force>], bodies: &[Body], tick_duration: f64) -> Vec<Vec3> {
+    let mut kicks = Vec::new();
+    kicks_into(forces, bodies, tick_duration, &mut kicks);
+    kicks
+}
+
+/// Calculates the kicks as for `kicks` into a buffer, which only allocates if the buffer has to
+/// grow.
+pub fn kicks_into(
+    forces: &mut [Box<dyn Force>],
+    bodies: &[Body],
+    tick_duration: f64,
+    kicks: &mut Vec<Vec3>,
+) {
+    kicks.clear();
+    kicks.extend((0..bodies.len()).map(|i| {
+        match bodies[i].is_mobile() {
+            false => Vec3::ZERO,
+            true => forces
+                .iter_mut()
+                .map(|f| f.kick(i, bodies, tick_duration))
+                .sum(),
+        }
+    }));
+}
+
+/// Newtonian mutual gravitation between all bodies, optionally with a modified force law. Test
+/// particles and massless bodies are skipped as sources.
+#[derive(Debug)]
+pub struct Gravity {
+    /// Periodic box in which separations take the minimum image.
+    pub periodic: Option<PeriodicBox>,
+    /// Exponent n of the pairwise force law F ∝ 1/rⁿ, which is 2 for Newtonian gravity.
+    pub exponent: f64,
+    /// MOND acceleration scale a₀. Total accelerations g well below a₀ are boosted towards √(a₀g)
+    /// by the simple interpolating function ν(y) = 1/2 + √(1/4 + 1/y), with y = g/a₀.
+    pub mond: Option<f64>,
+    /// Cubes distances for the Newtonian law by multiplication rather than `powf`, which is
+    /// deterministic but may differ from the default in the last bit.
+    pub deterministic: bool,
+    /// Sums the pairwise accelerations on each body with Kahan compensation, whose error doesn't
+    /// grow with the number of bodies.
+    pub compensated: bool,
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Self {
+            periodic: None,
+            exponent: 2.,
+            mond: None,
+            deterministic: false,
+            compensated: false,
+        }
+    }
+}
+
+impl Gravity {
+    /// Acceleration of `body` due to the gravity of `from` alone, before any MOND boost.
+    pub fn pairwise(&self, body: &Body, from: &Body) -> Vec3 {
+        let r: Vec3 = boundary::separation(self.periodic, body.position, from.position);
+        let distance = softened_distance(r, body, from);
+        let power = if self.deterministic && self.exponent == 2. {
+            distance * distance * distance
+        } else {
+            distance.powf(self.exponent + 1.)
+        };
+
+        (from.mass / power) * r
+    }
+}
+
+impl Force for Gravity {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        let body = &bodies[i];
+        let pairwise = (bodies.iter().enumerate())
+            .filter(|(j, from)| i != *j && from.is_source()) // forces from all bodies except itself
+            .map(|(_, from)| self.pairwise(body, from));
+        let acceleration: Vec3 = if self.compensated {
+            kahan_sum(pairwise)
+        } else {
+            pairwise.sum()
+        };
+        match self.mond {
+            Some(a0) if acceleration.length() > 0. => {
+                let y = acceleration.length() / a0;
+                (0.5 + (0.25 + 1. / y).sqrt()) * acceleration
+            }
+            _ => acceleration,
+        }
+    }
+
+    fn is_deterministic(&self) -> bool {
+        self.deterministic && self.exponent == 2.
+    }
+
+    /// Pairwise potential energy of the force law, without any MOND boost, summed with Kahan
+    /// compensation.
+    fn potential_energy(&self, bodies: &[Body]) -> f64 {
+        kahan_sum(
+            pairs(bodies)
+                .filter(|(a, b)| a.is_source() || b.is_source())
+                .map(|(a, b)| {
+                    let r = boundary::separation(self.periodic, a.position, b.position);
+                    let r = softened_distance(r, a, b);
+                    let n = self.exponent;
+                    let potential = if n == 1. {
+                        r.ln()
+                    } else {
+                        -1. / ((n - 1.) * r.powf(n - 1.))
+                    };
+                    a.mass * b.mass * potential
+                }),
+        )
+    }
+}
+
+/// Newtonian gravitation between all bodies and every periodic image of them in a periodic box, by
+/// Ewald summation. As is usual for periodic gravity, the mean density of the box is subtracted so
+/// that the sum converges. Test particles and massless bodies are skipped as sources.
+#[derive(Debug)]
+pub struct Ewald {
+    periodic: PeriodicBox,
+    /// Splitting parameter between the real and reciprocal space sums.
+    alpha: f64,
+    /// Reciprocal lattice vectors k, with their weights 4π/L³ exp(-k²/4α²)/k².
+    waves: Vec<(Vec3, f64)>,
+}
+
+impl Ewald {
+    /// Uses α = 2/L with real space images within 3.6L and waves with |kL/2π|² ≤ 10, which gives
+    /// relative errors of around 1e-6 (Hernquist, Bouchet & Suto 1991).
+    pub fn new(periodic: PeriodicBox) -> Self {
+        let size = periodic.size;
+        let alpha = 2. / size;
+        let mut waves = Vec::new();
+        for hx in -3..=3 {
+            for hy in -3..=3 {
+                for hz in -3..=3 {
+                    let h2 = hx * hx + hy * hy + hz * hz;
+                    if h2 == 0 || h2 > 10 {
+                        continue;
+                    }
+                    let k = (2. * PI / size) * Vec3::new(hx as f64, hy as f64, hz as f64);
+                    let k2 = k.length_squared();
+                    let weight = 4. * PI / size.powi(3) * (-k2 / (4. * alpha * alpha)).exp() / k2;
+                    waves.push((k, weight));
+                }
+            }
+        }
+        Self {
+            periodic,
+            alpha,
+            waves,
+        }
+    }
+
+    /// Acceleration of `body` due to the gravity of `from` and its periodic images.
+    pub fn pairwise(&self, body: &Body, from: &Body) -> Vec3 {
+        let size = self.periodic.size;
+        let r = self.periodic.wrap(body.position - from.position);
+        let mut sum = Vec3::ZERO;
+        // Short range part, from nearby images
+        for nx in -2..=2 {
+            for ny in -2..=2 {
+                for nz in -2..=2 {
+                    let rn = r + size * Vec3::new(nx as f64, ny as f64, nz as f64);
+                    let d = rn.length();
+                    if d == 0. || d > 3.6 * size {
+                        continue;
+                    }
+                    let ad = self.alpha * d;
+                    let factor = erfc(ad) + 2. * ad / PI.sqrt() * (-ad * ad).exp();
+                    sum += (factor / d.powi(3)) * rn;
+                }
+            }
+        }
+        // Long range part, in reciprocal space
+        for (k, weight) in &self.waves {
+            sum += (weight * Vec3::dot(k, &r).sin()) * *k;
+        }
+        -from.mass * sum
+    }
+
+    /// Potential per unit mass of each of two masses at separation `r`, due to the other and its
+    /// periodic images against the mean density, leaving out the image at zero separation, if any.
+    fn potential(&self, r: Vec3) -> f64 {
+        let size = self.periodic.size;
+        let r = self.periodic.wrap(r);
+        let mut sum = 0.;
+        // Short range part, from nearby images
+        for nx in -2..=2 {
+            for ny in -2..=2 {
+                for nz in -2..=2 {
+                    let d = (r + size * Vec3::new(nx as f64, ny as f64, nz as f64)).length();
+                    if d == 0. || d > 3.6 * size {
+                        continue;
+                    }
+                    sum += erfc(self.alpha * d) / d;
+                }
+            }
+        }
+        // Long range part, in reciprocal space, and the mean density
+        for (k, weight) in &self.waves {
+            sum += weight * Vec3::dot(k, &r).cos();
+        }
+        sum -= PI / (self.alpha * self.alpha * size.powi(3));
+        -sum
+    }
+}
+
+impl Force for Ewald {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        let body = &bodies[i];
+        bodies
+            .iter()
+            .enumerate()
+            .filter(|(j, from)| i != *j && from.is_source())
+            .map(|(_, from)| self.pairwise(body, from))
+            .sum()
+    }
+
+    /// Pairwise energy of the periodic potential, plus the energy of each source with its own
+    /// images, which is constant while masses are.
+    fn potential_energy(&self, bodies: &[Body]) -> f64 {
+        let pairs = pairs(bodies)
+            .filter(|(a, b)| a.is_source() || b.is_source())
+            .map(|(a, b)| a.mass * b.mass * self.potential(a.position - b.position));
+        // Limit of the pair potential less the direct 1/r as r → 0
+        let own = self.potential(Vec3::ZERO) + 2. * self.alpha / PI.sqrt();
+        let images = (bodies.iter())
+            .filter(|body| body.is_source())
+            .map(|body| body.mass * body.mass * own / 2.);
+        kahan_sum(pairs.chain(images))
+    }
+}
+
+/// Coulomb interaction between all charged bodies. Test particles are skipped as sources.
+#[derive(Debug)]
+pub struct Coulomb {
+    /// Coulomb constant.
+    pub k: f64,
+    /// Gravitational constant, to recover inertial masses from Gm.
+    pub g: f64,
+    /// Periodic box in which separations take the minimum image.
+    pub periodic: Option<PeriodicBox>,
+}
+
+impl Force for Coulomb {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        let body = &bodies[i];
+        if body.charge == 0. || body.mass == 0. {
+            return Vec3::ZERO;
+        }
+        let force: Vec3 = bodies
+            .iter()
+            .enumerate()
+            .filter(|(j, from)| i != *j && from.is_active() && !from.test && from.charge != 0.)
+            .map(|(_, from)| {
+                let r: Vec3 = boundary::separation(self.periodic, from.position, body.position);
+
+                let distance = r.length();
+                (self.k * body.charge * from.charge / (distance * distance * distance)) * r
+            })
+            .sum();
+        force * self.g / body.mass
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+
+    fn potential_energy(&self, bodies: &[Body]) -> f64 {
+        kahan_sum(
+            pairs(bodies)
+                .filter(|(a, b)| !(a.test && b.test))
+                .map(|(a, b)| {
+                    let r = boundary::separation(self.periodic, a.position, b.position).length();
+                    self.g * self.k * a.charge * b.charge / r
+                }),
+        )
+    }
+}
+
+/// Yukawa interaction between all charged bodies: Coulomb's law screened beyond a length λ, with
+/// potential energy k q₁q₂ exp(-r/λ)/r, as for Debye screening in a plasma. Test particles are
+/// skipped as sources.
+#[derive(Debug)]
+pub struct Yukawa {
+    /// Coulomb constant.
+    pub k: f64,
+    /// Gravitational constant, to recover inertial masses from Gm.
+    pub g: f64,
+    /// Screening length λ.
+    pub length: f64,
+    /// Periodic box in which separations take the minimum image.
+    pub periodic: Option<PeriodicBox>,
+}
+
+impl Force for Yukawa {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        let body = &bodies[i];
+        if body.charge == 0. || body.mass == 0. {
+            return Vec3::ZERO;
+        }
+        let force: Vec3 = bodies
+            .iter()
+            .enumerate()
+            .filter(|(j, from)| i != *j && from.is_active() && !from.test && from.charge != 0.)
+            .map(|(_, from)| {
+                let r: Vec3 = boundary::separation(self.periodic, from.position, body.position);
+                let d = r.length();
+                let screening = (1. + d / self.length) * (-d / self.length).exp();
+
+                (self.k * body.charge * from.charge * screening / d.powi(3)) * r
+            })
+            .sum();
+        force * self.g / body.mass
+    }
+
+    fn potential_energy(&self, bodies: &[Body]) -> f64 {
+        kahan_sum(
+            pairs(bodies)
+                .filter(|(a, b)| !(a.test && b.test))
+                .map(|(a, b)| {
+                    let r = boundary::separation(self.periodic, a.position, b.position).length();
+                    self.g * self.k * a.charge * b.charge * (-r / self.length).exp() / r
+                }),
+        )
+    }
+}
+
+/// Lorentz force q v × B on charged bodies from a uniform magnetic field.
+#[derive(Debug)]
+pub struct MagneticField {
+    pub field: Vec3,
+    /// Gravitational constant, to recover inertial masses from Gm.
+    pub g: f64,
+}
+
+impl MagneticField {
+    /// Charge to mass ratio of `body`, or zero if it is massless.
+    pub fn charge_to_mass(&self, body: &Body) -> f64 {
+        match body.mass {
+            0. => 0.,
+            mass => body.charge * self.g / mass,
+        }
+    }
+}
+
+impl Force for MagneticField {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        let body = &bodies[i];
+        self.charge_to_mass(body) * Vec3::cross(&body.velocity, &self.field)
+    }
+
+    fn as_magnetic_field(&self) -> Option<&MagneticField> {
+        Some(self)
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+/// First-order post-Newtonian correction to the gravity between each pair of bodies, in the
+/// test-particle (Schwarzschild) limit of the pair. This is accurate for systems dominated by one
+/// mass, such as the perihelion precession of planets around the Sun.
+#[derive(Debug)]
+pub struct PostNewtonian {
+    /// Speed of light.
+    pub c: f64,
+}
+
+impl Force for PostNewtonian {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        let body = &bodies[i];
+        bodies
+            .iter()
+            .enumerate()
+            .filter(|(j, from)| i != *j && from.is_source())
+            .map(|(_, from)| {
+                // a = Gm / (c² r³) [(4Gm / r - v²) r + 4 (r · v) v]
+                let r = body.position - from.position;
+                let v = body.velocity - from.velocity;
+                let distance = r.length();
+                (from.mass / (self.c * self.c * distance * distance * distance))
+                    * ((4. * from.mass / distance - v.length_squared()) * r
+                        + (4. * Vec3::dot(&r, &v)) * v)
+            })
+            .sum()
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+/// Radiation pressure and Poynting-Robertson drag from a luminous body, with the ratio β of
+/// radiation force to gravity (Burns, Lamy & Soter 1979).
+#[derive(Debug)]
+pub struct RadiationPressure {
+    /// Id of the luminous body.
+    pub source: usize,
+    /// Ratio of radiation pressure to the gravity of the luminous body.
+    pub beta: f64,
+    /// Ids of the bodies which feel the radiation. If empty, all bodies but the source do.
+    pub bodies: Vec<usize>,
+    /// Speed of light.
+    pub c: f64,
+}
+
+impl RadiationPressure {
+    /// Acceleration of `body` due to the radiation of `source`.
+    fn acceleration_from(&self, body: &Body, source: &Body) -> Vec3 {
+        if body.id == self.source || !(self.bodies.is_empty() || self.bodies.contains(&body.id)) {
+            return Vec3::ZERO;
+        }
+        // a = β GM / r² [(1 - ṙ / c) r̂ - v / c]
+        let r = body.position - source.position;
+        let v = body.velocity - source.velocity;
+        let r_hat = Vec3::unitv(&r);
+        let r_dot = Vec3::dot(&r_hat, &v);
+        (self.beta * source.mass / r.length_squared())
+            * ((1. - r_dot / self.c) * r_hat - v / self.c)
+    }
+}
+
+impl Force for RadiationPressure {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        match index_of(bodies, self.source) {
+            Some(source) => self.acceleration_from(&bodies[i], &bodies[source]),
+            None => Vec3::ZERO,
+        }
+    }
+
+    fn accumulate(&self, bodies: &[Body], accelerations: &mut [Vec3]) {
+        let Some(source) = index_of(bodies, self.source) else {
+            return;
+        };
+        for (body, acceleration) in bodies.iter().zip(accelerations) {
+            *acceleration += self.acceleration_from(body, &bodies[source]);
+        }
+    }
+}
+
+/// Perturbation from the J2 oblateness of a body whose symmetry axis is the z axis. The body
+/// itself feels the reaction, so that momentum is conserved.
+#[derive(Debug)]
+pub struct Oblateness {
+    /// Id of the oblate body.
+    pub body: usize,
+    /// Second zonal harmonic coefficient.
+    pub j2: f64,
+    /// Equatorial radius of the oblate body.
+    pub radius: f64,
+}
+
+impl Oblateness {
+    /// Acceleration of `body` due to the oblateness of `from`.
+    fn acceleration_from(&self, body: &Body, from: &Body) -> Vec3 {
+        let r = body.position - from.position;
+        let r2 = r.length_squared();
+        let z2 = r.z() * r.z() / r2;
+        // a = -3/2 J2 GM R² / r⁵ [x (1 - 5z²/r²), y (1 - 5z²/r²), z (3 - 5z²/r²)]
+        (-1.5 * self.j2 * from.mass * self.radius * self.radius / r2.powf(2.5))
+            * Vec3::new(
+                r.x() * (1. - 5. * z2),
+                r.y() * (1. - 5. * z2),
+                r.z() * (3. - 5. * z2),
+            )
+    }
+
+    /// Acceleration of the body at index `i` given the index of the oblate body.
+    fn acceleration_given(&self, i: usize, bodies: &[Body], oblate: usize) -> Vec3 {
+        let oblate_body = &bodies[oblate];
+        if i != oblate {
+            return self.acceleration_from(&bodies[i], oblate_body);
+        }
+        if oblate_body.mass == 0. {
+            return Vec3::ZERO;
+        }
+        bodies
+            .iter()
+            .enumerate()
+            .filter(|(j, b)| *j != i && b.is_source())
+            .map(|(_, b)| (-b.mass / oblate_body.mass) * self.acceleration_from(b, oblate_body))
+            .sum()
+    }
+}
+
+impl Force for Oblateness {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        match index_of(bodies, self.body) {
+            Some(oblate) => self.acceleration_given(i, bodies, oblate),
+            None => Vec3::ZERO,
+        }
+    }
+
+    fn accumulate(&self, bodies: &[Body], accelerations: &mut [Vec3]) {
+        let Some(oblate) = index_of(bodies, self.body) else {
+            return;
+        };
+        for (i, acceleration) in accelerations.iter_mut().enumerate() {
+            *acceleration += self.acceleration_given(i, bodies, oblate);
+        }
+    }
+}
+
+/// Tides raised on a primary body by a secondary, in the constant time lag model (Mignard 1979)
+/// with the spin of the primary neglected. The lag dissipates orbital energy, circularising the
+/// orbit and drawing the bodies together.
+#[derive(Clone, Debug)]
+pub struct Tide {
+    /// Id of the body on which the tide is raised.
+    pub primary: usize,
+    /// Id of the body raising the tide.
+    pub secondary: usize,
+    /// Love number k₂ of the primary.
+    pub love_number: f64,
+    /// Time lag of the tidal bulge.
+    pub time_lag: f64,
+    /// Radius of the primary.
+    pub radius: f64,
+}
+
+impl Force for Tide {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        let id = bodies[i].id;
+        if id != self.primary && id != self.secondary {
+            return Vec3::ZERO;
+        }
+        let (Some(primary), Some(secondary)) = (
+            index_of(bodies, self.primary),
+            index_of(bodies, self.secondary),
+        ) else {
+            return Vec3::ZERO;
+        };
+        let (primary, secondary) = (&bodies[primary], &bodies[secondary]);
+        let r = secondary.position - primary.position;
+        let v = secondary.velocity - primary.velocity;
+        let r2 = r.length_squared();
+        // F / m = -3 k₂ Gm R⁵ / r¹⁰ [r² r + Δt (2 (r · v) r + r² v)] on the secondary
+        let acceleration = (-3. * self.love_number * secondary.mass * self.radius.powi(5)
+            / r2.powi(5))
+            * (r2 * r + self.time_lag * ((2. * Vec3::dot(&r, &v)) * r + r2 * v));
+        if id == self.secondary {
+            acceleration
+        } else if primary.mass > 0. {
+            (-secondary.mass / primary.mass) * acceleration
+        } else {
+            Vec3::ZERO
+        }
+    }
+}
+
+/// Parses tides of the form `primary=0,secondary=1,k2=0.3,lag=600,radius=7e8`.
+impl FromStr for Tide {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parameters = Parameters::parse(s)?;
+        let tide = Self {
+            primary: parameters.take_index("primary", None)?,
+            secondary: parameters.take_index("secondary", None)?,
+            love_number: parameters.take("k2", None)?,
+            time_lag: parameters.take("lag", None)?,
+            radius: parameters.take("radius", None)?,
+        };
+        parameters.finish()?;
+        Ok(tide)
+    }
+}
+
+/// Drag from a medium, linear and quadratic in the velocity relative to the medium. The medium is
+/// at rest in the simulation frame, or moves with the body with id `centre` if given.
+#[derive(Debug)]
+pub struct Drag {
+    /// Coefficient of the drag linear in velocity.
+    pub linear: f64,
+    /// Coefficient of the drag quadratic in velocity.
+    pub quadratic: f64,
+    /// If given, only bodies within this distance of the origin (or the centre body) feel drag.
+    pub radius: Option<f64>,
+    pub centre: Option<usize>,
+}
+
+impl Drag {
+    /// Acceleration of `body` given the body the medium moves with, if any.
+    fn acceleration_given(&self, body: &Body, centre: Option<&Body>) -> Vec3 {
+        if self.centre == Some(body.id) {
+            return Vec3::ZERO;
+        }
+        let (position, velocity) = match centre {
+            Some(centre) => (
+                body.position - centre.position,
+                body.velocity - centre.velocity,
+            ),
+            None => (body.position, body.velocity),
+        };
+        if self.radius.is_some_and(|radius| position.length() > radius) {
+            return Vec3::ZERO;
+        }
+        // a = -(k₁ + k₂|v|) v
+        (-(self.linear + self.quadratic * velocity.length())) * velocity
+    }
+
+    fn centre<'a>(&self, bodies: &'a [Body]) -> Option<&'a Body> {
+        let centre = index_of(bodies, self.centre?)?;
+        Some(&bodies[centre])
+    }
+}
+
+impl Force for Drag {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        self.acceleration_given(&bodies[i], self.centre(bodies))
+    }
+
+    fn accumulate(&self, bodies: &[Body], accelerations: &mut [Vec3]) {
+        let centre = self.centre(bodies);
+        for (body, acceleration) in bodies.iter().zip(accelerations) {
+            *acceleration += self.acceleration_given(body, centre);
+        }
+    }
+}
+
+/// Chandrasekhar dynamical friction from a uniform, unresolved background with a Maxwellian
+/// velocity distribution at rest in the simulation frame.
+#[derive(Debug)]
+pub struct DynamicalFriction {
+    /// Ids of the bodies which feel the friction.
+    pub bodies: Vec<usize>,
+    /// Background density, in the same Gm units as body masses.
+    pub density: f64,
+    /// One-dimensional velocity dispersion of the background.
+    pub dispersion: f64,
+    /// Coulomb logarithm ln Λ.
+    pub coulomb_logarithm: f64,
+}
+
+impl Force for DynamicalFriction {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        let body = &bodies[i];
+        let v = body.velocity.length();
+        if !self.bodies.contains(&body.id) || v == 0. {
+            return Vec3::ZERO;
+        }
+        // a = -4π G²Mρ lnΛ / v³ [erf(X) - 2X/√π exp(-X²)] v, where X = v / (√2 σ)
+        let x = v / (2f64.sqrt() * self.dispersion);
+
+        (-4. * PI * body.mass * self.density * self.coulomb_logarithm * slower_fraction(x)
+            / v.powi(3))
+            * body.velocity
+    }
+}
+
+/// An unresolved background of field stars, modelled as a Plummer sphere centred on the origin.
+/// Bodies feel its smooth potential, dynamical friction, and random velocity kicks with the
+/// diffusion coefficients of a local Maxwellian distribution (Binney & Tremaine eq. 7.92), so
+/// that two-body relaxation by the background is emulated.
+#[derive(Debug)]
+pub struct BackgroundField {
+    /// Total mass of the background, in Gm units.
+    pub mass: f64,
+    /// Plummer scale radius.
+    pub scale: f64,
+    /// Mass of an individual field star, in Gm units.
+    pub star_mass: f64,
+    /// Coulomb logarithm ln Λ.
+    pub coulomb_logarithm: f64,
+    pub rng: Rng,
+}
+
+impl BackgroundField {
+    /// Local density (times G) and one-dimensional velocity dispersion of the background.
+    fn local(&self, body: &Body) -> (f64, f64) {
+        let s2 = body.position.length_squared() + self.scale * self.scale;
+        let density = 3. * self.mass / (4. * PI * self.scale.powi(3))
+            * (self.scale * self.scale / s2).powf(2.5);
+        let dispersion = (self.mass / (6. * s2.sqrt())).sqrt();
+        (density, dispersion)
+    }
+}
+
+impl Force for BackgroundField {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        let body = &bodies[i];
+        let s2 = body.position.length_squared() + self.scale * self.scale;
+        let smooth = (-self.mass / s2.powf(1.5)) * body.position;
+
+        let v = body.velocity.length();
+        if v == 0. {
+            return smooth;
+        }
+        // D[Δv∥] = -4π G²ρ (m + m_a) lnΛ G(X) / σ²
+        let (density, dispersion) = self.local(body);
+        let x = v / (2f64.sqrt() * dispersion);
+        let drift = -4. * PI * density * (body.mass + self.star_mass) * self.coulomb_logarithm
+            / (2. * x * x)
+            * slower_fraction(x)
+            / (dispersion * dispersion);
+
+        smooth + drift * Vec3::unitv(&body.velocity)
+    }
+
+    fn kick(&mut self, i: usize, bodies: &[Body], tick_duration: f64) -> Vec3 {
+        let body = &bodies[i];
+        let (density, dispersion) = self.local(body);
+        let v = body.velocity.length();
+        let x = v / (2f64.sqrt() * dispersion);
+        // G(X)/X and (erf(X) - G(X))/X, with their limits as X -> 0
+        let (parallel, perpendicular) = if x == 0. {
+            (2. / (3. * PI.sqrt()), 4. / (3. * PI.sqrt()))
+        } else {
+            let g = slower_fraction(x) / (2. * x * x);
+            (g / x, (erf(x) - g) / x)
+        };
+        let coefficient =
+            4. * 2f64.sqrt() * PI * density * self.star_mass * self.coulomb_logarithm / dispersion;
+
+        // Orthonormal basis aligned with the velocity
+        let e1 = if v == 0. {
+            Vec3::new(1., 0., 0.)
+        } else {
+            Vec3::unitv(&body.velocity)
+        };
+        let helper = if e1.x().abs() < 0.9 {
+            Vec3::new(1., 0., 0.)
+        } else {
+            Vec3::new(0., 1., 0.)
+        };
+        let e2 = Vec3::unitv(&Vec3::cross(&e1, &helper));
+        let e3 = Vec3::cross(&e1, &e2);
+
+        let sigma_parallel = (coefficient * parallel * tick_duration).sqrt();
+        // D[(Δv⊥)²] is shared between the two perpendicular directions
+        let sigma_perpendicular = (coefficient * perpendicular * tick_duration / 2.).sqrt();
+        (sigma_parallel * self.rng.normal()) * e1
+            + (sigma_perpendicular * self.rng.normal()) * e2
+            + (sigma_perpendicular * self.rng.normal()) * e3
+    }
+
+    fn rng(&self) -> Option<&Rng> {
+        Some(&self.rng)
+    }
+
+    fn rng_mut(&mut self) -> Option<&mut Rng> {
+        Some(&mut self.rng)
+    }
+}
+
+/// Length of the separation `r` between `a` and `b`, Plummer softened by the mean of their
+/// softening lengths.
+fn softened_distance(r: Vec3, a: &Body, b: &Body) -> f64 {
+    let softening = (a.softening + b.softening) / 2.;
+    (r.length_squared() + softening * softening).sqrt()
+}
+
+/// Every pair of distinct active bodies, each once.
+fn pairs(bodies: &[Body]) -> impl Iterator<Item = (&Body, &Body)> {
+    bodies.iter().enumerate().flat_map(move |(i, a)| {
+        bodies[i + 1..]
+            .iter()
+            .filter(move |b| a.is_active() && b.is_active())
+            .map(move |b| (a, b))
+    })
+}
+
+/// The fraction erf(X) - 2X/√π exp(-X²) of a Maxwellian background slower than X = v / (√2 σ),
+/// appearing in the dynamical friction and diffusion coefficients.
+fn slower_fraction(x: f64) -> f64 {
+    if x < 0.3 {
+        // Series expansion, avoiding the cancellation between the two terms
+        let x2 = x * x;
+        2. / PI.sqrt() * x * x2 * (2. / 3. - x2 * (2. / 5. - x2 * (1. / 7. - x2 / 27.)))
+    } else {
+        erf(x) - 2. * x / PI.sqrt() * (-x * x).exp()
+    }
+}
+
+/// Error function, accurate to about 1.2e-7.
+fn erf(x: f64) -> f64 {
+    1. - erfc(x)
+}
+
+/// Complementary error function, accurate to about 1.2e-7 (Numerical Recipes' Chebyshev fit).
+fn erfc(x: f64) -> f64 {
+    let t = 1. / (1. + 0.5 * x.abs());
+    let erfc = t
+        * (-x * x - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0. {
+        erfc
+    } else {
+        2. - erfc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{body, vec3::Point3};
+
+    #[test]
+    fn forces_follow_bodies_as_others_are_removed() {
+        let mut bodies: Vec<Body> = (0..4)
+            .map(|i| {
+                let i = i as f64;
+                Body::new(
+                    Point3::new(i, i * i, 0.5),
+                    Vec3::new(0., 0.2 * i, 0.1),
+                    1. + i,
+                )
+            })
+            .collect();
+        body::assign_ids(&mut bodies);
+        // The removed body exerts no force, so the others' accelerations shouldn't change
+        bodies[0].test = true;
+        let forces: Vec<Box<dyn Force>> = vec![
+            Box::new(RadiationPressure {
+                source: 1,
+                beta: 0.5,
+                bodies: vec![2, 3],
+                c: 10.,
+            }),
+            Box::new(Oblateness {
+                body: 2,
+                j2: 1e-2,
+                radius: 0.5,
+            }),
+            "primary=1,secondary=3,k2=0.3,lag=0.1,radius=0.5"
+                .parse::<Tide>()
+                .map(|tide| Box::new(tide) as Box<dyn Force>)
+                .unwrap(),
+            Box::new(Drag {
+                linear: 0.1,
+                quadratic: 0.,
+                radius: None,
+                centre: Some(2),
+            }),
+            Box::new(DynamicalFriction {
+                bodies: vec![3],
+                density: 0.1,
+                dispersion: 1.,
+                coulomb_logarithm: 3.,
+            }),
+        ];
+        let before = accelerations(&forces, &bodies);
+        bodies.remove(0);
+        let after = accelerations(&forces, &bodies);
+        assert_eq!(after.len(), 3);
+        for (after, before) in after.iter().zip(&before[1..]) {
+            assert_eq!((*after - *before).length_squared(), 0.);
+        }
+    }
+}
@@ -1,91 +1,177 @@
-use std::fmt::Display;
+use serde::{Serialize, Serializer};
 
-use crate::vec3::{Point3, Vec3};
+use crate::{
+    body::{self, Body},
+    boundary::SimulationBox,
+    collision::{self, CollisionMode},
+    cosmology::Cosmology,
+    event::{Event, Events},
+    force::{self, Force},
+    observer::Observers,
+    simulation::{Simulation, WorldState},
+};
 
 #[derive(Debug)]
-pub struct Body {
-    pub position: Point3,
-    pub velocity: Vec3,
-    pub mass: f64,
-    next_velocity: Vec3,
+pub struct World {
+    bodies: Vec<Body>,
+    forces: Vec<Box<dyn Force>>,
+    collision_mode: Option<CollisionMode>,
+    restitution: f64,
+    events: Events,
+    simulation_box: Option<SimulationBox>,
+    cosmology: Option<Cosmology>,
+    observers: Observers,
+    /// Id of the next body to be added.
+    next_id: usize,
+    time: f64,
 }
 
-impl Body {
-    fn acceleration(&self, from: &Self) -> Vec3 {
-        let r: Vec3 = from.position - self.position;
-
-        (from.mass / r.length().powf(3.0)) * r
-    }
-
-    pub fn new(position: Point3, velocity: Vec3, mass: f64) -> Self {
+impl World {
+    pub fn new(mut bodies: Vec<Body>, forces: Vec<Box<dyn Force>>) -> Self {
+        let next_id = body::assign_ids(&mut bodies);
         Self {
-            position,
-            velocity,
-            mass,
-            next_velocity: velocity,
+            bodies,
+            forces,
+            collision_mode: None,
+            restitution: 1.,
+            events: Events::default(),
+            simulation_box: None,
+            cosmology: None,
+            observers: Observers::default(),
+            next_id,
+            time: 0.,
         }
     }
 }
 
-impl Display for Body {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "r = [{:e}], v = [{:e}], Gm = {:e}",
-            self.position, self.velocity, self.mass
-        )
+/// Serialised as its [`SavedWorld`](crate::simulation::SavedWorld).
+impl Serialize for World {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.save().serialize(serializer)
     }
 }
 
-#[derive(Debug)]
-pub struct World {
-    bodies: Vec<Body>,
-    time: f64,
-}
-
-impl World {
-    pub fn new(bodies: Vec<Body>) -> Self {
-        Self { bodies, time: 0. }
-    }
-
-    pub fn tick(&mut self, tick_duration: f64) {
-        // Calculate and integrate accelerations
-        for i in 0..self.bodies.len() {
-            let acceleration: Vec3 = self
-                .bodies
-                .iter()
-                .enumerate()
-                .filter_map(|(j, b)| (i != j).then(|| self.bodies[i].acceleration(b))) // forces from all bodies except itself
-                .sum();
-            self.bodies[i].next_velocity += acceleration * tick_duration;
+impl Simulation for World {
+    fn tick(&mut self, tick_duration: f64) {
+        // Calculate accelerations from the current state
+        let mut accelerations = force::accelerations(&self.forces, &self.bodies);
+        if let Some(cosmology) = &self.cosmology {
+            cosmology.comoving_accelerations(&self.bodies, &mut accelerations);
         }
-        // Integrate velocities and then update
-        for body in &mut self.bodies {
-            body.position += body.velocity * tick_duration;
-            body.velocity = body.next_velocity;
+        // Integrate velocities and accelerations
+        for (body, acceleration) in self.bodies.iter_mut().zip(accelerations) {
+            if body.is_mobile() {
+                body.position += body.velocity * tick_duration;
+            }
+            body.velocity += acceleration * tick_duration;
+        }
+        self.confine_bodies();
+        // Apply stochastic velocity kicks
+        let kicks = force::kicks(&mut self.forces, &self.bodies, tick_duration);
+        for (body, kick) in self.bodies.iter_mut().zip(kicks) {
+            body.velocity += kick;
         }
         self.time += tick_duration;
+        if let Some(cosmology) = &mut self.cosmology {
+            cosmology.advance(tick_duration);
+        }
+        // Evolve masses
+        for body in &mut self.bodies {
+            body.evolve_mass(tick_duration);
+        }
+        // Resolve collisions
+        let periodic = self.simulation_box.and_then(|b| b.periodic());
+        if let Some(mode) = self.collision_mode {
+            let collisions = collision::resolve(
+                mode,
+                self.restitution,
+                &mut self.bodies,
+                self.time,
+                periodic,
+            );
+            for collision in collisions {
+                self.events.emit(Event::Collision(collision));
+            }
+        }
+        self.events.detect(self.time, &self.bodies, periodic);
+        self.notify_observers();
     }
 
-    pub fn time(&self) -> f64 {
+    fn time(&self) -> f64 {
         self.time
     }
 
-    pub fn bodies(&self) -> &Vec<Body> {
+    fn bodies(&self) -> &Vec<Body> {
         &self.bodies
     }
 
-    /// Converts into the Galilean rest frame of the body and index i
-    pub fn into_rest_frame(&mut self, i: usize) {
-        let (r_position, r_velocity) = self
-            .bodies()
-            .get(i)
-            .map(|r| (r.position, r.velocity))
-            .unwrap_or((Point3::ZERO, Vec3::ZERO));
-        for body in &mut self.bodies {
-            body.position -= r_position;
-            body.velocity -= r_velocity;
-            body.next_velocity -= r_velocity;
+    fn bodies_mut(&mut self) -> &mut Vec<Body> {
+        &mut self.bodies
+    }
+
+    fn add_body(&mut self, mut body: Body) -> usize {
+        body.id = self.next_id;
+        self.next_id += 1;
+        self.bodies.push(body);
+        self.next_id - 1
+    }
+
+    fn forces(&self) -> &[Box<dyn Force>] {
+        &self.forces
+    }
+
+    fn scheme(&self) -> &'static str {
+        "Forward Euler: a[n] = f(x[n]), x[n+1] = x[n] + δt v[n], v[n+1] = v[n] + δt a[n]"
+    }
+
+    fn set_collision_mode(&mut self, mode: CollisionMode, restitution: f64) {
+        self.collision_mode = Some(mode);
+        self.restitution = restitution;
+    }
+
+    fn events(&self) -> &Events {
+        &self.events
+    }
+
+    fn events_mut(&mut self) -> &mut Events {
+        &mut self.events
+    }
+
+    fn simulation_box(&self) -> Option<SimulationBox> {
+        self.simulation_box
+    }
+
+    fn set_simulation_box(&mut self, simulation_box: SimulationBox) {
+        self.simulation_box = Some(simulation_box);
+    }
+
+    fn cosmology(&self) -> Option<Cosmology> {
+        self.cosmology
+    }
+
+    fn set_cosmology(&mut self, cosmology: Cosmology) {
+        self.cosmology = Some(cosmology);
+    }
+
+    fn world_state(&self) -> WorldState {
+        WorldState {
+            time: self.time,
+            started: false,
+            tick: 0.,
+            next_id: self.next_id,
+            cosmology: self.cosmology,
+            rngs: force::rngs(&self.forces),
         }
     }
+
+    fn restore_world_state(&mut self, state: WorldState) {
+        self.time = state.time;
+        self.next_id = state.next_id;
+        self.cosmology = state.cosmology;
+        force::restore_rngs(&mut self.forces, state.rngs);
+    }
+
+    fn observers_mut(&mut self) -> &mut Observers {
+        &mut self.observers
+    }
 }
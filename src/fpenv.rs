@@ -0,0 +1,66 @@
+use std::hint::black_box;
+
+/// Floating-point behaviour of this build and machine which can make trajectories diverge
+/// between machines.
+#[derive(Debug)]
+pub struct FpEnvironment {
+    /// Whether subnormal results are flushed to zero.
+    pub flush_to_zero: bool,
+    /// Whether subnormal operands are treated as zero.
+    pub denormals_are_zero: bool,
+    /// Whether intermediate results carry extra precision, as with the x87 FPU.
+    pub extended_precision: bool,
+    /// Whether the processor has fused multiply-add instructions.
+    pub fma_available: bool,
+    /// Whether this build was compiled to use fused multiply-add instructions for `mul_add`.
+    pub fma_compiled: bool,
+}
+
+impl FpEnvironment {
+    /// Probes the floating-point environment. Operands go through `black_box` so the probes
+    /// are evaluated at run time rather than folded by the compiler.
+    pub fn detect() -> Self {
+        let subnormal = f64::MIN_POSITIVE / 4.;
+        Self {
+            flush_to_zero: black_box(f64::MIN_POSITIVE) / black_box(4.) == 0.,
+            denormals_are_zero: black_box(subnormal) * black_box(1.) == 0.,
+            // 1e16 + 1 rounds back to 1e16 in double precision, but not in extended precision
+            extended_precision: black_box(1e16) + black_box(1.) - black_box(1e16) != 0.,
+            fma_available: fma_available(),
+            fma_compiled: cfg!(target_feature = "fma"),
+        }
+    }
+
+    /// Whether arithmetic follows IEEE 754 double precision exactly, so that runs reproduce
+    /// across conforming machines.
+    pub fn is_strict(&self) -> bool {
+        !self.flush_to_zero && !self.denormals_are_zero && !self.extended_precision
+    }
+
+    pub fn print(&self) {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        println!("Floating-point environment:");
+        println!("  architecture: {}", std::env::consts::ARCH);
+        println!("  flush to zero: {}", yes_no(self.flush_to_zero));
+        println!("  denormals are zero: {}", yes_no(self.denormals_are_zero));
+        println!("  extended precision: {}", yes_no(self.extended_precision));
+        println!("  FMA available: {}", yes_no(self.fma_available));
+        println!("  FMA compiled in: {}", yes_no(self.fma_compiled));
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn fma_available() -> bool {
+    is_x86_feature_detected!("fma")
+}
+
+#[cfg(target_arch = "aarch64")]
+fn fma_available() -> bool {
+    // Always part of the base architecture
+    true
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+fn fma_available() -> bool {
+    false
+}
@@ -0,0 +1,87 @@
+//! Export to the Gadget-2 snapshot format (`SnapFormat=1`), which is read by most cosmological
+//! simulation analysis tools.
+//!
+//! Each block is a Fortran unformatted record, with its length in bytes as a u32 before and after
+//! it. The file has a 256 byte header, then the positions and velocities (3 f32 each per body),
+//! the particle IDs (u32) and, unless every body has the same mass, the masses (f32). All values are
+//! little-endian and every body is written as particle type 1.
+
+use std::io::{self, Write};
+
+use crate::{body::Body, vec3::Vec3};
+
+const HEADER_SIZE: usize = 256;
+const PARTICLE_TYPE: usize = 1;
+
+/// Writes the active bodies at `time` as a Gadget-2 snapshot, with masses converted from Gm with the
+/// gravitational constant `g` and each body's index in `bodies` as its particle ID.
+pub fn write(mut writer: impl Write, time: f64, bodies: &[Body], g: f64) -> io::Result<()> {
+    let active: Vec<(usize, &Body)> = (bodies.iter().enumerate())
+        .filter(|(_, body)| body.is_active())
+        .collect();
+    let count = u32::try_from(active.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many bodies for Gadget"))?;
+    let masses: Vec<f64> = active.iter().map(|(_, body)| body.mass / g).collect();
+    let mass_table = match masses.first() {
+        Some(&mass) if masses.iter().all(|&m| m == mass) => mass,
+        _ => 0.,
+    };
+
+    let mut counts = [0u32; 6];
+    counts[PARTICLE_TYPE] = count;
+    let mut mass_tables = [0.; 6];
+    mass_tables[PARTICLE_TYPE] = mass_table;
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    counts.iter().for_each(|n| header.extend(n.to_le_bytes()));
+    mass_tables
+        .iter()
+        .for_each(|m| header.extend(m.to_le_bytes()));
+    header.extend(time.to_le_bytes());
+    // Redshift
+    header.extend(0f64.to_le_bytes());
+    // Star formation and feedback flags
+    header.extend([0; 8]);
+    // Total numbers of particles across all files
+    counts.iter().for_each(|n| header.extend(n.to_le_bytes()));
+    // Cooling flag and number of files
+    header.extend(0i32.to_le_bytes());
+    header.extend(1i32.to_le_bytes());
+    // Box size, Ω₀, Ω_Λ and Hubble parameter
+    header.extend([0; 32]);
+    // Stellar age and metals flags, high words of the totals and entropy flag
+    header.extend([0; 8 + 24 + 4]);
+    header.resize(HEADER_SIZE, 0);
+    write_block(&mut writer, &header)?;
+
+    let vectors = |f: fn(&Body) -> Vec3| -> Vec<u8> {
+        (active.iter())
+            .flat_map(|(_, body)| {
+                let v = f(body);
+                [v.x(), v.y(), v.z()]
+            })
+            .flat_map(|x| (x as f32).to_le_bytes())
+            .collect()
+    };
+    write_block(&mut writer, &vectors(|body| body.position))?;
+    write_block(&mut writer, &vectors(|body| body.velocity))?;
+    let ids: Vec<u8> = (active.iter())
+        .flat_map(|&(i, _)| (i as u32).to_le_bytes())
+        .collect();
+    write_block(&mut writer, &ids)?;
+    if mass_table == 0. && !masses.is_empty() {
+        let masses: Vec<u8> = (masses.iter())
+            .flat_map(|&m| (m as f32).to_le_bytes())
+            .collect();
+        write_block(&mut writer, &masses)?;
+    }
+    writer.flush()
+}
+
+fn write_block(writer: &mut impl Write, block: &[u8]) -> io::Result<()> {
+    let size = u32::try_from(block.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Gadget block too large"))?
+        .to_le_bytes();
+    writer.write_all(&size)?;
+    writer.write_all(block)?;
+    writer.write_all(&size)
+}
@@ -0,0 +1,595 @@
+use std::{f64::consts::PI, str::FromStr};
+
+use crate::{
+    body::Body,
+    elements::Elements,
+    parameters::Parameters,
+    potential::Potential,
+    rng::Rng,
+    vec3::{Point3, Vec3},
+};
+
+/// Generators of initial conditions. All masses are in Gm units, and systems are centred on the
+/// origin.
+#[derive(Clone, Debug)]
+pub enum Generator {
+    /// Self-gravitating exponential disk with surface density ∝ exp(-R/scale) and a sech² vertical
+    /// profile of scale `height`, with radial velocity dispersions set by Toomre's Q, around an
+    /// optional central point mass `bulge`.
+    Disk {
+        n: usize,
+        mass: f64,
+        scale: f64,
+        height: f64,
+        q: f64,
+        bulge: f64,
+    },
+    /// Uniform sphere of equal masses with isotropic Gaussian velocities scaled to the virial
+    /// ratio 2T/|W|, which is 1 in equilibrium and 0 for a cold collapse.
+    UniformSphere {
+        n: usize,
+        mass: f64,
+        radius: f64,
+        virial: f64,
+    },
+    /// Plummer sphere of equal masses with density ∝ (1 + r²/scale²)^(-5/2), with velocities drawn
+    /// from its isotropic distribution function.
+    Plummer { n: usize, mass: f64, scale: f64 },
+    /// King model of equal masses with dimensionless central potential `w0` and King radius
+    /// `scale`, with velocities drawn from its lowered isothermal distribution function.
+    King {
+        n: usize,
+        mass: f64,
+        scale: f64,
+        w0: f64,
+    },
+    /// Binary of masses `m1` and `m2`, with the `orbit` of the second relative to the first.
+    Binary { m1: f64, m2: f64, orbit: Elements },
+    /// Hierarchical triple of an inner binary of `m1` and `m2` and an outer companion `m3`, with
+    /// the outer orbit in the xy plane and the inner orbit at a mutual inclination `i` and
+    /// argument of periapsis `periapsis`, as in Kozai-Lidov experiments.
+    Triple {
+        m1: f64,
+        m2: f64,
+        m3: f64,
+        inner: Elements,
+        outer: Elements,
+    },
+    /// Two virialised uniform spheres of half the bodies each, `separation` apart along x and
+    /// approaching each other at `speed` with an `impact` parameter along y.
+    Merger {
+        n: usize,
+        mass: f64,
+        radius: f64,
+        separation: f64,
+        speed: f64,
+        impact: f64,
+    },
+}
+
+impl Generator {
+    /// Generates bodies in equilibrium with their own gravity and the external `potentials`.
+    pub fn generate(&self, potentials: &[Potential], rng: &mut Rng) -> Vec<Body> {
+        match *self {
+            Self::Disk {
+                n,
+                mass,
+                scale,
+                height,
+                q,
+                bulge,
+            } => {
+                if bulge <= 0. {
+                    return disk(n, mass, scale, height, q, potentials, rng);
+                }
+                // The disk rotates in the potential of the bulge at the origin
+                let mut potentials = potentials.to_vec();
+                potentials.push(Potential::Kepler { mass: bulge });
+                let origin = Point3::new(0., 0., 0.);
+                let mut bodies = vec![Body::new(origin, Vec3::new(0., 0., 0.), bulge)];
+                bodies.extend(disk(n, mass, scale, height, q, &potentials, rng));
+                bodies
+            }
+            Self::UniformSphere {
+                n,
+                mass,
+                radius,
+                virial,
+            } => uniform_sphere(n, mass, radius, virial, rng),
+            Self::Plummer { n, mass, scale } => plummer(n, mass, scale, rng),
+            Self::King { n, mass, scale, w0 } => king(n, mass, scale, w0, rng),
+            Self::Binary { m1, m2, orbit } => binary(m1, m2, &orbit).to_vec(),
+            Self::Triple {
+                m1,
+                m2,
+                m3,
+                inner,
+                outer,
+            } => {
+                let [mut first, mut second] = binary(m1, m2, &inner);
+                let [centre, third] = binary(m1 + m2, m3, &outer);
+                for body in [&mut first, &mut second] {
+                    body.position += centre.position;
+                    body.velocity += centre.velocity;
+                }
+                vec![first, second, third]
+            }
+            Self::Merger {
+                n,
+                mass,
+                radius,
+                separation,
+                speed,
+                impact,
+            } => {
+                let mut bodies = uniform_sphere(n / 2, mass / 2., radius, 1., rng);
+                let offset = Vec3::new(separation / 2., impact / 2., 0.);
+                let velocity = Vec3::new(speed / 2., 0., 0.);
+                for body in &mut bodies {
+                    body.position -= offset;
+                    body.velocity += velocity;
+                }
+                let mut other = uniform_sphere(n - n / 2, mass / 2., radius, 1., rng);
+                for body in &mut other {
+                    body.position += offset;
+                    body.velocity -= velocity;
+                }
+                bodies.append(&mut other);
+                bodies
+            }
+        }
+    }
+
+    /// Parses generators of the form `name:key=value,key=value`, with `n` as the number of bodies
+    /// if it isn't given as a parameter.
+    pub fn parse(s: &str, n: Option<usize>) -> Result<Self, String> {
+        let (name, parameters) = s.split_once(':').unwrap_or((s, ""));
+        let mut parameters = Parameters::parse(parameters)?;
+        let context = |e: String| format!("{} for {} generator", e, name);
+
+        let generator = match name {
+            "disk" => {
+                let scale = parameters.take("scale", Some(1.)).map_err(context)?;
+                Self::Disk {
+                    n: parameters.take_index("n", n).map_err(context)?,
+                    mass: parameters.take("mass", Some(1.)).map_err(context)?,
+                    scale,
+                    height: parameters
+                        .take("height", Some(0.1 * scale))
+                        .map_err(context)?,
+                    q: parameters.take("q", Some(1.5)).map_err(context)?,
+                    bulge: parameters.take("bulge", Some(0.)).map_err(context)?,
+                }
+            }
+            "uniform-sphere" | "cold-collapse" => Self::UniformSphere {
+                n: parameters.take_index("n", n).map_err(context)?,
+                mass: parameters.take("mass", Some(1.)).map_err(context)?,
+                radius: parameters.take("radius", Some(1.)).map_err(context)?,
+                virial: match name {
+                    "uniform-sphere" => parameters.take("virial", Some(1.)).map_err(context)?,
+                    _ => 0.,
+                },
+            },
+            "plummer" => Self::Plummer {
+                n: parameters.take_index("n", n).map_err(context)?,
+                mass: parameters.take("mass", Some(1.)).map_err(context)?,
+                scale: parameters.take("scale", Some(1.)).map_err(context)?,
+            },
+            "king" => {
+                let w0 = parameters.take("w0", Some(6.)).map_err(context)?;
+                if !(w0 > 0. && w0 <= 20.) {
+                    return Err(context("`w0` must be between 0 and 20".to_string()));
+                }
+                Self::King {
+                    n: parameters.take_index("n", n).map_err(context)?,
+                    mass: parameters.take("mass", Some(1.)).map_err(context)?,
+                    scale: parameters.take("scale", Some(1.)).map_err(context)?,
+                    w0,
+                }
+            }
+            "binary" => {
+                let mut take = |key, default| parameters.take(key, Some(default)).map_err(context);
+                Self::Binary {
+                    m1: take("m1", 1.)?,
+                    m2: take("m2", 1.)?,
+                    orbit: Elements {
+                        a: take("a", 1.)?,
+                        e: take("e", 0.)?,
+                        i: 0.,
+                        node: 0.,
+                        periapsis: 0.,
+                        anomaly: take("anomaly", 0.)?.to_radians(),
+                    },
+                }
+            }
+            "triple" => {
+                let mut take = |key, default| parameters.take(key, Some(default)).map_err(context);
+                Self::Triple {
+                    m1: take("m1", 1.)?,
+                    m2: take("m2", 1.)?,
+                    m3: take("m3", 1.)?,
+                    inner: Elements {
+                        a: take("a1", 1.)?,
+                        e: take("e1", 0.)?,
+                        i: take("i", 0.)?.to_radians(),
+                        node: 0.,
+                        periapsis: take("periapsis", 0.)?.to_radians(),
+                        anomaly: take("anomaly1", 0.)?.to_radians(),
+                    },
+                    outer: Elements {
+                        a: take("a2", 10.)?,
+                        e: take("e2", 0.)?,
+                        i: 0.,
+                        node: 0.,
+                        periapsis: 0.,
+                        anomaly: take("anomaly2", 0.)?.to_radians(),
+                    },
+                }
+            }
+            "merger" => {
+                let mass = parameters.take("mass", Some(1.)).map_err(context)?;
+                let radius = parameters.take("radius", Some(1.)).map_err(context)?;
+                let separation =
+                    (parameters.take("separation", Some(10. * radius))).map_err(context)?;
+                Self::Merger {
+                    n: parameters.take_index("n", n).map_err(context)?,
+                    mass,
+                    radius,
+                    separation,
+                    // Parabolic approach of two point masses
+                    speed: (parameters.take("speed", Some((2. * mass / separation).sqrt())))
+                        .map_err(context)?,
+                    impact: parameters.take("impact", Some(0.)).map_err(context)?,
+                }
+            }
+            _ => return Err(format!("unknown generator `{}`", name)),
+        };
+        parameters.finish().map_err(context)?;
+        match &generator {
+            Self::Binary { orbit, .. } => orbit.check().map_err(context)?,
+            Self::Triple { inner, outer, .. } => {
+                inner.check().map_err(context)?;
+                outer.check().map_err(context)?;
+            }
+            _ => {}
+        }
+        Ok(generator)
+    }
+}
+
+/// Uniform sphere of `n` equal masses about the origin at rest, with velocities scaled to the
+/// `virial` ratio 2T/|W| using the potential energy -3M²/5R of a continuous uniform sphere.
+fn uniform_sphere(n: usize, mass: f64, radius: f64, virial: f64, rng: &mut Rng) -> Vec<Body> {
+    let mut bodies: Vec<Body> = (0..n)
+        .map(|_| {
+            let r = radius * rng.uniform().cbrt();
+            Body::new(
+                r * isotropic(rng),
+                Vec3::new(rng.normal(), rng.normal(), rng.normal()),
+                mass / n as f64,
+            )
+        })
+        .collect();
+    recentre(&mut bodies);
+    let kinetic: f64 = (bodies.iter())
+        .map(|body| 0.5 * body.mass * body.velocity.length_squared())
+        .sum();
+    let target = 0.5 * virial * 0.6 * mass * mass / radius;
+    let scale = if kinetic > 0. {
+        (target / kinetic).sqrt()
+    } else {
+        0.
+    };
+    for body in &mut bodies {
+        body.velocity *= scale;
+    }
+    bodies
+}
+
+/// Plummer sphere sampled as by Aarseth, Hénon & Wielen (1974).
+fn plummer(n: usize, mass: f64, scale: f64, rng: &mut Rng) -> Vec<Body> {
+    let mut bodies: Vec<Body> = (0..n)
+        .map(|_| {
+            // Invert the cumulative mass M(<r)/M = r³/(r² + a²)^(3/2)
+            let r = loop {
+                let r = scale / (rng.uniform().powf(-2. / 3.) - 1.).sqrt();
+                if r.is_finite() {
+                    break r;
+                }
+            };
+            // Speed as a fraction of the escape speed, with density ∝ q²(1 - q²)^(7/2)
+            let q = loop {
+                let q = rng.uniform();
+                if 0.1 * rng.uniform() < q * q * (1. - q * q).powf(3.5) {
+                    break q;
+                }
+            };
+            let escape_speed = (2. * mass / (r * r + scale * scale).sqrt()).sqrt();
+            Body::new(
+                r * isotropic(rng),
+                q * escape_speed * isotropic(rng),
+                mass / n as f64,
+            )
+        })
+        .collect();
+    recentre(&mut bodies);
+    bodies
+}
+
+/// King model, with the potential found by integrating Poisson's equation outwards from the centre
+/// to the tidal radius in units of the King radius and velocity dispersion parameter.
+fn king(n: usize, mass: f64, scale: f64, w0: f64, rng: &mut Rng) -> Vec<Body> {
+    // Density relative to the centre as a function of the dimensionless potential W
+    let central_density = king_density(w0);
+    let density = |w: f64| king_density(w) / central_density;
+    // In terms of x = ln r, with y = r² dW/dr = -M(<r), Poisson's equation is dW/dx = y/r and
+    // dy/dx = -9r³ρ
+    let derivatives = |x: f64, (w, y): (f64, f64)| {
+        let r = x.exp();
+        (y / r, -9. * r * r * r * density(w))
+    };
+    let step = 1e-3;
+    let r = 1e-4;
+    let (mut x, mut state) = (f64::ln(r), (w0 - 1.5 * r * r, -3. * r * r * r));
+    // Radius, mass enclosed and potential
+    let mut profile = vec![(0., 0., w0)];
+    while state.0 > 0. {
+        profile.push((x.exp(), -state.1, state.0));
+        let k1 = derivatives(x, state);
+        let k2 = derivatives(
+            x + step / 2.,
+            (state.0 + step / 2. * k1.0, state.1 + step / 2. * k1.1),
+        );
+        let k3 = derivatives(
+            x + step / 2.,
+            (state.0 + step / 2. * k2.0, state.1 + step / 2. * k2.1),
+        );
+        let k4 = derivatives(x + step, (state.0 + step * k3.0, state.1 + step * k3.1));
+        let next = (
+            state.0 + step / 6. * (k1.0 + 2. * k2.0 + 2. * k3.0 + k4.0),
+            state.1 + step / 6. * (k1.1 + 2. * k2.1 + 2. * k3.1 + k4.1),
+        );
+        if next.0 <= 0. {
+            // Interpolate to the tidal radius, where W = 0
+            let t = state.0 / (state.0 - next.0);
+            let tidal = (x + t * step).exp();
+            profile.push((tidal, -(state.1 + t * (next.1 - state.1)), 0.));
+        }
+        (x, state) = (x + step, next);
+    }
+    let total = profile.last().unwrap().1;
+    // Units with G = σ = r₀ = 1 scaled to the requested mass and King radius
+    let speed_unit = (mass / (total * scale)).sqrt();
+
+    let mut bodies: Vec<Body> = (0..n)
+        .map(|_| {
+            let enclosed = total * rng.uniform();
+            let k = profile.partition_point(|&(_, m, _)| m < enclosed).max(1);
+            let ((r0, m0, w0), (r1, m1, w1)) = (profile[k - 1], profile[k]);
+            let t = if m1 > m0 {
+                (enclosed - m0) / (m1 - m0)
+            } else {
+                0.
+            };
+            let (r, w) = (r0 + t * (r1 - r0), w0 + t * (w1 - w0));
+            // Speed as a fraction x of the escape speed √(2W), with density ∝ x²(exp(W(1 - x²)) - 1)
+            let weight = |x: f64| x * x * ((w * (1. - x * x)).exp() - 1.);
+            let envelope = 1.1 * (1..200).map(|i| weight(i as f64 / 200.)).fold(0., f64::max);
+            let x = loop {
+                let x = rng.uniform();
+                if envelope * rng.uniform() < weight(x) {
+                    break x;
+                }
+            };
+            Body::new(
+                scale * r * isotropic(rng),
+                speed_unit * x * (2. * w).sqrt() * isotropic(rng),
+                mass / n as f64,
+            )
+        })
+        .collect();
+    recentre(&mut bodies);
+    bodies
+}
+
+/// Density of a King model at dimensionless potential `w`, up to a constant factor, which is
+/// exp(W) P(5/2, W) for the regularised lower incomplete gamma function P.
+fn king_density(w: f64) -> f64 {
+    if w <= 0. {
+        return 0.;
+    }
+    // Series for γ(5/2, W) exp(W) / W^(5/2)
+    let (mut term, mut sum) = (1. / 2.5, 1. / 2.5);
+    let mut k = 1.;
+    while term > 1e-17 * sum {
+        term *= w / (2.5 + k);
+        sum += term;
+        k += 1.;
+    }
+    w.powf(2.5) * sum
+}
+
+/// Bodies of masses `m1` and `m2` about their centre of mass at rest, with the second on an orbit
+/// of the given `elements` relative to the first.
+fn binary(m1: f64, m2: f64, elements: &Elements) -> [Body; 2] {
+    let (position, velocity) = (elements.to_cartesian(m1 + m2))
+        .expect("Orbital elements are checked when the generator is parsed");
+    let (f1, f2) = (m2 / (m1 + m2), m1 / (m1 + m2));
+    [
+        Body::new(-f1 * position, -f1 * velocity, m1),
+        Body::new(f2 * position, f2 * velocity, m2),
+    ]
+}
+
+/// Uniformly distributed unit vector.
+fn isotropic(rng: &mut Rng) -> Vec3 {
+    let z = 2. * rng.uniform() - 1.;
+    let (sin, cos) = (2. * PI * rng.uniform()).sin_cos();
+    let xy = (1. - z * z).sqrt();
+    Vec3::new(xy * cos, xy * sin, z)
+}
+
+/// Moves the centre of mass of bodies to the origin at rest.
+fn recentre(bodies: &mut [Body]) {
+    let mass: f64 = bodies.iter().map(|body| body.mass).sum();
+    if mass <= 0. {
+        return;
+    }
+    let position: Vec3 = bodies.iter().map(|body| body.mass * body.position).sum();
+    let velocity: Vec3 = bodies.iter().map(|body| body.mass * body.velocity).sum();
+    for body in bodies {
+        body.position -= position / mass;
+        body.velocity -= velocity / mass;
+    }
+}
+
+fn disk(
+    n: usize,
+    mass: f64,
+    scale: f64,
+    height: f64,
+    q: f64,
+    potentials: &[Potential],
+    rng: &mut Rng,
+) -> Vec<Body> {
+    let surface_density = |r: f64| mass / (2. * PI * scale * scale) * (-r / scale).exp();
+    // Circular speed squared of the Freeman disk plus the external potentials in the disk plane
+    let circular_speed2 = |r: f64| {
+        let y = r / (2. * scale);
+        let disk = if y < 300. {
+            2. * mass / scale * y * y * (bessel_i0(y) * bessel_k0(y) - bessel_i1(y) * bessel_k1(y))
+        } else {
+            mass / r
+        };
+        let external: f64 = potentials
+            .iter()
+            .map(|p| -r * p.acceleration(Point3::new(r, 0., 0.)).x())
+            .sum();
+        disk + external
+    };
+
+    (0..n)
+        .map(|_| {
+            // The radius of an exponential disk follows a gamma distribution with shape 2
+            let r = -scale * ((1. - rng.uniform()) * (1. - rng.uniform())).ln();
+            let phi = 2. * PI * rng.uniform();
+            let z = height * (2. * rng.uniform() - 1.).atanh();
+
+            let v2 = circular_speed2(r);
+            let omega2 = v2 / (r * r);
+            let h = 1e-4 * r;
+            let dv2 = (circular_speed2(r + h) - circular_speed2(r - h)) / (2. * h);
+            // κ² = R dΩ²/dR + 4Ω²
+            let kappa2 = (dv2 / r - 2. * omega2 + 4. * omega2).max(0.);
+            let sigma_r = q * 3.36 * surface_density(r) / kappa2.sqrt();
+            let sigma_phi = sigma_r * (kappa2 / (4. * omega2)).sqrt();
+            let sigma_z = (PI * surface_density(r) * height).sqrt();
+            // Asymmetric drift lowers the mean rotation of the warmer parts of the disk
+            let mean_phi = (v2
+                + sigma_r * sigma_r * (1. - kappa2 / (4. * omega2) - 2. * r / scale))
+                .max(0.)
+                .sqrt();
+
+            let v_r = sigma_r * rng.normal();
+            let v_phi = mean_phi + sigma_phi * rng.normal();
+            let v_z = sigma_z * rng.normal();
+            let (sin, cos) = phi.sin_cos();
+            Body::new(
+                Point3::new(r * cos, r * sin, z),
+                Vec3::new(v_r * cos - v_phi * sin, v_r * sin + v_phi * cos, v_z),
+                mass / n as f64,
+            )
+        })
+        .collect()
+}
+
+/// Parses generators of the form `name:key=value,key=value`, e.g. `disk:n=1000,mass=1,scale=1`.
+impl FromStr for Generator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, None)
+    }
+}
+
+// Modified Bessel functions, from the polynomial approximations of Abramowitz & Stegun 9.8.
+
+fn bessel_i0(x: f64) -> f64 {
+    if x <= 3.75 {
+        let t = (x / 3.75).powi(2);
+        1. + t
+            * (3.5156229
+                + t * (3.0899424
+                    + t * (1.2067492 + t * (0.2659732 + t * (0.0360768 + t * 0.0045813)))))
+    } else {
+        let t = 3.75 / x;
+        x.exp() / x.sqrt()
+            * (0.39894228
+                + t * (0.01328592
+                    + t * (0.00225319
+                        + t * (-0.00157565
+                            + t * (0.00916281
+                                + t * (-0.02057706
+                                    + t * (0.02635537 + t * (-0.01647633 + t * 0.00392377))))))))
+    }
+}
+
+fn bessel_i1(x: f64) -> f64 {
+    if x <= 3.75 {
+        let t = (x / 3.75).powi(2);
+        x * (0.5
+            + t * (0.87890594
+                + t * (0.51498869
+                    + t * (0.15084934 + t * (0.02658733 + t * (0.00301532 + t * 0.00032411))))))
+    } else {
+        let t = 3.75 / x;
+        x.exp() / x.sqrt()
+            * (0.39894228
+                + t * (-0.03988024
+                    + t * (-0.00362018
+                        + t * (0.00163801
+                            + t * (-0.01031555
+                                + t * (0.02282967
+                                    + t * (-0.02895312 + t * (0.01787654 - t * 0.00420059))))))))
+    }
+}
+
+fn bessel_k0(x: f64) -> f64 {
+    if x <= 2. {
+        let t = x * x / 4.;
+        -(x / 2.).ln() * bessel_i0(x)
+            + (-0.57721566
+                + t * (0.42278420
+                    + t * (0.23069756
+                        + t * (0.03488590 + t * (0.00262698 + t * (0.00010750 + t * 0.0000074))))))
+    } else {
+        let t = 2. / x;
+        (-x).exp() / x.sqrt()
+            * (1.25331414
+                + t * (-0.07832358
+                    + t * (0.02189568
+                        + t * (-0.01062446
+                            + t * (0.00587872 + t * (-0.00251540 + t * 0.00053208))))))
+    }
+}
+
+fn bessel_k1(x: f64) -> f64 {
+    if x <= 2. {
+        let t = x * x / 4.;
+        (x * (x / 2.).ln() * bessel_i1(x)
+            + (1.
+                + t * (0.15443144
+                    + t * (-0.67278579
+                        + t * (-0.18156897
+                            + t * (-0.01919402 + t * (-0.00110404 - t * 0.00004686)))))))
+            / x
+    } else {
+        let t = 2. / x;
+        (-x).exp() / x.sqrt()
+            * (1.25331414
+                + t * (0.23498619
+                    + t * (-0.03655620
+                        + t * (0.01504268
+                            + t * (-0.00780353 + t * (0.00325614 - t * 0.00068245))))))
+    }
+}
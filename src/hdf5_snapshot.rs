@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use hdf5_metno::{types::VarLenUnicode, File, Result};
+use ndarray::Array2;
+
+use crate::body::Body;
+
+/// Writes the active bodies at `time` to an HDF5 file for analysis with tools such as h5py and yt.
+/// The file has datasets `position` and `velocity` of shape (n, 3), `mass` with masses converted
+/// from Gm with the gravitational constant `g`, and `index` with each body's index in `bodies`. The
+/// time, `g` and, for snapshots of a simulation, the name and tick of the `integrator` are
+/// attributes of the root group.
+pub fn write(
+    path: impl AsRef<Path>,
+    time: f64,
+    bodies: &[Body],
+    g: f64,
+    integrator: Option<(&str, f64)>,
+) -> Result<()> {
+    let active: Vec<(usize, &Body)> = (bodies.iter().enumerate())
+        .filter(|(_, body)| body.is_active())
+        .collect();
+    let vectors = |f: fn(&Body) -> [f64; 3]| {
+        let values = active.iter().flat_map(|(_, body)| f(body)).collect();
+        Array2::from_shape_vec((active.len(), 3), values)
+    };
+    let positions = vectors(|b| [b.position.x(), b.position.y(), b.position.z()])?;
+    let velocities = vectors(|b| [b.velocity.x(), b.velocity.y(), b.velocity.z()])?;
+    let masses: Vec<f64> = active.iter().map(|(_, body)| body.mass / g).collect();
+    let indices: Vec<u64> = active.iter().map(|&(i, _)| i as u64).collect();
+
+    let file = File::create(path)?;
+    file.new_dataset_builder()
+        .with_data(&positions)
+        .create("position")?;
+    file.new_dataset_builder()
+        .with_data(&velocities)
+        .create("velocity")?;
+    file.new_dataset_builder()
+        .with_data(masses.as_slice())
+        .create("mass")?;
+    file.new_dataset_builder()
+        .with_data(indices.as_slice())
+        .create("index")?;
+    file.new_attr::<f64>().create("time")?.write_scalar(&time)?;
+    file.new_attr::<f64>().create("G")?.write_scalar(&g)?;
+    if let Some((name, tick)) = integrator {
+        let name: VarLenUnicode = name.parse().map_err(|e| format!("{}", e))?;
+        file.new_attr::<VarLenUnicode>()
+            .create("integrator")?
+            .write_scalar(&name)?;
+        file.new_attr::<f64>().create("tick")?.write_scalar(&tick)?;
+    }
+    file.close()
+}
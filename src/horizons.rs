@@ -0,0 +1,181 @@
+use std::error::Error;
+
+use serde::Deserialize;
+
+use crate::{
+    body::Body,
+    units::Units,
+    vec3::{Point3, Vec3},
+};
+
+const API: &str = "https://ssd.jpl.nasa.gov/api/horizons.api";
+
+/// A major body with its Horizons ID and Gm (km³/s²).
+pub struct Major {
+    pub name: &'static str,
+    id: &'static str,
+    mass: f64,
+}
+
+/// Major bodies in order from the Sun, over which ranges of names are taken.
+const ORDER: [Major; 10] = [
+    Major {
+        name: "Sun",
+        id: "10",
+        mass: 132712440041.93938,
+    },
+    Major {
+        name: "Mercury",
+        id: "199",
+        mass: 22031.86855,
+    },
+    Major {
+        name: "Venus",
+        id: "299",
+        mass: 324858.592,
+    },
+    Major {
+        name: "Earth",
+        id: "399",
+        mass: 398600.435436,
+    },
+    Major {
+        name: "Mars",
+        id: "499",
+        mass: 42828.375214,
+    },
+    Major {
+        name: "Jupiter",
+        id: "599",
+        mass: 126686531.900,
+    },
+    Major {
+        name: "Saturn",
+        id: "699",
+        mass: 37931206.234,
+    },
+    Major {
+        name: "Uranus",
+        id: "799",
+        mass: 5793951.256,
+    },
+    Major {
+        name: "Neptune",
+        id: "899",
+        mass: 6835099.97,
+    },
+    Major {
+        name: "Pluto",
+        id: "999",
+        mass: 869.326,
+    },
+];
+
+/// The Moon, which is only fetched by name since it doesn't orbit the Sun on its own.
+const MOON: Major = Major {
+    name: "Moon",
+    id: "301",
+    mass: 4902.800066,
+};
+
+/// Parses a comma-separated list of body names, case-insensitively, in which `a..b` stands for
+/// every body from `a` to `b` in order from the Sun, e.g. `sun,mercury..neptune`.
+pub fn parse_bodies(s: &str) -> Result<Vec<&'static Major>, String> {
+    let find = |name: &str| {
+        (ORDER.iter())
+            .position(|major| major.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = ORDER.iter().map(|major| major.name).collect();
+                format!(
+                    "unknown body `{}`, expected one of {} or Moon",
+                    name,
+                    names.join(", ")
+                )
+            })
+    };
+    let mut bodies = Vec::new();
+    for item in s.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        if item.eq_ignore_ascii_case(MOON.name) {
+            bodies.push(&MOON);
+        } else if let Some((first, last)) = item.split_once("..") {
+            let (first, last) = (find(first.trim())?, find(last.trim())?);
+            if first > last {
+                return Err(format!("range `{}` is backwards", item));
+            }
+            bodies.extend(&ORDER[first..=last]);
+        } else {
+            bodies.push(&ORDER[find(item)?]);
+        }
+    }
+    if bodies.is_empty() {
+        return Err("no bodies given".to_string());
+    }
+    Ok(bodies)
+}
+
+#[derive(Deserialize)]
+struct Response {
+    result: Option<String>,
+    error: Option<String>,
+}
+
+/// Fetches the heliocentric state vectors of `bodies` at `epoch` (TDB, as a calendar or Julian
+/// date) in the ecliptic frame of J2000, in `units` with masses in Gm.
+pub fn fetch(bodies: &[&Major], epoch: &str, units: Units) -> Result<Vec<Body>, Box<dyn Error>> {
+    let (length, time) = (units.length(), units.time());
+    (bodies.iter())
+        .map(|major| {
+            let text = ureq::get(API)
+                .query("format", "json")
+                .query("COMMAND", format!("'{}'", major.id))
+                .query("OBJ_DATA", "'NO'")
+                .query("MAKE_EPHEM", "'YES'")
+                .query("EPHEM_TYPE", "'VECTORS'")
+                .query("CENTER", "'500@10'")
+                .query("REF_PLANE", "'ECLIPTIC'")
+                .query("TLIST", format!("'{}'", epoch))
+                .query("OUT_UNITS", "'KM-S'")
+                .query("VEC_TABLE", "'2'")
+                .query("CSV_FORMAT", "'YES'")
+                .call()?
+                .body_mut()
+                .read_to_string()?;
+            let response: Response = serde_json::from_str(&text)?;
+            if let Some(error) = response.error {
+                return Err(format!("Horizons error for {}: {}", major.name, error).into());
+            }
+            let result = response.result.unwrap_or_default();
+            let (position, velocity) = parse_vectors(&result).ok_or_else(|| {
+                format!(
+                    "no state vectors for {} in the Horizons response:\n{}",
+                    major.name, result
+                )
+            })?;
+            Ok(Body {
+                name: Some(major.name.to_string()),
+                ..Body::new(
+                    1e3 / length * position,
+                    1e3 * time / length * velocity,
+                    1e9 * time * time / (length * length * length) * major.mass,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Position (km) and velocity (km/s) in the first row of CSV vectors, which has the Julian date,
+/// calendar date, X, Y, Z, VX, VY and VZ.
+fn parse_vectors(result: &str) -> Option<(Point3, Vec3)> {
+    let start = result.find("$$SOE")? + "$$SOE".len();
+    let row = result[start..]
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())?;
+    let values: Vec<f64> = (row.split(',').skip(2).take(6))
+        .map(|value| value.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match values[..] {
+        [x, y, z, vx, vy, vz] => Some((Point3::new(x, y, z), Vec3::new(vx, vy, vz))),
+        _ => None,
+    }
+}
@@ -1,104 +1,299 @@
-use std::fmt::Display;
+use std::borrow::Cow;
 
-use crate::vec3::{Point3, Vec3};
+use serde::{Serialize, Serializer};
+
+use crate::{
+    body::{self, Body},
+    boundary::SimulationBox,
+    collision::{self, CollisionMode},
+    cosmology::Cosmology,
+    event::{Event, Events},
+    force::{self, Force},
+    observer::Observers,
+    simulation::{Simulation, WorldState},
+    vec3::Vec3,
+};
 
 #[derive(Debug)]
-pub struct Body {
-    pub position: Point3,
-    pub velocity: Vec3,
-    pub mass: f64,
+pub struct World {
+    bodies: Vec<Body>,
+    forces: Vec<Box<dyn Force>>,
+    collision_mode: Option<CollisionMode>,
+    restitution: f64,
+    events: Events,
+    simulation_box: Option<SimulationBox>,
+    cosmology: Option<Cosmology>,
+    observers: Observers,
+    /// Id of the next body to be added.
+    next_id: usize,
+    /// Accelerations and kicks, kept between ticks so that ticks don't allocate.
+    buffer: Vec<Vec3>,
+    time: f64,
+    /// Whether velocities have been offset by half a tick yet.
+    started: bool,
+    /// Duration of the last tick, half of which the velocities lead the positions by.
+    tick: f64,
 }
 
-impl Body {
-    fn acceleration(&self, from: &Self) -> Vec3 {
-        let r: Vec3 = from.position - self.position;
+impl World {
+    pub fn new(mut bodies: Vec<Body>, forces: Vec<Box<dyn Force>>) -> Self {
+        let next_id = body::assign_ids(&mut bodies);
+        Self {
+            bodies,
+            forces,
+            collision_mode: None,
+            restitution: 1.,
+            events: Events::default(),
+            simulation_box: None,
+            cosmology: None,
+            observers: Observers::default(),
+            next_id,
+            buffer: Vec::new(),
+            time: 0.,
+            started: false,
+            tick: 0.,
+        }
+    }
 
-        (from.mass / r.length().powf(3.0)) * r
+    fn half_tick_velocity(&mut self, tick_duration: f64) {
+        // Calculate and integrate accelerations
+        // v[1/2] = v[0] + 1/2 * δt * a[n+1]
+        self.kick_all(tick_duration / 2.);
     }
 
-    pub fn new(position: Point3, velocity: Vec3, mass: f64) -> Self {
-        Self {
-            position,
-            velocity,
-            mass,
+    /// Kicks every body by its acceleration at the current positions for `duration`.
+    fn kick_all(&mut self, duration: f64) {
+        let mut accelerations = std::mem::take(&mut self.buffer);
+        self.accelerations(&mut accelerations);
+        // Each kick only depends on the velocity of its own body
+        for (i, &acceleration) in accelerations.iter().enumerate() {
+            self.bodies[i].velocity = self.kick(i, acceleration, duration);
         }
+        self.buffer = accelerations;
     }
-}
 
-impl Display for Body {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "r = [{:e}], v = [{:e}], Gm = {:e}",
-            self.position, self.velocity, self.mass
-        )
+    /// Accelerations from all forces except magnetic fields, which are integrated by `kick`.
+    fn accelerations(&self, accelerations: &mut Vec<Vec3>) {
+        let forces = self
+            .forces
+            .iter()
+            .filter(|f| f.as_magnetic_field().is_none());
+        force::accelerations_into(forces, &self.bodies, accelerations);
+        if let Some(cosmology) = &self.cosmology {
+            cosmology.comoving_accelerations(&self.bodies, accelerations);
+        }
     }
-}
 
-#[derive(Debug)]
-pub struct World {
-    bodies: Vec<Body>,
-    time: f64,
+    /// Velocity of the body at index `i` after being accelerated by `acceleration` for `duration`.
+    /// Magnetic fields rotate the velocity with the Boris push, which keeps gyro-orbits stable.
+    fn kick(&self, i: usize, acceleration: Vec3, duration: f64) -> Vec3 {
+        let body = &self.bodies[i];
+        let t: Vec3 = self
+            .forces
+            .iter()
+            .filter_map(|f| f.as_magnetic_field())
+            .map(|m| (m.charge_to_mass(body) * duration / 2.) * m.field)
+            .sum();
+        if !body.is_mobile() || t.length_squared() == 0. {
+            return body.velocity + duration * acceleration;
+        }
+        // Half acceleration, rotation about the field, then another half acceleration
+        let minus = body.velocity + (duration / 2.) * acceleration;
+        let s = (2. / (1. + t.length_squared())) * t;
+        let prime = minus + Vec3::cross(&minus, &t);
+        let plus = minus + Vec3::cross(&prime, &s);
+        plus + (duration / 2.) * acceleration
+    }
 }
 
-impl World {
-    pub fn new(bodies: Vec<Body>) -> Self {
-        Self { bodies, time: 0. }
+/// Serialised as its [`SavedWorld`](crate::simulation::SavedWorld).
+impl Serialize for World {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.save().serialize(serializer)
     }
+}
 
-    pub fn tick(&mut self, tick_duration: f64) {
+impl Simulation for World {
+    fn tick(&mut self, tick_duration: f64) {
+        if !self.started {
+            // Offset velocities by half a tick before the first one
+            self.half_tick_velocity(tick_duration);
+            self.started = true;
+        }
+        self.tick = tick_duration;
         // Integrate velocities
-        for body in &mut self.bodies {
+        for body in self.bodies.iter_mut().filter(|body| body.is_mobile()) {
             // x[n+1] = x[n] + δt * v[1/2 + n]
             body.position += body.velocity * tick_duration;
         }
+        self.confine_bodies();
         self.time += tick_duration;
+        if let Some(cosmology) = &mut self.cosmology {
+            cosmology.advance(tick_duration);
+        }
         // Calculate and integrate accelerations
         // a[n + 1] = f(r[n + 1])
-        for i in 0..self.bodies.len() {
-            let acceleration: Vec3 = self
-                .bodies
-                .iter()
-                .enumerate()
-                .filter_map(|(j, b)| (i != j).then(|| self.bodies[i].acceleration(b)))
-                .sum();
-            // v[1/2 + n + 1] = v[1/2 + n] + δt * a[n+1]
-            self.bodies[i].velocity += tick_duration * acceleration
+        // v[1/2 + n + 1] = v[1/2 + n] + δt * a[n+1]
+        self.kick_all(tick_duration);
+        // Apply stochastic velocity kicks
+        force::kicks_into(
+            &mut self.forces,
+            &self.bodies,
+            tick_duration,
+            &mut self.buffer,
+        );
+        for (body, kick) in self.bodies.iter_mut().zip(&self.buffer) {
+            body.velocity += *kick;
         }
-    }
-
-    pub fn half_tick_velocity(&mut self, tick_duration: f64) {
-        // Calculate and integrate accelerations
-        for i in 0..self.bodies.len() {
-            let acceleration: Vec3 = self
-                .bodies
-                .iter()
-                .enumerate()
-                .filter_map(|(j, b)| (i != j).then(|| self.bodies[i].acceleration(b))) // forces from all bodies except itself
-                .sum();
-            // v[1/2] = v[0] + 1/2 * δt * a[n+1]
-            self.bodies[i].velocity += acceleration * tick_duration / 2.;
+        // Evolve masses
+        for body in &mut self.bodies {
+            body.evolve_mass(tick_duration);
+        }
+        // Resolve collisions
+        let periodic = self.simulation_box.and_then(|b| b.periodic());
+        if let Some(mode) = self.collision_mode {
+            let collisions = collision::resolve(
+                mode,
+                self.restitution,
+                &mut self.bodies,
+                self.time,
+                periodic,
+            );
+            for collision in collisions {
+                self.events.emit(Event::Collision(collision));
+            }
         }
+        self.events.detect(self.time, &self.bodies, periodic);
+        self.notify_observers();
     }
 
-    pub fn time(&self) -> f64 {
+    fn time(&self) -> f64 {
         self.time
     }
 
-    pub fn bodies(&self) -> &Vec<Body> {
+    fn bodies(&self) -> &Vec<Body> {
         &self.bodies
     }
 
-    /// Converts into the Galilean rest frame of the body and index i
-    pub fn into_rest_frame(&mut self, i: usize) {
-        let (r_position, r_velocity) = self
-            .bodies()
-            .get(i)
-            .map(|r| (r.position, r.velocity))
-            .unwrap_or((Point3::ZERO, Vec3::ZERO));
-        for body in &mut self.bodies {
-            body.position -= r_position;
-            body.velocity -= r_velocity;
+    fn bodies_mut(&mut self) -> &mut Vec<Body> {
+        &mut self.bodies
+    }
+
+    /// Once started, the body is kicked by half a tick, so that its velocity leads its position
+    /// like those of the other bodies.
+    fn add_body(&mut self, mut body: Body) -> usize {
+        body.id = self.next_id;
+        self.next_id += 1;
+        self.bodies.push(body);
+        if self.started {
+            let mut accelerations = std::mem::take(&mut self.buffer);
+            self.accelerations(&mut accelerations);
+            let i = self.bodies.len() - 1;
+            self.bodies[i].velocity = self.kick(i, accelerations[i], self.tick / 2.);
+            self.buffer = accelerations;
+        }
+        self.next_id - 1
+    }
+
+    fn forces(&self) -> &[Box<dyn Force>] {
+        &self.forces
+    }
+
+    /// Bodies with the last half kick undone, v[n] = v[n+1/2] - δt/2 a[n], leaving out stochastic
+    /// kicks.
+    fn synchronised_bodies(&self) -> Cow<'_, [Body]> {
+        if !self.started {
+            return Cow::Borrowed(&self.bodies);
+        }
+        let mut accelerations = Vec::new();
+        self.accelerations(&mut accelerations);
+        let velocities: Vec<Vec3> = (accelerations.into_iter().enumerate())
+            .map(|(i, acceleration)| self.kick(i, acceleration, -self.tick / 2.))
+            .collect();
+        let mut bodies = self.bodies.clone();
+        for (body, velocity) in bodies.iter_mut().zip(velocities) {
+            body.velocity = velocity;
+        }
+        Cow::Owned(bodies)
+    }
+
+    fn scheme(&self) -> &'static str {
+        "Leapfrog: velocities are at half ticks, v[1/2] = v[0] + δt/2 a[0] on the first tick, then x[n+1] = x[n] + δt v[n+1/2], a[n+1] = f(x[n+1]), v[n+3/2] = v[n+1/2] + δt a[n+1]"
+    }
+
+    fn set_collision_mode(&mut self, mode: CollisionMode, restitution: f64) {
+        self.collision_mode = Some(mode);
+        self.restitution = restitution;
+    }
+
+    fn events(&self) -> &Events {
+        &self.events
+    }
+
+    fn events_mut(&mut self) -> &mut Events {
+        &mut self.events
+    }
+
+    fn simulation_box(&self) -> Option<SimulationBox> {
+        self.simulation_box
+    }
+
+    fn set_simulation_box(&mut self, simulation_box: SimulationBox) {
+        self.simulation_box = Some(simulation_box);
+    }
+
+    fn cosmology(&self) -> Option<Cosmology> {
+        self.cosmology
+    }
+
+    fn set_cosmology(&mut self, cosmology: Cosmology) {
+        self.cosmology = Some(cosmology);
+    }
+
+    fn world_state(&self) -> WorldState {
+        WorldState {
+            time: self.time,
+            started: self.started,
+            tick: self.tick,
+            next_id: self.next_id,
+            cosmology: self.cosmology,
+            rngs: force::rngs(&self.forces),
+        }
+    }
+
+    fn restore_world_state(&mut self, state: WorldState) {
+        self.time = state.time;
+        self.started = state.started;
+        self.tick = state.tick;
+        self.next_id = state.next_id;
+        self.cosmology = state.cosmology;
+        force::restore_rngs(&mut self.forces, state.rngs);
+    }
+
+    fn observers_mut(&mut self) -> &mut Observers {
+        &mut self.observers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{force::Gravity, vec3::Point3};
+
+    #[test]
+    fn bodies_added_after_starting_keep_their_velocity() {
+        let bodies = vec![
+            Body::new(Point3::new(0., 0., 0.), Vec3::ZERO, 1.),
+            Body::new(Point3::new(1., 0., 0.), Vec3::new(0., 1., 0.), 1e-3),
+        ];
+        let mut world = World::new(bodies, vec![Box::new(Gravity::default())]);
+        for _ in 0..10 {
+            world.tick(1e-2);
         }
+        let velocity = Vec3::new(-0.7, 0., 0.);
+        let id = world.add_body(Body::new(Point3::new(0., 2., 0.), velocity, 1e-3));
+        let i = world.index_of(id).unwrap();
+        let error = (world.synchronised_bodies()[i].velocity - velocity).length();
+        assert!(error < 1e-15, "velocity error {:e}", error);
     }
 }
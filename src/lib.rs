@@ -0,0 +1,64 @@
+//! Library for simulating systems of bodies under gravity and other forces, used by the
+//! `rs-nbody` command line tool.
+
+pub mod analysis;
+pub mod body;
+pub mod boundary;
+pub mod check;
+pub mod checkpoint;
+pub mod collision;
+#[cfg(feature = "io")]
+pub mod compress;
+pub mod config;
+pub mod cosmology;
+#[cfg(feature = "io")]
+pub mod diagnostics;
+pub mod elements;
+pub mod ensemble;
+pub mod error;
+pub mod event;
+pub mod explain;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod force;
+pub mod forward_euler;
+pub mod fpenv;
+pub mod gadget;
+pub mod generate;
+#[cfg(feature = "hdf5")]
+pub mod hdf5_snapshot;
+#[cfg(feature = "net")]
+pub mod horizons;
+pub mod leapfrog;
+pub mod observer;
+pub mod parameters;
+#[cfg(feature = "plot")]
+pub mod plot;
+pub mod potential;
+pub mod presets;
+#[cfg(feature = "io")]
+pub mod progress;
+#[cfg(all(feature = "io", feature = "plot"))]
+pub mod report;
+pub mod respa;
+pub mod rewind;
+pub mod rng;
+pub mod selftest;
+pub mod sensitivity;
+pub mod simulation;
+pub mod snapshot;
+pub mod softening;
+pub mod summation;
+pub mod symplectic_euler;
+pub mod system;
+pub mod tle;
+pub mod trajectory;
+#[cfg(feature = "io")]
+pub mod tui;
+pub mod units;
+pub mod vec3;
+pub mod vtk;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "io")]
+pub mod window;
@@ -1,35 +1,758 @@
-mod forward_euler;
-mod leapfrog;
-mod symplectic_euler;
-mod vec3;
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{BufRead, BufReader, Read, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use std::fs::File;
-
-use clap::{command, Parser, ValueHint};
+use clap::{parser::ValueSource, ArgGroup, CommandFactory, Parser, ValueEnum, ValueHint};
+use rs_nbody::{
+    analysis::{self, ClosestApproach, OrbitalElements},
+    body::{self, Body, State},
+    boundary::{Boundary, PeriodicBox, SimulationBox},
+    check::{self, Severity},
+    checkpoint::{Checkpoint, RunState},
+    collision::CollisionMode,
+    compress::{self, AtomicFile, Compression},
+    config,
+    cosmology::Cosmology,
+    diagnostics::DiagnosticsLog,
+    ensemble::Ensemble,
+    error::{Context, Error},
+    event::{Event, EventKind},
+    explain,
+    force::{
+        BackgroundField, Coulomb, Drag, DynamicalFriction, Ewald, Force, Gravity, MagneticField,
+        Oblateness, PostNewtonian, RadiationPressure, Tide, Yukawa,
+    },
+    forward_euler,
+    fpenv::FpEnvironment,
+    gadget,
+    generate::Generator,
+    leapfrog,
+    potential::Potential,
+    presets::Preset,
+    progress::ProgressDisplay,
+    respa,
+    rng::Rng,
+    selftest, sensitivity,
+    simulation::{CancellationToken, SavedWorld, Simulation},
+    snapshot, softening, symplectic_euler,
+    system::System,
+    tle,
+    trajectory::{OutputSchedule, TrajectoryWriter},
+    tui::LiveView,
+    units::Units,
+    vec3::{Point3, Vec3},
+    vtk,
+    window::EventRecorder,
+};
 use serde::{Deserialize, Serialize};
-use vec3::{Point3, Vec3};
+
+#[cfg(feature = "net")]
+use rs_nbody::horizons;
+#[cfg(feature = "plot")]
+use rs_nbody::{
+    plot::{self, Animation, Axes, Coloring},
+    report::{self, Run},
+    trajectory,
+};
 
 /// Basic implementation of an N-body simulator.
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
-struct Args {
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Options of a simulation run.
+#[derive(clap::Args, Debug)]
+#[command(group(ArgGroup::new("speed_of_light_source").args(["units", "speed_of_light"]).multiple(true)))]
+#[command(group(ArgGroup::new("box_boundary").args(["boundary", "periodic"])))]
+#[command(group(ArgGroup::new("outputs").args(["out", "stream", "ensemble"]).multiple(true)))]
+#[command(group(ArgGroup::new("perturbations").args(["perturb_pos", "perturb_vel"]).multiple(true)))]
+struct RunArgs {
+    /// TOML file of options for the run, keyed by their long flag names, e.g. `tick = 0.01`,
+    /// `sim = "leapfrog"` and `potential = ["kepler:mass=1"]`. Flags given on the command line
+    /// override those in the file.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
     /// File containing simulation initial conditions. Must contain headers `pos_x`, `pos_y`,
-    /// `pos_z`, `vel_x`, `vel_y`, `vel_z`, `mass`.
-    #[arg(short, long, value_hint = ValueHint::FilePath)]
-    file: String,
+    /// `pos_z`, `vel_x`, `vel_y`, `vel_z`, `mass`, and may contain `radius` and `test` (`true` for
+    /// test particles which exert no force), `fixed` (`true` for bodies which never move) and
+    /// `mass_rate` (rate of isotropic mass loss or accretion), `charge`, `softening` (Plummer
+    /// softening length of gravity) and `name`. May instead be a binary snapshot, which is detected from its
+    /// contents, or a TOML or JSON file with a `.toml` or `.json` extension, which has a list of
+    /// `bodies` with optional names and colours and may set `G` and a default `softening`.
+    #[arg(short, long, value_hint = ValueHint::FilePath, required_unless_present_any = ["preset", "resume"])]
+    file: Option<String>,
+    /// Simulate a built-in system instead of reading initial conditions from a file. Masses are
+    /// reported in the preset's units unless `--units` or `--G` is given.
+    #[arg(long, value_enum, conflicts_with = "file")]
+    preset: Option<Preset>,
+    /// File of two-line element sets, each optionally preceded by a name line, of satellites to add
+    /// as test particles around `--tle-around`. Their mean elements are taken as Keplerian
+    /// elements relative to its equator, advanced to the latest epoch among them. Times are in
+    /// seconds unless `--units` says otherwise.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    tle: Option<PathBuf>,
+    /// Index or name of the body which satellites from `--tle` orbit.
+    #[arg(long, default_value = "Earth", requires = "tle")]
+    tle_around: String,
     /// Tick duration.
     #[arg(short, long, default_value_t = 1e-3)]
     tick: f64,
     /// Simulation method.
     #[clap(short, long, default_value_t, value_enum)]
     sim: SimType,
+    /// Number of substeps per tick for forces other than external potentials with `--sim respa`.
+    #[arg(long, default_value = "4")]
+    substeps: NonZeroUsize,
     /// Duration of simulation.
     #[arg(short, long)]
     dur: f64,
-    /// If specified, the final output will be presented in the rest frame of the body at this
-    /// index. If the index is out of bounds, the default frame is used.
+    /// If specified, the final output will be presented in the rest frame of the body with this
+    /// index or name.
     #[clap(short, long)]
-    rest_frame: Option<usize>,
+    rest_frame: Option<String>,
+    /// If specified, bodies with a radius which overlap are resolved with this method.
+    #[arg(short, long, value_enum)]
+    collision: Option<CollisionMode>,
+    /// Coefficient of restitution for bouncing collisions.
+    #[arg(long, default_value_t = 1.)]
+    restitution: f64,
+    /// Pairwise interaction between bodies.
+    #[arg(short, long, default_value_t, value_enum)]
+    interaction: Interaction,
+    /// Exponent n of the gravitational force law F ∝ 1/rⁿ, for exploring non-Newtonian dynamics.
+    #[arg(long, default_value_t = 2., conflicts_with = "ewald")]
+    force_exponent: f64,
+    /// Modifies gravity with MOND, boosting accelerations well below this acceleration scale a₀
+    /// towards √(a₀g).
+    #[arg(long, conflicts_with = "ewald")]
+    mond_a0: Option<f64>,
+    /// Plummer softening length of gravity for every body, overriding the file.
+    #[arg(long)]
+    softening: Option<f64>,
+    /// Adapt the softening length of each body after every tick to `--softening-factor` times the
+    /// distance to its this-many-th nearest neighbour. The energy report is corrected for the
+    /// changes in potential energy this causes.
+    #[arg(long)]
+    adaptive_softening: Option<NonZeroUsize>,
+    /// Ratio of adaptive softening lengths to the distance to the neighbour.
+    #[arg(long, default_value_t = 1., requires = "adaptive_softening")]
+    softening_factor: f64,
+    /// Coulomb constant, in units consistent with the charges, masses and G.
+    #[arg(long, default_value_t = 1.)]
+    coulomb_constant: f64,
+    /// Screening length of the Yukawa interaction, beyond which electrostatic forces fall off
+    /// exponentially.
+    #[arg(long, required_if_eq("interaction", "yukawa"))]
+    screening_length: Option<f64>,
+    /// Uniform magnetic field `x,y,z` acting on charged bodies. The leapfrog integrator uses the
+    /// Boris push for the Lorentz force.
+    #[arg(long, value_parser = parse_vec3)]
+    magnetic_field: Option<Vec3>,
+    /// Side length of a cubic box centred on the origin which confines the bodies, with walls
+    /// given by `--boundary`.
+    #[arg(long = "box", requires = "box_boundary")]
+    box_size: Option<f64>,
+    /// Boundary conditions at the walls of the box. In a periodic box, gravitational and Coulomb
+    /// separations use the minimum image, i.e. the nearest periodic image of each body.
+    #[arg(long, value_enum, requires = "box_size")]
+    boundary: Option<Boundary>,
+    /// Shorthand for `--boundary periodic`.
+    #[arg(long, requires = "box_size", conflicts_with = "boundary")]
+    periodic: bool,
+    /// Computes periodic gravity by Ewald summation over all periodic images, rather than from the
+    /// nearest image alone. The mean density of the box is subtracted, as in cosmological runs.
+    /// Energies include the energy of each body with its own images, which is constant.
+    #[arg(long, requires = "box_size")]
+    ewald: bool,
+    /// Integrates in comoving coordinates, with comoving velocities, in a universe expanding with
+    /// this Hubble constant. All forces are computed from comoving positions and scaled by 1/a³,
+    /// and bodies feel the Hubble drag -2Hv.
+    #[arg(long)]
+    hubble: Option<f64>,
+    /// Matter density parameter of the expanding universe.
+    #[arg(long, default_value_t = 1., requires = "hubble")]
+    omega_m: f64,
+    /// Cosmological constant density parameter of the expanding universe.
+    #[arg(long, default_value_t = 0., requires = "hubble")]
+    omega_lambda: f64,
+    /// Scale factor at the start of the simulation, where the Hubble constant is at a = 1.
+    #[arg(long, default_value_t = 1., requires = "hubble")]
+    scale_factor: f64,
+    /// Indices of bodies which feel dynamical friction from an unresolved background, e.g.
+    /// `--friction-bodies 1,2`.
+    #[arg(long, value_delimiter = ',')]
+    friction_bodies: Vec<usize>,
+    /// Density of the dynamical friction background, in the same Gm units as body masses.
+    #[arg(long, default_value_t = 0.)]
+    friction_density: f64,
+    /// One-dimensional velocity dispersion of the dynamical friction background.
+    #[arg(long, default_value_t = 1.)]
+    friction_dispersion: f64,
+    /// Coulomb logarithm used for dynamical friction.
+    #[arg(long, default_value_t = 3.)]
+    friction_log: f64,
+    /// Index of an oblate body, whose J2 perturbation acts on all other bodies. Its symmetry axis
+    /// is the z axis.
+    #[arg(long, requires_all = ["j2", "j2_radius"])]
+    j2_body: Option<usize>,
+    /// J2 coefficient of the oblate body.
+    #[arg(long, requires = "j2_body")]
+    j2: Option<f64>,
+    /// Equatorial radius of the oblate body.
+    #[arg(long, requires = "j2_body")]
+    j2_radius: Option<f64>,
+    /// Tide raised on one body by another, given as
+    /// `primary=<index>,secondary=<index>,k2=<Love number>,lag=<time lag>,radius=<primary radius>`.
+    /// May be repeated.
+    #[arg(long)]
+    tide: Vec<Tide>,
+    /// Coefficient of drag linear in velocity, a = -k v.
+    #[arg(long, default_value_t = 0.)]
+    drag_linear: f64,
+    /// Coefficient of drag quadratic in velocity, a = -k |v| v.
+    #[arg(long, default_value_t = 0.)]
+    drag_quadratic: f64,
+    /// If specified, only bodies within this distance of the drag centre feel drag.
+    #[arg(long)]
+    drag_radius: Option<f64>,
+    /// Index of the body the drag medium moves with. If not specified, the medium is at rest at
+    /// the origin.
+    #[arg(long)]
+    drag_centre: Option<usize>,
+    /// Index of a luminous body exerting radiation pressure and Poynting-Robertson drag. Requires
+    /// `--units` or `--speed-of-light`.
+    #[arg(long, requires = "speed_of_light_source")]
+    radiation_source: Option<usize>,
+    /// Ratio of radiation pressure to the gravity of the luminous body. Must be nonzero with
+    /// `--radiation-source`.
+    #[arg(long, default_value_t = 0.)]
+    radiation_beta: f64,
+    /// Indices of the bodies which feel radiation pressure. If not specified, all bodies except
+    /// the luminous one do.
+    #[arg(long, value_delimiter = ',')]
+    radiation_bodies: Vec<usize>,
+    /// If specified, all bodies move through an unresolved Plummer-sphere background of this total
+    /// mass (in Gm units), feeling its smooth potential and stochastic two-body relaxation kicks.
+    #[arg(long)]
+    background_mass: Option<f64>,
+    /// Plummer scale radius of the background.
+    #[arg(long, default_value_t = 1.)]
+    background_scale: f64,
+    /// Mass of an individual background star (in Gm units).
+    #[arg(long, default_value_t = 0.)]
+    background_star_mass: f64,
+    /// Coulomb logarithm used for background relaxation.
+    #[arg(long, default_value_t = 10.)]
+    background_log: f64,
+    /// External potential in which the bodies move, given as `name:key=value,...`. May be
+    /// repeated. Available potentials are `point-mass:mass,x,y,z`, `kepler:mass`,
+    /// `harmonic:omega`, `miyamoto-nagai:mass,a,b`, `nfw:mass,rs` and `logarithmic:v0,rc,q`, with
+    /// masses in Gm units.
+    #[arg(short, long)]
+    potential: Vec<Potential>,
+    /// Relativistic corrections to gravity. Requires `--units` or `--speed-of-light`.
+    #[arg(long, value_enum, requires = "speed_of_light_source")]
+    relativity: Option<Relativity>,
+    /// Speed of light, overriding the one implied by `--units`.
+    #[arg(long)]
+    speed_of_light: Option<f64>,
+    /// Unit system of the input and output.
+    #[arg(short, long, value_enum)]
+    units: Option<Units>,
+    /// Gravitational constant, overriding the one implied by `--units` or set by a TOML or JSON
+    /// input file.
+    #[arg(long = "G", value_name = "G")]
+    g: Option<f64>,
+    /// Explain this many initial ticks, printing the accelerations on each body and the
+    /// integrator's updates. Intended for small systems.
+    #[arg(long, default_value_t = 0)]
+    explain: usize,
+    /// Print the angular momentum about the barycentre every this many ticks.
+    #[arg(long)]
+    log_angular_momentum: Option<usize>,
+    /// Print the virial ratio 2T/|U|, mean speed and one-dimensional velocity dispersion every
+    /// this many ticks, to check whether a cluster is in equilibrium.
+    #[arg(long)]
+    log_virial: Option<usize>,
+    /// Write the final state to this file so that it can be used as the initial conditions of a
+    /// continuation run, with velocities at the same time as the positions even for leapfrog,
+    /// which keeps them half a tick ahead. Files with a `.csv` extension get the active bodies in the same format as
+    /// the input, and any other file gets a binary snapshot of every body. Files with a `.gadget`
+    /// extension get the active bodies as a Gadget-2 snapshot and, with the `hdf5` feature, files
+    /// with an `.h5` or `.hdf5` extension get the active bodies and run parameters in HDF5. Neither
+    /// can be read back in.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    save_state: Option<PathBuf>,
+    /// Write a checkpoint to `--checkpoint-file` every this much simulation time, from which the
+    /// run can be continued exactly with `--resume`.
+    #[arg(long, requires = "checkpoint_file")]
+    checkpoint_every: Option<f64>,
+    /// File to write checkpoints to. Each checkpoint replaces the last once it has been written
+    /// completely. Checkpoints are binary, or JSON if the file has a `.json` extension.
+    #[arg(long, requires = "checkpoint_every", value_hint = ValueHint::FilePath)]
+    checkpoint_file: Option<PathBuf>,
+    /// Continue a run from a checkpoint written by `--checkpoint-every` until `--dur`. Forces,
+    /// collisions, the box and other options of the simulation must be given as they were for the
+    /// original run, with the same `--sim` and `--tick`. Outputs such as `--out` start again from
+    /// the time of the checkpoint.
+    #[arg(long, value_hint = ValueHint::FilePath, conflicts_with_all = ["file", "preset", "tle", "ensemble"])]
+    resume: Option<PathBuf>,
+    /// Stop the run cleanly once it has taken this much wall-clock time, e.g. `2h`, `1h30m` or
+    /// `90s`, writing a checkpoint if they are enabled, the final state to `--save-state` and the
+    /// report, and exiting with status 124. The limit is checked after every tick.
+    #[arg(long, value_parser = parse_walltime)]
+    max_walltime: Option<Duration>,
+    /// Write the trajectories of the bodies to this CSV file, with one row of time, body index,
+    /// position and velocity per active body at each output. If the file has a `.vtk` extension, a
+    /// numbered VTK file is written for each output instead, which ParaView loads as a time series.
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    out: Option<PathBuf>,
+    /// Write the time and bodies to stdout as one line of JSON per output while the simulation
+    /// runs, for a live plotter or another process reading from a pipe. Bodies are as in the
+    /// `--format json` report, which is written as the final line, so that stdout is entirely
+    /// newline-delimited JSON unless other messages are printed. The report is JSON even if
+    /// `--format` is text.
+    #[arg(long)]
+    stream: bool,
+    /// Show the bodies, time, tick rate and energy drift live in the terminal while the simulation
+    /// runs. Pressing q stops the simulation early and prints the report as usual.
+    #[arg(long)]
+    tui: bool,
+    /// Don't show the progress bar, which is otherwise drawn on stderr when it is a terminal.
+    #[arg(short, long)]
+    quiet: bool,
+    /// Simulation time between outputs to `--out` and `--stream` and rows of `--ensemble`
+    /// statistics. By default every tick is written, and `--ensemble` statistics are printed at 20
+    /// evenly spaced times.
+    #[arg(long, requires = "outputs")]
+    every: Option<f64>,
+    /// Compress the files written by `--out`, `--diag-out` and `--report`, and CSV files and binary
+    /// snapshots written by `--save-state`, appending `.gz` or `.zst` to their names. Each output is
+    /// a separate gzip member or zstd frame, so the temporary files of interrupted runs can still be
+    /// decompressed. Compressed input files are detected automatically.
+    #[arg(long, value_enum)]
+    compress: Option<Compression>,
+    /// Sync each output file to disk before it replaces any file at its path. Every file is written
+    /// under its name with `.tmp` appended and renamed once complete, so an interrupted run leaves
+    /// earlier outputs intact and its own output in the temporary file; syncing also protects them
+    /// from a crash of the machine. Checkpoints are always synced.
+    #[arg(long)]
+    fsync: bool,
+    /// Write the time, energy, momentum, angular momentum, virial ratio, density centre and 10%, 50%
+    /// and 90% Lagrangian radii to this CSV file every `--diag-every` ticks.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    diag_out: Option<PathBuf>,
+    /// Number of ticks between rows of `--diag-out`.
+    #[arg(long, default_value = "100", requires = "diag_out")]
+    diag_every: NonZeroUsize,
+    /// Record this many ticks either side of each collision or close encounter to `event-<n>.csv`,
+    /// in long format with one row per body per tick.
+    #[arg(long)]
+    event_window: Option<usize>,
+    /// Directory to write event windows to.
+    #[arg(long, default_value = ".", requires = "event_window", value_hint = ValueHint::DirPath)]
+    event_dir: PathBuf,
+    /// Report pairs of bodies coming within this separation of each other as close encounters.
+    #[arg(long)]
+    encounter_distance: Option<f64>,
+    /// Stop the run after the tick in which an event of this kind occurs, printing the report as
+    /// usual. Escapes and close encounters are only detected with `--escape-radius` and
+    /// `--encounter-distance`. May be repeated.
+    #[arg(long, value_enum)]
+    stop_on: Vec<EventKind>,
+    /// Instead of reporting the final state, run this many copies of the system with their initial
+    /// conditions perturbed at random by `--perturb-pos` and `--perturb-vel`, and print statistics
+    /// of how far they diverge from the unperturbed run over time and an estimate of the largest
+    /// Lyapunov exponent.
+    #[arg(long, requires = "perturbations")]
+    ensemble: Option<NonZeroUsize>,
+    /// Standard deviation of the perturbations of `--ensemble` initial position components,
+    /// relative to the RMS initial distance of the bodies.
+    #[arg(long, requires = "ensemble")]
+    perturb_pos: Option<f64>,
+    /// Standard deviation of the perturbations of `--ensemble` initial velocity components,
+    /// relative to the RMS initial speed of the bodies.
+    #[arg(long, requires = "ensemble")]
+    perturb_vel: Option<f64>,
+    /// Number of `--ensemble` members to run in parallel.
+    #[arg(long, default_value = "1", requires = "ensemble")]
+    threads: NonZeroUsize,
+    /// Print a report of the floating-point environment, e.g. flush-to-zero and FMA, which explains
+    /// why trajectories may differ between machines.
+    #[arg(long)]
+    fp_report: bool,
+    /// Guarantee results which are bit-identical between runs, `--threads` and machines with
+    /// strict IEEE 754 arithmetic. Newtonian gravity is then calculated without the platform's
+    /// maths library, which can change results in the last bit, and forces and merging
+    /// collisions which need it are refused, as are machines whose arithmetic isn't strict.
+    #[arg(long)]
+    deterministic: bool,
+    /// Sum the gravitational accelerations on each body with Kahan compensation, which keeps
+    /// their rounding error from growing with the number of bodies at the cost of a few more
+    /// additions per pair. Energies and momenta are always summed this way.
+    #[arg(long)]
+    compensated: bool,
+    /// Print every body even when there are more than `--summary-threshold`.
+    #[arg(long)]
+    print_bodies: bool,
+    /// Above this many bodies, a summary is printed instead of every body.
+    #[arg(long, default_value_t = 1000)]
+    summary_threshold: usize,
+    /// Track the minimum separation between any pair of bodies and report it at the end of the run.
+    #[arg(long)]
+    closest_approach: bool,
+    /// Warn whenever a pair of bodies comes within this separation, below which the tick is too
+    /// long to resolve their interaction reliably. Implies `--closest-approach`.
+    #[arg(long)]
+    warn_separation: Option<f64>,
+    /// Report bodies as escapers when they are further than this from the barycentre with a
+    /// positive specific energy relative to it.
+    #[arg(long)]
+    escape_radius: Option<f64>,
+    /// Remove escapers from the simulation, so they no longer contribute to the forces.
+    #[arg(long, requires = "escape_radius")]
+    remove_escapers: bool,
+    /// Print the orbital elements of body `i` about body `j` at the end of the run, given as `i,j`,
+    /// or about the barycentre of all other bodies, given as `i`. May be repeated.
+    #[arg(long, value_parser = parse_pair)]
+    elements: Vec<(usize, Option<usize>)>,
+    /// Format of the final report.
+    #[arg(long, value_enum, default_value_t)]
+    format: Format,
+    /// Write the final report to this file instead of stdout. Requires `--format json` or `vtk`.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    report: Option<PathBuf>,
+    /// If specified, the run fails with exit status 8 unless the final state hash (before any rest
+    /// frame transform) matches this hexadecimal value.
+    #[arg(long, value_parser = parse_hash)]
+    verify_hash: Option<u64>,
+    /// Seed for the random number generator.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Run a simulation.
+    ///
+    /// By default the `mass` column is taken to be Gm. If a unit system or gravitational constant
+    /// is specified, it is taken to be the actual mass instead, and masses are also reported in
+    /// those units.
+    ///
+    /// Ctrl-C stops the run after the current tick, writing a checkpoint if they are enabled, the
+    /// final state to `--save-state` and the report, and exits with status 130. A second Ctrl-C
+    /// exits immediately.
+    Run(Box<RunArgs>),
+    /// Convert bodies between CSV and binary snapshots.
+    ///
+    /// Binary snapshots are much faster to read and write than CSV for large numbers of bodies, and
+    /// keep every value and lifecycle state exactly.
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Convert {
+        #[command(subcommand)]
+        command: Option<ConvertCommand>,
+        /// CSV file or binary snapshot, possibly compressed, detected from its contents, or TOML or
+        /// JSON file if it has a `.toml` or `.json` extension.
+        #[arg(value_hint = ValueHint::FilePath, required = true)]
+        input: Option<PathBuf>,
+        /// CSV file if it has a `.csv` extension, Gadget-2 snapshot if it has a `.gadget` extension,
+        /// HDF5 file if it has an `.h5` or `.hdf5` extension and the `hdf5` feature is enabled, or
+        /// otherwise binary snapshot.
+        #[arg(value_hint = ValueHint::FilePath, required = true)]
+        output: Option<PathBuf>,
+        /// Unit system of CSV masses.
+        #[arg(short, long, value_enum)]
+        units: Option<Units>,
+        /// Gravitational constant of CSV masses, overriding the one implied by `--units`.
+        #[arg(long = "G", value_name = "G")]
+        g: Option<f64>,
+        /// Compress the output, appending `.gz` or `.zst` to its name.
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+    },
+    /// Print the energy, momentum, angular momentum and structure of the bodies in a file.
+    ///
+    /// Energies are of Newtonian gravity alone. The structure is given by the virial ratio, velocity
+    /// dispersion, density centre and radii enclosing 10%, 50% and 90% of the mass.
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Analyze {
+        #[command(subcommand)]
+        command: Option<AnalyzeCommand>,
+        /// File of bodies in any format accepted by `run --file`.
+        #[arg(value_hint = ValueHint::FilePath, required = true)]
+        input: Option<PathBuf>,
+        /// Unit system of CSV masses.
+        #[arg(short, long, value_enum)]
+        units: Option<Units>,
+        /// Gravitational constant of CSV masses, overriding the one implied by `--units`.
+        #[arg(long = "G", value_name = "G")]
+        g: Option<f64>,
+        /// Print the orbital elements of body `i` about body `j`, given as `i,j`, or about the
+        /// barycentre of all other bodies, given as `i`. May be repeated.
+        #[arg(long, value_parser = parse_pair)]
+        elements: Vec<(usize, Option<usize>)>,
+    },
+    /// Run every integrator on the same initial conditions and compare them.
+    ///
+    /// Bodies feel Newtonian gravity alone. Prints the relative energy drift, change in momentum and
+    /// wall-clock time of each integrator, then the final position of every body under each.
+    /// Energies of leapfrog are taken with its velocities synchronised to the positions, so that
+    /// its drift shrinks with the square of the tick as it should.
+    Compare {
+        /// File of initial conditions in any format accepted by `run --file`.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+        /// Duration of simulation.
+        #[arg(short, long)]
+        dur: f64,
+        /// Simulation time per tick.
+        #[arg(short, long, default_value_t = 1e-3)]
+        tick: f64,
+        /// Number of substeps per tick of the respa integrator.
+        #[arg(long, default_value = "4")]
+        substeps: NonZeroUsize,
+        /// Unit system of CSV masses.
+        #[arg(short, long, value_enum)]
+        units: Option<Units>,
+        /// Gravitational constant of CSV masses, overriding the one implied by `--units`.
+        #[arg(long = "G", value_name = "G")]
+        g: Option<f64>,
+    },
+    /// Measure the order of convergence of every integrator as the tick is halved.
+    ///
+    /// Bodies feel Newtonian gravity alone. Each integrator is run with `--levels` successively
+    /// halved ticks, and the error of its final positions against a reference solution, integrated
+    /// with leapfrog at an eighth of the smallest respa substep, is written as CSV with columns
+    /// `integrator`, `tick`, `error` and `order`. Errors are RMS distances from the reference
+    /// positions relative to the RMS initial distance of the bodies, and the order is the base-2
+    /// logarithm of the ratio of the error at the previous tick to that at this tick.
+    Converge {
+        /// File of initial conditions in any format accepted by `run --file`.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+        /// Duration of simulation.
+        #[arg(short, long)]
+        dur: f64,
+        /// Largest simulation time per tick, which is rounded down to divide the duration exactly.
+        #[arg(short, long, default_value_t = 1e-3)]
+        tick: f64,
+        /// Number of ticks to run each integrator with, each half the last.
+        #[arg(long, default_value = "5", value_parser = clap::value_parser!(u32).range(2..=20))]
+        levels: u32,
+        /// Number of substeps per tick of the respa integrator.
+        #[arg(long, default_value = "4")]
+        substeps: NonZeroUsize,
+        /// Write the CSV to this file instead of stdout, and print the order of each integrator at
+        /// the smallest tick.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: Option<PathBuf>,
+        /// Unit system of CSV masses.
+        #[arg(short, long, value_enum)]
+        units: Option<Units>,
+        /// Gravitational constant of CSV masses, overriding the one implied by `--units`.
+        #[arg(long = "G", value_name = "G")]
+        g: Option<f64>,
+    },
+    /// Check initial conditions for problems and suggest a tick.
+    ///
+    /// Reports missing or unknown CSV headers, unparseable rows, non-finite values, negative masses,
+    /// radii and softening lengths, and bodies at the same position, then suggests a tick of 1% of
+    /// the shortest free-fall or crossing time of any pair of bodies. Exits with an error if there
+    /// are any errors.
+    Check {
+        /// File of initial conditions in any format accepted by `run --file`.
+        #[arg(value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+        /// Unit system of CSV masses.
+        #[arg(short, long, value_enum)]
+        units: Option<Units>,
+        /// Gravitational constant of CSV masses, overriding the one implied by `--units`.
+        #[arg(long = "G", value_name = "G")]
+        g: Option<f64>,
+    },
+    /// Fetch heliocentric state vectors of major bodies from JPL Horizons and write them to a file.
+    ///
+    /// Vectors are in the ecliptic frame of J2000, and named after the bodies.
+    #[cfg(feature = "net")]
+    Fetch {
+        /// Comma-separated bodies, or ranges of them in order from the Sun such as
+        /// `mercury..neptune`, out of sun, mercury, venus, earth, mars, jupiter, saturn, uranus,
+        /// neptune and pluto. The moon can also be given on its own.
+        #[arg(long)]
+        bodies: String,
+        /// Time of the vectors in TDB, as a calendar date such as `2024-01-01` or
+        /// `2024-01-01 12:00`, or a Julian date.
+        #[arg(long)]
+        epoch: String,
+        /// File to write, in any format supported by `convert`.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: PathBuf,
+        /// Unit system of the positions, velocities and masses written.
+        #[arg(short, long, value_enum, default_value = "si")]
+        units: Units,
+        /// Compress the output, appending `.gz` or `.zst` to its name.
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+    },
+    /// Generate random initial conditions and write them to a file.
+    ///
+    /// Masses are in Gm units, and the system is centred on the origin. Disks feel any
+    /// `--potential`.
+    Generate {
+        /// Model, optionally with parameters as `model:key=value,key=value`: `uniform-sphere`
+        /// (`mass`, `radius`, and `virial` ratio 2T/|W|), `cold-collapse` (`mass`, `radius`),
+        /// `plummer` (`mass`, `scale`), `king` (`mass`, `scale` as the King radius, and central
+        /// potential `w0`), `merger` of two uniform spheres (`mass`, `radius`, `separation`,
+        /// `speed`, `impact`), `disk` (`mass`, `scale`, `height`, Toomre `q`, and `bulge` mass at
+        /// the centre), `binary` (`m1`, `m2`, `a`, `e`, `anomaly`) or hierarchical `triple` of an
+        /// inner binary and outer companion (`m1`, `m2`, `m3`, `a1`, `e1`, `anomaly1`, `a2`, `e2`,
+        /// `anomaly2`, and the inner orbit's mutual inclination `i` and argument of `periapsis`).
+        /// Angles are in degrees.
+        #[arg(long)]
+        model: String,
+        /// Number of bodies of models which take one, unless given as the `n` parameter.
+        #[arg(long)]
+        n: Option<usize>,
+        /// Seed for the random number generator.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// External potential in which disks rotate, as for `run --potential`. May be repeated.
+        #[arg(long)]
+        potential: Vec<Potential>,
+        /// File to write, in any format supported by `convert`.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: PathBuf,
+        /// Unit system of CSV masses.
+        #[arg(short, long, value_enum)]
+        units: Option<Units>,
+        /// Gravitational constant of CSV masses, overriding the one implied by `--units`.
+        #[arg(long = "G", value_name = "G")]
+        g: Option<f64>,
+        /// Compress the output, appending `.gz` or `.zst` to its name.
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+    },
+    /// Render trajectories written by `run --out` to a PNG or SVG image.
+    #[cfg(feature = "plot")]
+    Plot {
+        /// Trajectory CSV file, possibly compressed.
+        #[arg(value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+        /// Plane onto which the trajectories are projected.
+        #[arg(long, value_enum, default_value = "xy")]
+        axes: Axes,
+        /// Image file, SVG if it has a `.svg` extension and otherwise PNG.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: PathBuf,
+    },
+    /// Render trajectories written by `run --out` to an animated GIF or MP4, with a frame per output.
+    #[cfg(feature = "plot")]
+    Render {
+        /// Trajectory CSV file, possibly compressed.
+        #[arg(value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+        /// Animation file, MP4 if it has a `.mp4` extension, which needs `ffmpeg` to be installed,
+        /// and otherwise GIF.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: PathBuf,
+        /// Frames per second.
+        #[arg(long, default_value_t = 30)]
+        fps: u32,
+        /// Plane onto which the trajectories are projected.
+        #[arg(long, value_enum, default_value = "xy")]
+        axes: Axes,
+        /// Magnification about the centre of the whole trajectory.
+        #[arg(long, default_value_t = 1.)]
+        zoom: f64,
+        /// Number of previous frames over which each body's path is drawn behind it, unless the
+        /// body has its own `trail` in the system it was run from.
+        #[arg(long, default_value_t = 20)]
+        trail: usize,
+        /// Colouring of the bodies.
+        #[arg(long, value_enum, default_value = "body")]
+        color: Coloring,
+    },
+    /// Gather the outputs of a run into a report with tables and plots.
+    ///
+    /// The run directory should hold the files written by `run` with `--format json --report
+    /// <DIR>/report.json`, `--diag-out <DIR>/diagnostics.csv` and `--out <DIR>/trajectory.csv`, any
+    /// of which may be missing or compressed. The report gives the parameters of the run, the
+    /// conservation of energy and momentum, the final diagnostics with plots of their history, and
+    /// plots of the trajectories.
+    #[cfg(feature = "plot")]
+    Report {
+        /// Directory of the run's output files.
+        #[arg(value_hint = ValueHint::DirPath)]
+        run: PathBuf,
+        /// Report file, a self-contained HTML page if it has a `.html` extension and otherwise
+        /// Markdown, with its plots written beside it as SVG files. By default `report.html` in the
+        /// run directory.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: Option<PathBuf>,
+    },
+    /// Run the built-in validation checks and report whether each passed within its tolerance.
+    Selftest,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ConvertCommand {
+    /// Replace the orbital elements of bodies in a TOML or JSON file by their positions and
+    /// velocities.
+    ElementsToCartesian {
+        /// TOML file, or JSON file if it has a `.json` extension.
+        #[arg(value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+        /// TOML file, or JSON file if it has a `.json` extension.
+        #[arg(value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+        /// Gravitational constant of the masses, overriding the one set by the input.
+        #[arg(long = "G", value_name = "G")]
+        g: Option<f64>,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum AnalyzeCommand {
+    /// Analyse the sensitivity of the final state of a run to its initial conditions.
+    ///
+    /// The run is repeated with each initial position, velocity and mass component perturbed by
+    /// `--step`, and how far each perturbation moves the final state is printed, most sensitive
+    /// first. Takes the options of `run`, except `--resume` and `--ensemble`.
+    Sensitivity {
+        /// Step of the perturbations, relative to each component.
+        #[arg(long, conflicts_with_all = ["resume", "ensemble"])]
+        step: f64,
+        #[command(flatten)]
+        run: Box<RunArgs>,
+    },
+    /// Compare the outputs of several runs.
+    ///
+    /// Each run directory holds the outputs of a run as for `report`. The comparison has a table of
+    /// the final values of each run with their differences from the first, and plots overlaying
+    /// their energy drift, Lagrangian radii and virial ratios and the orbital elements given by
+    /// `--elements`.
+    #[cfg(feature = "plot")]
+    Compare {
+        /// Directories of the runs' output files.
+        #[arg(value_hint = ValueHint::DirPath, num_args = 2.., required = true)]
+        runs: Vec<PathBuf>,
+        /// Comparison file, a self-contained HTML page if it has a `.html` extension and otherwise
+        /// Markdown, with its plots written beside it as SVG files.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: PathBuf,
+        /// Plot the orbital elements of body `i` about body `j` over the trajectories, given as
+        /// `i,j`, or about the barycentre of all other bodies, given as `i`. May be repeated.
+        #[arg(long, value_parser = parse_pair)]
+        elements: Vec<(usize, Option<usize>)>,
+    },
 }
 
 #[derive(clap::ValueEnum, Clone, Default, Debug, Serialize)]
@@ -40,9 +763,43 @@ enum SimType {
     ForwardEuler,
     SymplecticEuler,
     Leapfrog,
+    /// Two-level leapfrog, integrating every force except external potentials with
+    /// `--substeps` substeps per tick
+    Respa,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+enum Format {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// JSON, with every body regardless of `--summary-threshold`
+    Json,
+    /// Legacy VTK point cloud of the active bodies, for ParaView
+    Vtk,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug)]
+enum Interaction {
+    /// Newtonian gravity
+    #[default]
+    Gravity,
+    /// Electrostatic forces between charged bodies
+    Coulomb,
+    /// Gravity and electrostatic forces
+    Both,
+    /// Screened electrostatic forces between charged bodies, with `--screening-length`
+    Yukawa,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum Relativity {
+    /// First-order post-Newtonian corrections
+    #[value(name = "1pn")]
+    FirstOrder,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
 struct CsvBody {
     pos_x: f64,
     pos_y: f64,
@@ -51,101 +808,1755 @@ struct CsvBody {
     vel_y: f64,
     vel_z: f64,
     mass: f64,
+    #[serde(default)]
+    radius: f64,
+    #[serde(default)]
+    test: bool,
+    #[serde(default)]
+    fixed: bool,
+    #[serde(default)]
+    mass_rate: f64,
+    #[serde(default)]
+    charge: f64,
+    #[serde(default)]
+    softening: f64,
+    name: Option<String>,
 }
 
-impl From<CsvBody> for forward_euler::Body {
+impl From<CsvBody> for Body {
     fn from(value: CsvBody) -> Self {
-        Self::new(
-            Point3::new(value.pos_x, value.pos_y, value.pos_z),
-            Vec3::new(value.vel_x, value.vel_y, value.vel_z),
-            value.mass,
-        )
+        Self {
+            radius: value.radius,
+            test: value.test,
+            fixed: value.fixed,
+            mass_rate: value.mass_rate,
+            charge: value.charge,
+            softening: value.softening,
+            name: value.name,
+            ..Self::new(
+                Point3::new(value.pos_x, value.pos_y, value.pos_z),
+                Vec3::new(value.vel_x, value.vel_y, value.vel_z),
+                value.mass,
+            )
+        }
     }
 }
 
-impl From<CsvBody> for symplectic_euler::Body {
-    fn from(value: CsvBody) -> Self {
-        Self::new(
-            Point3::new(value.pos_x, value.pos_y, value.pos_z),
-            Vec3::new(value.vel_x, value.vel_y, value.vel_z),
-            value.mass,
-        )
+impl From<&Body> for CsvBody {
+    fn from(body: &Body) -> Self {
+        Self {
+            pos_x: body.position.x(),
+            pos_y: body.position.y(),
+            pos_z: body.position.z(),
+            vel_x: body.velocity.x(),
+            vel_y: body.velocity.y(),
+            vel_z: body.velocity.z(),
+            mass: body.mass,
+            radius: body.radius,
+            test: body.test,
+            fixed: body.fixed,
+            mass_rate: body.mass_rate,
+            charge: body.charge,
+            softening: body.softening,
+            name: body.name.clone(),
+        }
     }
 }
 
-impl From<CsvBody> for leapfrog::Body {
-    fn from(value: CsvBody) -> Self {
-        Self::new(
-            Point3::new(value.pos_x, value.pos_y, value.pos_z),
-            Vec3::new(value.vel_x, value.vel_y, value.vel_z),
-            value.mass,
-        )
+fn write_csv(file: impl Write, bodies: &[Body], g: f64) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_writer(file);
+    for body in bodies {
+        let mut row = CsvBody::from(body);
+        row.mass /= g;
+        row.mass_rate /= g;
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads bodies and the time from a binary snapshot, or otherwise bodies from a CSV, TOML or JSON
+/// file with masses converted to Gm with the gravitational constant and a time of zero. Snapshots
+/// and CSV files may be compressed. The gravitational constant is `g` if given, or otherwise that
+/// set by a TOML or JSON file, and is returned.
+fn read_bodies(
+    path: impl AsRef<Path>,
+    g: Option<f64>,
+) -> Result<(f64, Vec<Body>, Option<f64>), Error> {
+    let path = path.as_ref();
+    if let Some("toml" | "json") = path.extension().and_then(|e| e.to_str()) {
+        let system = read_system(path)?;
+        let g = g.or(system.g);
+        let bodies = (system.bodies(g.unwrap_or(1.))).map_err(|e| Error::malformed(path, e))?;
+        return Ok((0., bodies, g));
+    }
+    let mut reader = open(path)?;
+    let header = reader.fill_buf().map_err(|e| Error::reading(path, e))?;
+    if header.starts_with(snapshot::MAGIC) {
+        let (time, bodies) = snapshot::read(reader).map_err(|e| Error::malformed(path, e))?;
+        Ok((time, bodies, g))
+    } else {
+        let bodies = read_csv(reader, g.unwrap_or(1.)).map_err(|e| Error::malformed(path, e))?;
+        Ok((0., bodies, g))
+    }
+}
+
+/// Opens a file which may be compressed.
+fn open(path: &Path) -> Result<Box<dyn BufRead>, Error> {
+    compress::open(path).map_err(|e| Error::reading(path, e))
+}
+
+/// Prints the problems with the initial conditions in a file and the tightest pair of bodies, and
+/// returns whether there were no errors. Every row of a CSV file is checked, and labelled with its
+/// line.
+fn check_file(path: &Path, g: Option<f64>) -> Result<bool, Error> {
+    // Location, severity and message of each problem
+    let mut problems: Vec<(Option<String>, Severity, String)> = Vec::new();
+    let is_csv = !matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("toml" | "json")
+    ) && {
+        let mut reader = open(path)?;
+        let header = reader.fill_buf().map_err(|e| Error::reading(path, e))?;
+        !header.starts_with(snapshot::MAGIC)
+    };
+    // Line of each body in a CSV file
+    let mut lines = Vec::new();
+    let bodies = if is_csv {
+        let mut reader = csv::Reader::from_reader(open(path)?);
+        let headers = reader.headers().cloned().unwrap_or_default();
+        for problem in check::headers(&headers) {
+            problems.push((None, problem.severity, problem.message));
+        }
+        let mut bodies = Vec::new();
+        let mut record = csv::StringRecord::new();
+        loop {
+            let line = reader.position().line();
+            let result = reader.read_record(&mut record).and_then(|more| {
+                more.then(|| record.deserialize::<CsvBody>(Some(&headers)))
+                    .transpose()
+            });
+            match result {
+                Ok(None) => break,
+                Ok(Some(row)) => {
+                    let mut body: Body = row.into();
+                    body.mass *= g.unwrap_or(1.);
+                    body.mass_rate *= g.unwrap_or(1.);
+                    bodies.push(body);
+                    lines.push(line);
+                }
+                Err(e) => {
+                    let location = Some(format!("line {}", line));
+                    problems.push((location, Severity::Error, e.to_string()));
+                    if e.is_io_error() {
+                        break;
+                    }
+                }
+            }
+        }
+        bodies
+    } else {
+        read_bodies(path, g)?.1
+    };
+
+    let label = |i: usize| {
+        let location = match lines.get(i) {
+            Some(line) => format!("line {}", line),
+            None => format!("body {}", i),
+        };
+        match &bodies[i].name {
+            Some(name) => format!("{} ({})", location, name),
+            None => location,
+        }
+    };
+    for problem in check::bodies(&bodies, label) {
+        problems.push((problem.body.map(label), problem.severity, problem.message));
+    }
+    for (location, severity, message) in &problems {
+        match location {
+            Some(location) => println!("{}: {}: {}", location, severity, message),
+            None => println!("{}: {}", severity, message),
+        }
+    }
+    let count = |severity| problems.iter().filter(|p| p.1 == severity).count();
+    let errors = count(Severity::Error);
+    println!(
+        "{} bodies, {} errors, {} warnings",
+        bodies.len(),
+        errors,
+        count(Severity::Warning)
+    );
+    if let Some(tightest) = check::tightest(&bodies) {
+        let (i, j) = tightest.bodies;
+        println!(
+            "Tightest pair: {} and {}, {:e} apart with a timescale of {:e}; suggested --tick {:.1e}",
+            label(i),
+            label(j),
+            tightest.separation,
+            tightest.timescale,
+            tightest.tick
+        );
+    }
+    Ok(errors == 0)
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+/// Reads a system from a JSON file if the path has a `.json` extension, or otherwise a TOML file.
+fn read_system(path: &Path) -> Result<System, Error> {
+    let contents = fs::read_to_string(path).map_err(|e| Error::reading(path, e))?;
+    if is_json(path) {
+        System::from_json(&contents).map_err(|e| Error::malformed(path, e))
+    } else {
+        System::from_toml(&contents).map_err(|e| Error::malformed(path, e))
     }
 }
 
-fn read_csv<B>(file: File) -> Result<Vec<B>, csv::Error>
-where
-    B: From<CsvBody>,
-{
+/// Writes bodies to a CSV file with masses converted from Gm with the gravitational constant `g`
+/// if the path has a `.csv` extension, to a Gadget-2 snapshot if it has a `.gadget` extension, to an
+/// HDF5 file if it has an `.h5` or `.hdf5` extension, or otherwise to a binary snapshot at `time`.
+/// CSV, Gadget and HDF5 files have no lifecycle state, so only active bodies are written to them.
+/// The name and tick of the `integrator` are recorded in HDF5 files. CSV files and binary snapshots
+/// are compressed with `compression`, if given. Each file is written under a temporary name which
+/// replaces the path once complete, after syncing it to disk if `sync`.
+fn write_bodies(
+    path: impl AsRef<Path>,
+    time: f64,
+    bodies: &[Body],
+    g: f64,
+    integrator: Option<(&str, f64)>,
+    compression: Option<Compression>,
+    sync: bool,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let context = format!("unable to write {}", path.display());
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => {
+            let active: Vec<Body> = (bodies.iter())
+                .filter(|body| body.is_active())
+                .cloned()
+                .collect();
+            let mut file = compress::create(path, compression, sync).context(&context)?;
+            write_csv(&mut file, &active, g).context(&context)?;
+            file.finish().context(&context)
+        }
+        Some("gadget") => {
+            let mut file = AtomicFile::create(path, sync).context(&context)?;
+            gadget::write(&mut file, time, bodies, g).context(&context)?;
+            file.finish().context(&context)
+        }
+        Some("h5" | "hdf5") => {
+            // The HDF5 library writes the file itself, so it is renamed into place afterwards
+            let temporary = compress::temporary_path(path);
+            write_hdf5(&temporary, time, bodies, g, integrator).context(&context)?;
+            if sync {
+                File::open(&temporary)
+                    .and_then(|file| file.sync_all())
+                    .context(&context)?;
+            }
+            compress::rename(&temporary, path, sync).context(&context)
+        }
+        _ => {
+            let mut file = compress::create(path, compression, sync).context(&context)?;
+            snapshot::write(&mut file, time, bodies).context(&context)?;
+            file.finish().context(&context)
+        }
+    }
+}
+
+#[cfg(feature = "hdf5")]
+fn write_hdf5(
+    path: &Path,
+    time: f64,
+    bodies: &[Body],
+    g: f64,
+    integrator: Option<(&str, f64)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Ok(rs_nbody::hdf5_snapshot::write(
+        path, time, bodies, g, integrator,
+    )?)
+}
+
+#[cfg(not(feature = "hdf5"))]
+fn write_hdf5(
+    _: &Path,
+    _: f64,
+    _: &[Body],
+    _: f64,
+    _: Option<(&str, f64)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err("writing HDF5 files requires building with the hdf5 feature".into())
+}
+
+/// Reads bodies from `file`, converting masses (and their rates of change) to Gm with the
+/// gravitational constant `g`.
+fn read_csv(file: impl Read, g: f64) -> Result<Vec<Body>, Box<dyn std::error::Error>> {
     let mut reader = csv::Reader::from_reader(file);
-    reader
+    let problems = check::headers(reader.headers()?);
+    if let Some(problem) = (problems.iter()).find(|problem| problem.severity == Severity::Error) {
+        return Err(problem.message.clone().into());
+    }
+    Ok(reader
         .deserialize()
-        .map(|res| res.map(|row: CsvBody| row.into()))
-        .collect()
+        .map(|res| {
+            res.map(|row: CsvBody| {
+                let mut body: Body = row.into();
+                body.mass *= g;
+                body.mass_rate *= g;
+                body
+            })
+        })
+        .collect::<Result<_, _>>()?)
+}
+
+/// Final report of a run, for `--format json`. Energies and momenta are in the same units as the
+/// text report.
+#[derive(Serialize)]
+struct Report<'a> {
+    time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scale_factor: Option<f64>,
+    parameters: ReportParameters<'a>,
+    energy: ReportChange<f64>,
+    momentum: ReportChange<Vec3>,
+    center_of_mass_drift: Vec3,
+    angular_momentum: ReportChange<Vec3>,
+    state_hash: String,
+    bodies: Vec<ReportBody>,
+}
+
+#[derive(Serialize)]
+struct ReportParameters<'a> {
+    sim: &'a SimType,
+    tick: f64,
+    duration: f64,
+    #[serde(rename = "G", skip_serializing_if = "Option::is_none")]
+    g: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct ReportChange<T> {
+    initial: T,
+    #[serde(rename = "final")]
+    end: T,
+}
+
+/// Body as it is serialised, with its actual mass and speed.
+#[derive(Serialize)]
+struct ReportBody {
+    #[serde(flatten)]
+    body: Body,
+    /// Actual mass, if G is known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mass: Option<f64>,
+    speed: f64,
+}
+
+impl ReportBody {
+    fn new(body: &Body, g: Option<f64>) -> Self {
+        Self {
+            body: body.clone(),
+            mass: g.map(|g| body.mass / g),
+            speed: body.velocity.length(),
+        }
+    }
+}
+
+/// Line of `--stream` output.
+#[derive(Serialize)]
+struct StreamLine {
+    time: f64,
+    bodies: Vec<ReportBody>,
+}
+
+/// Prints aggregate properties of the bodies, for when there are too many to print each one.
+fn print_summary(bodies: &[Body], g: Option<f64>) {
+    let count = |f: fn(&Body) -> bool| bodies.iter().filter(|b| f(b)).count();
+    println!(
+        "{} bodies ({} test particles, {} fixed, {} inactive)",
+        bodies.len(),
+        count(|b| b.is_active() && !b.is_source()),
+        count(|b| b.fixed),
+        count(|b| !b.is_active())
+    );
+    let mass: f64 = bodies
+        .iter()
+        .filter(|b| b.is_active())
+        .map(|b| b.mass)
+        .sum();
+    match g {
+        Some(g) => println!("Total Gm = {:e}, m = {:e}", mass, mass / g),
+        None => println!("Total Gm = {:e}", mass),
+    }
+    let max_by = |f: fn(&Body) -> f64| {
+        bodies
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.is_active())
+            .max_by(|(_, a), (_, b)| f(a).total_cmp(&f(b)))
+            .map(|(i, b)| (i, f(b)))
+    };
+    if let Some((i, distance)) = max_by(|b| b.position.length()) {
+        println!("Furthest from origin: body {} at r = {:e}", i, distance);
+    }
+    if let Some((i, speed)) = max_by(|b| b.velocity.length()) {
+        println!("Fastest: body {} at speed = {:e}", i, speed);
+    }
+}
+
+fn parse_vec3(s: &str) -> Result<Vec3, String> {
+    let components = s
+        .split(',')
+        .map(|c| c.trim().parse::<f64>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match components[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!(
+            "expected three components `x,y,z`, found {}",
+            components.len()
+        )),
+    }
+}
+
+fn parse_pair(s: &str) -> Result<(usize, Option<usize>), String> {
+    let parse = |s: &str| s.trim().parse::<usize>().map_err(|e| e.to_string());
+    match s.split_once(',') {
+        Some((i, j)) => Ok((parse(i)?, Some(parse(j)?))),
+        None => Ok((parse(s)?, None)),
+    }
+}
+
+/// Index of the body referred to by `s`, which is either an index or a name.
+fn body_index(bodies: &[Body], s: &str) -> Result<usize, String> {
+    if let Ok(i) = s.parse::<usize>() {
+        return check_index(bodies, i);
+    }
+    (bodies.iter())
+        .position(|body| body.name.as_deref() == Some(s))
+        .ok_or_else(|| format!("there is no body named {:?}", s))
+}
+
+/// `i`, if it is the index of one of the bodies.
+fn check_index(bodies: &[Body], i: usize) -> Result<usize, String> {
+    match i < bodies.len() {
+        true => Ok(i),
+        false => Err(format!("there is no body {} among {}", i, bodies.len())),
+    }
+}
+
+/// Checks that the bodies which forces refer to exist. Forces refer to bodies by id, which is their
+/// index in the initial conditions.
+fn check_body_ids(args: &RunArgs, bodies: &[Body]) -> Result<(), Error> {
+    let check = |flag: &str, id: usize| match bodies.iter().any(|body| body.id == id) {
+        true => Ok(()),
+        false => Err(Error::InvalidArguments(format!(
+            "invalid --{}: there is no body {}",
+            flag, id
+        ))),
+    };
+    for &body in &args.friction_bodies {
+        check("friction-bodies", body)?;
+    }
+    if let Some(source) = args.radiation_source {
+        check("radiation-source", source)?;
+    }
+    for &body in &args.radiation_bodies {
+        check("radiation-bodies", body)?;
+    }
+    if let Some(body) = args.j2_body {
+        check("j2-body", body)?;
+    }
+    for tide in &args.tide {
+        check("tide", tide.primary)?;
+        check("tide", tide.secondary)?;
+    }
+    if let Some(centre) = args.drag_centre {
+        check("drag-centre", centre)?;
+    }
+    Ok(())
+}
+
+fn parse_hash(s: &str) -> Result<u64, std::num::ParseIntError> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16)
+}
+
+/// Parses a duration such as `90s`, `45m`, `2h`, `1d` or `1h30m`, or a plain number of seconds.
+fn parse_walltime(s: &str) -> Result<Duration, String> {
+    if let Ok(seconds) = s.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string());
+    }
+    let mut seconds = 0.;
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        let unit_start = (rest.find(|c: char| c.is_ascii_alphabetic()))
+            .ok_or_else(|| format!("missing unit after `{}`", rest))?;
+        let unit_end = (rest[unit_start..].find(|c: char| !c.is_ascii_alphabetic()))
+            .map_or(rest.len(), |i| unit_start + i);
+        let value: f64 = (rest[..unit_start].parse())
+            .map_err(|_| format!("invalid number `{}`", &rest[..unit_start]))?;
+        let unit = match &rest[unit_start..unit_end] {
+            "s" => 1.,
+            "m" | "min" => 60.,
+            "h" => 3600.,
+            "d" => 86400.,
+            unit => return Err(format!("unknown unit `{}`, expected s, m, h or d", unit)),
+        };
+        seconds += value * unit;
+        rest = &rest[unit_end..];
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+fn print_body(body: &Body, g: Option<f64>) {
+    if let Some(name) = &body.name {
+        print!("{}: ", name);
+    }
+    match g {
+        Some(g) => println!(
+            "{}, m = {:e}, speed = {}",
+            body,
+            body.mass / g,
+            body.velocity.length()
+        ),
+        None => println!("{}, speed = {}", body, body.velocity.length()),
+    }
+}
+
+fn simulation_box(args: &RunArgs) -> Option<SimulationBox> {
+    let boundary = match args.periodic {
+        true => Some(Boundary::Periodic),
+        false => args.boundary,
+    };
+    Some(SimulationBox {
+        size: args.box_size?,
+        boundary: boundary?,
+    })
+}
+
+/// Speed of light from `--speed-of-light` or `--units`.
+fn speed_of_light(args: &RunArgs) -> Option<f64> {
+    args.speed_of_light
+        .or(args.units.map(Units::speed_of_light))
+}
+
+/// Checks that a speed of light is given for the forces which need one.
+fn check_speed_of_light(args: &RunArgs) -> Result<(), Error> {
+    if speed_of_light(args).is_none() {
+        if args.relativity.is_some() {
+            return Err(Error::InvalidArguments(
+                "--relativity requires --units or --speed-of-light".to_string(),
+            ));
+        }
+        if args.radiation_source.is_some() {
+            return Err(Error::InvalidArguments(
+                "--radiation-source requires --units or --speed-of-light".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Builds the forces acting on the bodies, which must have been checked by
+/// `check_speed_of_light`.
+fn forces(args: &RunArgs, g: Option<f64>) -> Vec<Box<dyn Force>> {
+    let c = speed_of_light(args);
+    let periodic = simulation_box(args).and_then(|b| b.periodic());
+    let mut forces: Vec<Box<dyn Force>> = Vec::new();
+    if let Interaction::Gravity | Interaction::Both = args.interaction {
+        match periodic {
+            Some(periodic) if args.ewald => forces.push(Box::new(Ewald::new(periodic))),
+            _ => forces.push(Box::new(Gravity {
+                periodic,
+                exponent: args.force_exponent,
+                mond: args.mond_a0,
+                deterministic: args.deterministic,
+                compensated: args.compensated,
+            })),
+        }
+    }
+    if let Interaction::Coulomb | Interaction::Both = args.interaction {
+        forces.push(Box::new(Coulomb {
+            k: args.coulomb_constant,
+            g: g.unwrap_or(1.),
+            periodic,
+        }));
+    }
+    if let (Interaction::Yukawa, Some(length)) = (args.interaction, args.screening_length) {
+        forces.push(Box::new(Yukawa {
+            k: args.coulomb_constant,
+            g: g.unwrap_or(1.),
+            length,
+            periodic,
+        }));
+    }
+    if let Some(field) = args.magnetic_field {
+        forces.push(Box::new(MagneticField {
+            field,
+            g: g.unwrap_or(1.),
+        }));
+    }
+    if !args.friction_bodies.is_empty() {
+        forces.push(Box::new(DynamicalFriction {
+            bodies: args.friction_bodies.clone(),
+            density: args.friction_density,
+            dispersion: args.friction_dispersion,
+            coulomb_logarithm: args.friction_log,
+        }));
+    }
+    if let (Some(Relativity::FirstOrder), Some(c)) = (&args.relativity, c) {
+        forces.push(Box::new(PostNewtonian { c }));
+    }
+    if let (Some(source), Some(c)) = (args.radiation_source, c) {
+        forces.push(Box::new(RadiationPressure {
+            source,
+            beta: args.radiation_beta,
+            bodies: args.radiation_bodies.clone(),
+            c,
+        }));
+    }
+    if let (Some(body), Some(j2), Some(radius)) = (args.j2_body, args.j2, args.j2_radius) {
+        forces.push(Box::new(Oblateness { body, j2, radius }));
+    }
+    for tide in &args.tide {
+        forces.push(Box::new(tide.clone()));
+    }
+    for potential in &args.potential {
+        forces.push(Box::new(potential.clone()));
+    }
+    if args.drag_linear != 0. || args.drag_quadratic != 0. {
+        forces.push(Box::new(Drag {
+            linear: args.drag_linear,
+            quadratic: args.drag_quadratic,
+            radius: args.drag_radius,
+            centre: args.drag_centre,
+        }));
+    }
+    if let Some(mass) = args.background_mass {
+        forces.push(Box::new(BackgroundField {
+            mass,
+            scale: args.background_scale,
+            star_mass: args.background_star_mass,
+            coulomb_logarithm: args.background_log,
+            rng: Rng::new(args.seed),
+        }));
+    }
+    forces
+}
+
+/// Parses the command line, with any options from `run --config` appended to it.
+fn parse_cli() -> Result<Cli, Error> {
+    let cli: Vec<OsString> = std::env::args_os().collect();
+    // Required options may be in the config file, so errors are ignored until it has been read
+    let matches = Cli::command()
+        .ignore_errors(true)
+        .mut_subcommand("run", |run| run.ignore_errors(true))
+        .mut_subcommand("analyze", |analyze| {
+            (analyze.ignore_errors(true))
+                .mut_subcommand("sensitivity", |sensitivity| sensitivity.ignore_errors(true))
+        })
+        .get_matches_from(&cli);
+    // The options of a run are taken by `run` and `analyze sensitivity`
+    let (names, matches): (&[&str], _) = match matches.subcommand() {
+        Some(("run", matches)) => (&["run"], matches),
+        Some(("analyze", matches)) => match matches.subcommand_matches("sensitivity") {
+            Some(matches) => (&["analyze", "sensitivity"], matches),
+            None => return Ok(Cli::parse_from(cli)),
+        },
+        _ => return Ok(Cli::parse_from(cli)),
+    };
+    let Some(path) = matches.get_one::<PathBuf>("config") else {
+        return Ok(Cli::parse_from(cli));
+    };
+    let invalid =
+        |e: String| Error::InvalidArguments(format!("invalid config {}: {}", path.display(), e));
+    let text = fs::read_to_string(path).map_err(|e| Error::reading(path, e))?;
+    let table: toml::Table = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let command = Cli::command();
+    let run = (names.iter()).fold(&command, |command, name| {
+        command.find_subcommand(name).unwrap()
+    });
+    let arguments = config::arguments(run, &table, given).map_err(invalid)?;
+    Ok(Cli::parse_from(
+        cli.into_iter()
+            .chain(arguments.into_iter().map(OsString::from)),
+    ))
 }
 
 fn main() {
-    let args = Args::parse();
-    let file = File::open(args.file).expect("Unable to open the specified file");
+    if let Err(error) = execute() {
+        eprintln!("error: {}", error);
+        std::process::exit(error.exit_code());
+    }
+}
+
+/// Executes the subcommand given on the command line.
+fn execute() -> Result<(), Error> {
+    match parse_cli()?.command {
+        Command::Run(args) => simulate(&args, None)?,
+        Command::Convert {
+            command: Some(ConvertCommand::ElementsToCartesian { input, output, g }),
+            ..
+        } => {
+            let system = read_system(&input)?;
+            let system = (system.to_cartesian(g.or(system.g).unwrap_or(1.)))
+                .map_err(|e| Error::malformed(&input, e))?;
+            let contents = if is_json(&output) {
+                system.to_json().context("unable to serialise the system")?
+            } else {
+                system.to_toml().context("unable to serialise the system")?
+            };
+            let context = format!("unable to write {}", output.display());
+            let mut file = AtomicFile::create(&output, false).context(&context)?;
+            file.write_all(contents.as_bytes()).context(&context)?;
+            file.finish().context(&context)?;
+            println!(
+                "Converted {} bodies to {}",
+                system.bodies.len(),
+                output.display()
+            );
+        }
+        Command::Convert {
+            input,
+            output,
+            units,
+            g,
+            compress,
+            ..
+        } => {
+            // Both are required without a nested subcommand
+            let (input, output) = (input.unwrap(), output.unwrap());
+            let g = g.or(units.map(Units::gravitational_constant));
+            let (time, bodies, g) = read_bodies(&input, g)?;
+            write_bodies(
+                &output,
+                time,
+                &bodies,
+                g.unwrap_or(1.),
+                None,
+                compress,
+                false,
+            )?;
+            println!("Converted {} bodies to {}", bodies.len(), output.display());
+        }
+        Command::Analyze {
+            command: Some(AnalyzeCommand::Sensitivity { step, run }),
+            ..
+        } => simulate(&run, Some(step))?,
+        #[cfg(feature = "plot")]
+        Command::Analyze {
+            command:
+                Some(AnalyzeCommand::Compare {
+                    runs,
+                    out,
+                    elements,
+                }),
+            ..
+        } => {
+            let runs = (runs.iter())
+                .map(|run| Run::read(run).context(&format!("unable to read {}", run.display())))
+                .collect::<Result<Vec<_>, _>>()?;
+            report::compare(&out, &runs, &elements).context("unable to compare the runs")?;
+            println!("Compared {} runs in {}", runs.len(), out.display());
+        }
+        Command::Analyze {
+            input,
+            units,
+            g,
+            elements,
+            ..
+        } => {
+            // Required without a nested subcommand
+            let input = input.unwrap();
+            let g = g.or(units.map(Units::gravitational_constant));
+            let (time, bodies, g) = read_bodies(&input, g)?;
+            analyze(time, bodies, &elements, g);
+        }
+        Command::Compare {
+            file,
+            dur,
+            tick,
+            substeps,
+            units,
+            g,
+        } => {
+            let g = g.or(units.map(Units::gravitational_constant));
+            let (_, bodies, g) = read_bodies(&file, g)?;
+            compare(&bodies, dur, tick, substeps.get(), g);
+        }
+        Command::Converge {
+            file,
+            dur,
+            tick,
+            levels,
+            substeps,
+            out,
+            units,
+            g,
+        } => {
+            let g = g.or(units.map(Units::gravitational_constant));
+            let (_, bodies, _) = read_bodies(&file, g)?;
+            let (levels, substeps) = (levels as usize, substeps.get());
+            match out {
+                Some(out) => {
+                    let context = format!("unable to write {}", out.display());
+                    let mut file = AtomicFile::create(&out, false).context(&context)?;
+                    let orders = converge(&bodies, dur, tick, levels, substeps, &mut file)
+                        .context(&context)?;
+                    file.finish().context(&context)?;
+                    for (name, order) in orders {
+                        println!("Order of {}: {:.3}", name, order.unwrap());
+                    }
+                }
+                None => {
+                    let stdout = std::io::stdout().lock();
+                    converge(&bodies, dur, tick, levels, substeps, stdout)
+                        .context("unable to write the convergence study")?;
+                }
+            }
+        }
+        Command::Check { input, units, g } => {
+            let g = g.or(units.map(Units::gravitational_constant));
+            let passed = check_file(&input, g)?;
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+        #[cfg(feature = "net")]
+        Command::Fetch {
+            bodies,
+            epoch,
+            out,
+            units,
+            compress,
+        } => {
+            let majors = horizons::parse_bodies(&bodies)
+                .map_err(|e| Error::InvalidArguments(format!("invalid --bodies: {}", e)))?;
+            let bodies = horizons::fetch(&majors, &epoch, units)
+                .context("unable to fetch from JPL Horizons")?;
+            let g = units.gravitational_constant();
+            write_bodies(&out, 0., &bodies, g, None, compress, false)?;
+            println!("Wrote {} bodies to {}", bodies.len(), out.display());
+        }
+        Command::Generate {
+            model,
+            n,
+            seed,
+            potential,
+            out,
+            units,
+            g,
+            compress,
+        } => {
+            let generator = Generator::parse(&model, n)
+                .map_err(|e| Error::InvalidArguments(format!("invalid --model: {}", e)))?;
+            let bodies = generator.generate(&potential, &mut Rng::new(seed));
+            let g = g.or(units.map(Units::gravitational_constant));
+            write_bodies(&out, 0., &bodies, g.unwrap_or(1.), None, compress, false)?;
+            println!("Wrote {} bodies to {}", bodies.len(), out.display());
+        }
+        #[cfg(feature = "plot")]
+        Command::Plot { input, axes, out } => {
+            let frames =
+                trajectory::read(open(&input)?).context("unable to parse the trajectory")?;
+            plot::render(&out, &frames, axes).context("unable to render the plot")?;
+            println!("Plotted {} outputs to {}", frames.len(), out.display());
+        }
+        #[cfg(feature = "plot")]
+        Command::Render {
+            input,
+            out,
+            fps,
+            axes,
+            zoom,
+            trail,
+            color,
+        } => {
+            let frames =
+                trajectory::read(open(&input)?).context("unable to parse the trajectory")?;
+            let animation = Animation {
+                axes,
+                fps,
+                zoom,
+                trail,
+                coloring: color,
+            };
+            plot::animate(&out, &frames, &animation).context("unable to render the animation")?;
+            println!("Rendered {} frames to {}", frames.len(), out.display());
+        }
+        #[cfg(feature = "plot")]
+        Command::Report { run, out } => {
+            let out = out.unwrap_or_else(|| run.join("report.html"));
+            let context = format!("unable to report on {}", run.display());
+            let run = Run::read(&run).context(&context)?;
+            report::write(&out, &run).context(&context)?;
+            println!("Wrote a report on {} to {}", run.name, out.display());
+        }
+        Command::Selftest => {
+            let passed = selftest::run();
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+    }
+    Ok(())
+}
+
+/// Prints the conserved quantities and structure of the bodies at `time`, with the potential
+/// energy of Newtonian gravity, and the orbital elements of the pairs in `elements`.
+fn analyze(time: f64, bodies: Vec<Body>, elements: &[(usize, Option<usize>)], g: Option<f64>) {
+    let g_units = g.unwrap_or(1.);
+    let world = leapfrog::World::new(bodies, vec![Box::new(Gravity::default())]);
+    let bodies = world.bodies();
+    let active = bodies.iter().filter(|body| body.is_active()).count();
+    println!("Time: {}", time);
+    println!("Bodies: {} of {} active", active, bodies.len());
+    println!("Total mass: {:e}", world.total_mass() / g_units);
+    println!(
+        "Energy: kinetic = {:e}, potential = {:e}, total = {:e}",
+        world.kinetic_energy() / g_units,
+        world.potential_energy() / g_units,
+        world.total_energy() / g_units
+    );
+    println!("Virial ratio: {}", world.virial_ratio());
+    println!(
+        "Mean speed: {}, velocity dispersion: {}",
+        world.mean_speed(),
+        world.velocity_dispersion()
+    );
+    println!("Momentum: [{:e}]", world.total_momentum() / g_units);
+    println!(
+        "Angular momentum: [{:e}]",
+        world.angular_momentum() / g_units
+    );
+    println!("Centre of mass: [{:e}]", world.center_of_mass());
+    let center = analysis::density_center(bodies);
+    println!("Density centre: [{:e}]", center);
+    let radii = analysis::lagrangian_radii(bodies, center, &[0.1, 0.5, 0.9]);
+    println!(
+        "Lagrangian radii: 10% = {:e}, 50% = {:e}, 90% = {:e}",
+        radii[0], radii[1], radii[2]
+    );
+    for &(i, j) in elements {
+        let about = j.map_or("the barycentre of the others".to_string(), |j| {
+            j.to_string()
+        });
+        match OrbitalElements::of(bodies, i, j) {
+            Some(elements) => println!("Orbital elements of {} about {}: {}", i, about, elements),
+            None => eprintln!("No orbital elements of {} about {}", i, about),
+        }
+    }
+}
+
+/// Checks that a run can be deterministic, as for `--deterministic`.
+fn deterministic(args: &RunArgs, environment: &FpEnvironment) -> Result<(), Error> {
+    if !environment.is_strict() {
+        return Err(Error::InvalidArguments(
+            "--deterministic requires strict IEEE 754 double precision arithmetic, which this \
+             machine doesn't have (see --fp-report)"
+                .to_string(),
+        ));
+    }
+    if let Some(CollisionMode::Merge | CollisionMode::MergeAngular) = args.collision {
+        return Err(Error::InvalidArguments(
+            "--deterministic can't be used with merging collisions, whose radii need the \
+             platform's maths library"
+                .to_string(),
+        ));
+    }
+    let g = args.g.or(args.units.map(Units::gravitational_constant));
+    if let Some(force) = forces(args, g).iter().find(|f| !f.is_deterministic()) {
+        return Err(Error::InvalidArguments(format!(
+            "--deterministic can't be used with {:?}, which needs the platform's maths library",
+            force
+        )));
+    }
+    Ok(())
+}
+
+/// Format of the final report, which is JSON rather than text with `--stream` so that stdout stays
+/// newline-delimited JSON.
+fn report_format(args: &RunArgs) -> Format {
+    match args.format {
+        Format::Text if args.stream => Format::Json,
+        format => format,
+    }
+}
+
+/// Runs a simulation from initial conditions or a checkpoint, or analyses the sensitivity of its
+/// final state with perturbations of the relative `sensitivity` step if one is given.
+fn simulate(args: &RunArgs, sensitivity: Option<f64>) -> Result<(), Error> {
+    let environment = FpEnvironment::detect();
+    if args.fp_report {
+        environment.print();
+    }
+    check_speed_of_light(args)?;
+    if args.deterministic {
+        deterministic(args, &environment)?;
+    } else if !environment.is_strict() {
+        eprintln!(
+            "Warning: floating-point arithmetic is not strict IEEE 754 double precision, so \
+             results may not reproduce on other machines (see --fp-report)"
+        );
+    }
+    if args.ewald && simulation_box(args).and_then(|b| b.periodic()).is_none() {
+        return Err(Error::InvalidArguments(
+            "--ewald requires a periodic box".to_string(),
+        ));
+    }
+    if args.radiation_source.is_some() && args.radiation_beta == 0. {
+        return Err(Error::InvalidArguments(
+            "--radiation-source requires a nonzero --radiation-beta".to_string(),
+        ));
+    }
+    if args.report.is_some() && report_format(args) == Format::Text {
+        return Err(Error::InvalidArguments(
+            "--report requires --format json or vtk".to_string(),
+        ));
+    }
+    let g = args.g.or(args.units.map(Units::gravitational_constant));
+    let (bodies, g, checkpoint) = match &args.resume {
+        Some(path) => {
+            let mut checkpoint = read_checkpoint(path, args)?;
+            let bodies = std::mem::take(&mut checkpoint.bodies);
+            (bodies, g.or(checkpoint.g), Some(checkpoint))
+        }
+        None => {
+            let (bodies, g) = initial_bodies(args, g)?;
+            (bodies, g, None)
+        }
+    };
+    check_body_ids(args, &bodies)?;
     match args.sim {
-        SimType::ForwardEuler => {
-            let bodies: Vec<forward_euler::Body> =
-                read_csv(file).expect("Error parsing the specified file");
-            let mut world = forward_euler::World::new(bodies);
-            while world.time() < args.dur {
-                world.tick(args.tick)
+        SimType::ForwardEuler => start(
+            forward_euler::World::new,
+            bodies,
+            args,
+            g,
+            checkpoint,
+            sensitivity,
+        ),
+        SimType::SymplecticEuler => start(
+            symplectic_euler::World::new,
+            bodies,
+            args,
+            g,
+            checkpoint,
+            sensitivity,
+        ),
+        SimType::Leapfrog => start(
+            leapfrog::World::new,
+            bodies,
+            args,
+            g,
+            checkpoint,
+            sensitivity,
+        ),
+        SimType::Respa => {
+            let substeps = args.substeps.get();
+            let new_world = |bodies, forces| respa::World::new(bodies, forces, substeps);
+            start(new_world, bodies, args, g, checkpoint, sensitivity)
+        }
+    }
+}
+
+/// Bodies of the initial conditions, from a preset or file with any satellites from TLEs added,
+/// softening lengths set and ids of their indices, and the gravitational constant, which may be
+/// set by the file.
+fn initial_bodies(args: &RunArgs, g: Option<f64>) -> Result<(Vec<Body>, Option<f64>), Error> {
+    let (_, mut bodies, g) = match args.preset {
+        Some(preset) => (
+            0.,
+            preset.bodies(),
+            g.or(Some(preset.units().gravitational_constant())),
+        ),
+        None => {
+            let path = args.file.as_ref().unwrap();
+            let (time, bodies, g) = read_bodies(path, g)?;
+            if let Some((body, value)) = (bodies.iter().enumerate())
+                .find_map(|(i, body)| check::non_finite(body).map(|value| (i, value)))
+            {
+                let path = path.into();
+                return Err(Error::NonFinite { path, body, value });
             }
-            if let Some(rest_frame) = args.rest_frame {
-                world.into_rest_frame(rest_frame);
+            (time, bodies, g)
+        }
+    };
+    if let Some(path) = &args.tle {
+        let invalid = |e: String| Error::InvalidArguments(format!("invalid --tle: {}", e));
+        let text = fs::read_to_string(path).map_err(|e| Error::reading(path, e))?;
+        let tles = tle::parse(&text).map_err(invalid)?;
+        let earth = body_index(&bodies, &args.tle_around).map_err(invalid)?;
+        let units = args.units.or(args.preset.map(Preset::units));
+        let day = 86400. / units.map_or(1., Units::time);
+        let satellites = tle::satellites(&tles, &bodies[earth], day).map_err(invalid)?;
+        bodies.extend(satellites);
+    }
+    if let Some(softening) = args.softening {
+        bodies
+            .iter_mut()
+            .for_each(|body| body.softening = softening);
+    }
+    if let Some(neighbours) = args.adaptive_softening {
+        softening::adapt(&mut bodies, neighbours.get(), args.softening_factor);
+    }
+    body::assign_ids(&mut bodies);
+    Ok((bodies, g))
+}
+
+/// Reads a checkpoint to resume from, exiting with an error if it was written by a run with a
+/// different integrator or tick.
+fn read_checkpoint(path: &Path, args: &RunArgs) -> Result<Checkpoint, Error> {
+    let file = File::open(path).map_err(|e| Error::reading(path, e))?;
+    let context = format!("unable to read checkpoint {}", path.display());
+    let mut checkpoint: Checkpoint = if is_json(path) {
+        serde_json::from_reader(BufReader::new(file)).context(&context)?
+    } else {
+        Checkpoint::read(BufReader::new(file)).context(&context)?
+    };
+    let sim = args.sim.to_possible_value().unwrap();
+    if checkpoint.integrator != sim.get_name() || checkpoint.tick != args.tick {
+        return Err(Error::InvalidArguments(format!(
+            "checkpoint {} was written with `--sim {} --tick {}`",
+            path.display(),
+            checkpoint.integrator,
+            checkpoint.tick
+        )));
+    }
+    // JSON checkpoints from before the last tick was saved, whose ticks were all the same
+    if checkpoint.world.started && checkpoint.world.tick == 0. {
+        checkpoint.world.tick = checkpoint.tick;
+    }
+    Ok(checkpoint)
+}
+
+/// Writes a checkpoint to a temporary file which then replaces the file at `path`, so that a run
+/// stopped while writing, or a crash, leaves the previous checkpoint intact.
+fn write_checkpoint(path: &Path, checkpoint: &Checkpoint) -> Result<(), Error> {
+    let context = format!("unable to write checkpoint {}", path.display());
+    // The contents must be on disk before the rename is, or a crash could leave the checkpoint
+    // empty or truncated, whether or not other outputs are synced
+    let mut file = AtomicFile::create(path, true).context(&context)?;
+    if is_json(path) {
+        serde_json::to_writer(&mut file, checkpoint).context(&context)?;
+    } else {
+        checkpoint.write(&mut file).context(&context)?;
+    }
+    file.finish().context(&context)
+}
+
+fn start<S: Simulation>(
+    new_world: impl Fn(Vec<Body>, Vec<Box<dyn Force>>) -> S + Sync,
+    bodies: Vec<Body>,
+    args: &RunArgs,
+    g: Option<f64>,
+    checkpoint: Option<Checkpoint>,
+    sensitivity: Option<f64>,
+) -> Result<(), Error> {
+    // Every world gets fresh forces, so that stochastic forces are reseeded
+    let world = |bodies| {
+        let mut world = new_world(bodies, forces(args, g));
+        if let Some(mode) = args.collision {
+            world.set_collision_mode(mode, args.restitution);
+        }
+        if let Some(simulation_box) = simulation_box(args) {
+            world.set_simulation_box(simulation_box);
+        }
+        let events = world.events_mut();
+        events.set_escape_radius(args.escape_radius);
+        events.set_encounter_distance(args.encounter_distance);
+        for &kind in &args.stop_on {
+            events.stop_on(kind);
+        }
+        if let Some(hubble) = args.hubble {
+            world.set_cosmology(Cosmology::new(
+                hubble,
+                args.omega_m,
+                args.omega_lambda,
+                args.scale_factor,
+            ));
+        }
+        world
+    };
+    match (sensitivity, args.ensemble) {
+        (Some(step), _) => {
+            sensitivity::analyze(world, &bodies, args.dur, args.tick, step);
+            Ok(())
+        }
+        (None, Some(members)) => {
+            let ensemble = Ensemble {
+                members: members.get(),
+                position: args.perturb_pos.unwrap_or(0.),
+                velocity: args.perturb_vel.unwrap_or(0.),
+                every: args.every.unwrap_or(args.dur / 20.),
+                threads: args.threads.get(),
+            };
+            let mut rng = Rng::new(args.seed);
+            ensemble.run(world, &bodies, &mut rng, args.dur, args.tick);
+            Ok(())
+        }
+        (None, None) => {
+            let (world, state) = match checkpoint {
+                Some(checkpoint) => {
+                    // Restoring rather than building the world keeps the ids of the bodies
+                    let mut world = world(Vec::new());
+                    let saved = SavedWorld {
+                        state: checkpoint.world,
+                        bodies,
+                    };
+                    saved.restore(&mut world);
+                    (world, Some(checkpoint.run))
+                }
+                None => (world(bodies), None),
+            };
+            run(world, args, g, state)
+        }
+    }
+}
+
+/// Runs every integrator on the bodies and prints how well each conserved energy and momentum, from
+/// velocities synchronised with the positions, how long it took and the final positions of the
+/// bodies.
+fn compare(bodies: &[Body], duration: f64, tick: f64, substeps: usize, g: Option<f64>) {
+    let g_units = g.unwrap_or(1.);
+    let initial = integrate(&SimType::Leapfrog, bodies.to_vec(), 0, tick, substeps);
+    let (initial_energy, initial_momentum) = (initial.total_energy(), initial.total_momentum());
+    let ticks = (duration / tick).ceil() as u64;
+    let runs: Vec<_> = (SimType::value_variants().iter())
+        .map(|sim| {
+            let started = Instant::now();
+            let world = integrate(sim, bodies.to_vec(), ticks, tick, substeps);
+            let name = sim.to_possible_value().unwrap().get_name().to_string();
+            (name, world, started.elapsed())
+        })
+        .collect();
+
+    println!(
+        "{:<18} {:>14} {:>14} {:>12}",
+        "integrator", "energy drift", "momentum drift", "wall time"
+    );
+    for (name, world, elapsed) in &runs {
+        println!(
+            "{:<18} {:>14.6e} {:>14.6e} {:>10.3} s",
+            name,
+            (world.total_energy() - initial_energy) / initial_energy.abs(),
+            (world.total_momentum() - initial_momentum).length() / g_units,
+            elapsed.as_secs_f64()
+        );
+    }
+    println!();
+    println!("Final positions at t = {}", runs[0].1.time());
+    println!(
+        "{:>6} {:<18} {:>14} {:>14} {:>14}",
+        "body", "integrator", "x", "y", "z"
+    );
+    for i in 0..bodies.len() {
+        for (name, world, _) in &runs {
+            let position = world.bodies()[i].position;
+            println!(
+                "{:>6} {:<18} {:>14.6e} {:>14.6e} {:>14.6e}",
+                i, name, position[0], position[1], position[2]
+            );
+        }
+    }
+}
+
+/// Integrates the bodies with Newtonian gravity alone using `sim` for the given number of ticks.
+fn integrate(
+    sim: &SimType,
+    bodies: Vec<Body>,
+    ticks: u64,
+    tick: f64,
+    substeps: usize,
+) -> Box<dyn Simulation> {
+    let forces: Vec<Box<dyn Force>> = vec![Box::new(Gravity::default())];
+    let mut world: Box<dyn Simulation> = match sim {
+        SimType::ForwardEuler => Box::new(forward_euler::World::new(bodies, forces)),
+        SimType::SymplecticEuler => Box::new(symplectic_euler::World::new(bodies, forces)),
+        SimType::Leapfrog => Box::new(leapfrog::World::new(bodies, forces)),
+        SimType::Respa => Box::new(respa::World::new(bodies, forces, substeps)),
+    };
+    for _ in 0..ticks {
+        world.tick(tick);
+    }
+    world
+}
+
+#[derive(Serialize)]
+struct ConvergenceRow<'a> {
+    integrator: &'a str,
+    tick: f64,
+    error: f64,
+    /// Empirical order of convergence between the previous tick and this one.
+    order: Option<f64>,
+}
+
+/// Runs every integrator on the bodies with `levels` successively halved ticks, and writes the
+/// error of the final positions against a reference solution and the empirical order of
+/// convergence at each tick as CSV.
+///
+/// The reference solution is integrated with leapfrog at an eighth of the smallest respa substep.
+/// Errors are the RMS distance of the bodies from their reference positions, relative to the RMS
+/// initial distance of the bodies.
+fn converge(
+    bodies: &[Body],
+    duration: f64,
+    tick: f64,
+    levels: usize,
+    substeps: usize,
+    writer: impl Write,
+) -> Result<Vec<(String, Option<f64>)>, csv::Error> {
+    let ticks = (duration / tick).ceil() as u64;
+    let tick = duration / ticks as f64;
+    let refinement = (1 << (levels - 1)) * substeps as u64 * 8;
+    let reference = integrate(
+        &SimType::Leapfrog,
+        bodies.to_vec(),
+        ticks * refinement,
+        tick / refinement as f64,
+        substeps,
+    );
+    let rms = |f: &dyn Fn(usize) -> f64| {
+        ((0..bodies.len()).map(|i| f(i).powi(2)).sum::<f64>() / bodies.len() as f64).sqrt()
+    };
+    let scale = rms(&|i| bodies[i].position.length());
+
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut orders = Vec::new();
+    for sim in SimType::value_variants() {
+        let name = sim.to_possible_value().unwrap().get_name().to_string();
+        let mut previous: Option<f64> = None;
+        for level in 0..levels {
+            let refinement = 1 << level;
+            let world = integrate(
+                sim,
+                bodies.to_vec(),
+                ticks * refinement,
+                tick / refinement as f64,
+                substeps,
+            );
+            let error =
+                rms(&|i| (world.bodies()[i].position - reference.bodies()[i].position).length())
+                    / scale;
+            let order = previous.map(|previous| (previous / error).log2());
+            writer.serialize(ConvergenceRow {
+                integrator: &name,
+                tick: tick / refinement as f64,
+                error,
+                order,
+            })?;
+            previous = Some(error);
+            if level == levels - 1 {
+                orders.push((name.clone(), order));
             }
-            println!("Simulation time: {}", world.time());
-            world
-                .bodies()
-                .iter()
-                .for_each(|body| println!("{}, speed = {}", body, body.velocity.length()))
-        }
-        SimType::SymplecticEuler => {
-            let bodies: Vec<symplectic_euler::Body> =
-                read_csv(file).expect("Error parsing the specified file");
-            let mut world = symplectic_euler::World::new(bodies);
-            while world.time() < args.dur {
-                world.tick(args.tick)
+        }
+    }
+    writer.flush()?;
+    Ok(orders)
+}
+
+/// Updates the closest approach, if it is being tracked, warning about pairs which have just come
+/// within the threshold separation.
+fn track_closest(
+    closest: &mut Option<ClosestApproach>,
+    world: &impl Simulation,
+    periodic: Option<PeriodicBox>,
+) {
+    let Some(closest) = closest else {
+        return;
+    };
+    for (i, j, separation) in closest.update(world.time(), world.bodies(), periodic) {
+        eprintln!(
+            "Warning: bodies {} and {} came within {:e} of each other at t = {}",
+            i,
+            j,
+            separation,
+            world.time()
+        );
+    }
+}
+
+/// Prints the bodies as a line of `--stream` output if one is due.
+fn stream(
+    schedule: &mut Option<OutputSchedule>,
+    world: &impl Simulation,
+    args: &RunArgs,
+    g: Option<f64>,
+) -> Result<(), Error> {
+    let Some(schedule) = schedule else {
+        return Ok(());
+    };
+    if schedule.due(world.time(), args.tick).is_none() {
+        return Ok(());
+    }
+    let line = StreamLine {
+        time: world.time(),
+        bodies: (world.bodies().iter())
+            .map(|body| ReportBody::new(body, g))
+            .collect(),
+    };
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer(&mut stdout, &line).context("unable to write the stream")?;
+    writeln!(stdout).context("unable to write the stream")
+}
+
+/// Runs the simulation until `--dur`, continuing from the `resumed` state of the run loop if it was
+/// restored from a checkpoint.
+fn run(
+    mut world: impl Simulation,
+    args: &RunArgs,
+    g: Option<f64>,
+    resumed: Option<RunState>,
+) -> Result<(), Error> {
+    // Kept as an id, so that it still refers to the same body if others are added or removed
+    let rest_frame = (args.rest_frame.as_ref())
+        .map(|s| body_index(world.bodies(), s).map_err(Error::RestFrame))
+        .transpose()?
+        .map(|i| world.bodies()[i].id);
+    let mut recorder = args
+        .event_window
+        .map(|window| EventRecorder::new(window, args.event_dir.clone(), args.fsync));
+    // Energies and momenta are in units of G times their value unless G is known
+    let g_units = g.unwrap_or(1.);
+    let RunState {
+        ticks,
+        start,
+        initial_energy,
+        // Energy changes due to adapting softening lengths and removing escapers rather than motion
+        mut energy_correction,
+        initial_momentum,
+        initial_center,
+        initial_angular_momentum,
+        escaped,
+    } = resumed.unwrap_or_else(|| RunState {
+        ticks: 0,
+        start: world.time(),
+        initial_energy: world.total_energy(),
+        energy_correction: 0.,
+        initial_momentum: world.total_momentum(),
+        initial_center: world.center_of_mass(),
+        initial_angular_momentum: world.angular_momentum(),
+        escaped: Vec::new(),
+    });
+    let mut ticks = ticks as usize;
+    world.events_mut().set_escaped(escaped);
+    let mut diagnostics = (args.diag_out.as_ref())
+        .map(|path| DiagnosticsLog::create(path, g_units, args.compress, args.fsync))
+        .transpose()
+        .context("unable to create the diagnostics file")?;
+    if let Some(diagnostics) = &mut diagnostics {
+        (diagnostics.record(&world, 0.)).context("unable to write diagnostics")?;
+    }
+    let mut closest = (args.closest_approach || args.warn_separation.is_some())
+        .then(|| ClosestApproach::new(args.warn_separation));
+    let periodic = world.simulation_box().and_then(|b| b.periodic());
+    let mut trajectory = (args.out.as_ref())
+        .map(|path| TrajectoryWriter::create(path, args.every, args.compress, args.fsync))
+        .transpose()
+        .context("unable to create the trajectory file")?;
+    if let Some(trajectory) = &mut trajectory {
+        trajectory
+            .record(world.time(), args.tick, world.bodies())
+            .context("unable to write trajectory")?;
+    }
+    let mut schedule = args.stream.then(|| OutputSchedule::new(args.every));
+    let mut checkpoints = args.checkpoint_every.map(|every| {
+        let mut schedule = OutputSchedule::new(Some(every));
+        // The state at the start is already in the initial conditions or checkpoint
+        schedule.due(world.time(), args.tick);
+        schedule
+    });
+    stream(&mut schedule, &world, args, g)?;
+    track_closest(&mut closest, &world, periodic);
+    let mut live = (args.tui.then(|| LiveView::new(initial_energy)))
+        .transpose()
+        .context("unable to start the live view")?;
+    let mut progress = (!args.quiet && !args.tui).then(|| {
+        let ticks = ((args.dur - world.time()) / args.tick).ceil().max(0.);
+        ProgressDisplay::new(ticks as u64, initial_energy)
+    });
+    let cancel = CancellationToken::new();
+    (cancel.cancel_on_interrupt()).context("unable to handle interrupts")?;
+    let started = Instant::now();
+    let mut timed_out = false;
+    while world.time() < args.dur {
+        if ticks < args.explain {
+            explain::explain_tick(&mut world, args.tick);
+        } else {
+            world.tick(args.tick);
+        }
+        ticks += 1;
+        if let Some(neighbours) = args.adaptive_softening {
+            let before = world.potential_energy();
+            softening::adapt(world.bodies_mut(), neighbours.get(), args.softening_factor);
+            energy_correction += world.potential_energy() - before;
+        }
+        let events = world.take_events();
+        for event in &events {
+            if let (Event::Escape(escape), true) = (event, args.remove_escapers) {
+                let Some(i) = world.index_of(escape.body) else {
+                    continue;
+                };
+                let before = world.total_energy();
+                world.bodies_mut()[i].state = State::Escaped { time: escape.time };
+                energy_correction += world.total_energy() - before;
             }
-            if let Some(rest_frame) = args.rest_frame {
-                world.into_rest_frame(rest_frame);
+        }
+        if args
+            .log_angular_momentum
+            .is_some_and(|every| ticks.is_multiple_of(every))
+        {
+            println!(
+                "Angular momentum at t = {}: [{:e}]",
+                world.time(),
+                world.angular_momentum() / g_units
+            );
+        }
+        if args
+            .log_virial
+            .is_some_and(|every| ticks.is_multiple_of(every))
+        {
+            println!(
+                "Virial ratio at t = {}: {}, mean speed = {}, velocity dispersion = {}",
+                world.time(),
+                world.virial_ratio(),
+                world.mean_speed(),
+                world.velocity_dispersion()
+            );
+        }
+        if let Some(diagnostics) = &mut diagnostics {
+            if ticks.is_multiple_of(args.diag_every.get()) {
+                diagnostics
+                    .record(&world, energy_correction)
+                    .context("unable to write diagnostics")?;
             }
-            println!("Simulation time: {}", world.time());
-            world
-                .bodies()
-                .iter()
-                .for_each(|body| println!("{}, speed = {}", body, body.velocity.length()))
-        }
-        SimType::Leapfrog => {
-            let bodies: Vec<leapfrog::Body> =
-                read_csv(file).expect("Error parsing the specified file");
-            let mut world = leapfrog::World::new(bodies);
-            world.half_tick_velocity(args.tick);
-            while world.time() < args.dur {
-                world.tick(args.tick)
+        }
+        if let Some(trajectory) = &mut trajectory {
+            trajectory
+                .record(world.time(), args.tick, world.bodies())
+                .context("unable to write trajectory")?;
+        }
+        stream(&mut schedule, &world, args, g)?;
+        track_closest(&mut closest, &world, periodic);
+        match &mut recorder {
+            Some(recorder) => recorder
+                .record(world.time(), world.bodies(), &events)
+                .context("unable to write event window")?,
+            None => events.iter().for_each(|event| println!("{}", event)),
+        }
+        if let Some(progress) = &mut progress {
+            progress.update(&world, energy_correction);
+        }
+        if let Some(live) = &mut live {
+            if !live
+                .update(&world, energy_correction)
+                .context("unable to draw the live view")?
+            {
+                break;
             }
-            if let Some(rest_frame) = args.rest_frame {
-                world.into_rest_frame(rest_frame);
+        }
+        // Stop after finishing the tick, checkpointing if checkpoints are being written
+        let interrupted = cancel.is_cancelled();
+        timed_out = args
+            .max_walltime
+            .is_some_and(|limit| started.elapsed() >= limit);
+        if let Some(checkpoints) = &mut checkpoints {
+            let due = checkpoints.due(world.time(), args.tick).is_some();
+            if due || interrupted || timed_out {
+                let checkpoint = Checkpoint {
+                    integrator: args.sim.to_possible_value().unwrap().get_name().to_string(),
+                    tick: args.tick,
+                    g,
+                    world: world.world_state(),
+                    run: RunState {
+                        ticks: ticks as u64,
+                        start,
+                        initial_energy,
+                        energy_correction,
+                        initial_momentum,
+                        initial_center,
+                        initial_angular_momentum,
+                        escaped: world.events().escaped(),
+                    },
+                    bodies: world.bodies().clone(),
+                };
+                write_checkpoint(args.checkpoint_file.as_ref().unwrap(), &checkpoint)?;
             }
+        }
+        if interrupted {
+            eprintln!("Interrupted at t = {}, stopping", world.time());
+            break;
+        }
+        if timed_out {
+            eprintln!(
+                "Wall-clock time limit reached at t = {}, stopping",
+                world.time()
+            );
+            break;
+        }
+        if world.events().stopped() {
+            eprintln!(
+                "Stopping at t = {} after an event given by --stop-on",
+                world.time()
+            );
+            break;
+        }
+    }
+    // Restore the terminal before printing the report
+    drop(live);
+    if let Some(progress) = &progress {
+        progress.finish();
+    }
+    if let Some(trajectory) = trajectory {
+        trajectory.finish().context("unable to write trajectory")?;
+    }
+    if let Some(diagnostics) = diagnostics {
+        diagnostics
+            .finish()
+            .context("unable to write diagnostics")?;
+    }
+    if let Some(recorder) = recorder {
+        recorder
+            .finish()
+            .context("unable to write event recordings")?;
+    }
+    let final_energy = world.total_energy() - energy_correction;
+    let final_momentum = world.total_momentum();
+    let final_angular_momentum = world.angular_momentum();
+    // Drift of the centre of mass from uniform motion with the initial momentum
+    let center_drift = world.center_of_mass()
+        - (initial_center + (world.time() - start) / world.total_mass() * initial_momentum);
+    let hash = world.state_hash();
+    if let Some(path) = &args.save_state {
+        let sim = args.sim.to_possible_value().unwrap();
+        let integrator = Some((sim.get_name(), args.tick));
+        write_bodies(
+            path,
+            world.time(),
+            &world.synchronised_bodies(),
+            g_units,
+            integrator,
+            args.compress,
+            args.fsync,
+        )?;
+    }
+    if let Some(i) = rest_frame.and_then(|id| world.index_of(id)) {
+        world.transform_to_rest_frame(i);
+    }
+    match report_format(args) {
+        Format::Text => {
             println!("Simulation time: {}", world.time());
-            world
-                .bodies()
-                .iter()
-                .for_each(|body| println!("{}, speed = {}", body, body.velocity.length()))
+            if let Some(cosmology) = world.cosmology() {
+                println!("Scale factor: {}", cosmology.scale_factor());
+            }
+            println!(
+                "Energy: initial = {:e}, final = {:e}, relative drift = {:e}",
+                initial_energy / g_units,
+                final_energy / g_units,
+                (final_energy - initial_energy) / initial_energy.abs()
+            );
+            println!(
+                "Momentum: initial = [{:e}], final = [{:e}]",
+                initial_momentum / g_units,
+                final_momentum / g_units
+            );
+            println!("Centre of mass drift: [{:e}]", center_drift);
+            println!(
+                "Angular momentum: initial = [{:e}], final = [{:e}], change = [{:e}]",
+                initial_angular_momentum / g_units,
+                final_angular_momentum / g_units,
+                (final_angular_momentum - initial_angular_momentum) / g_units
+            );
+            if let Some(closest) = closest {
+                println!("Closest approach: {}", closest);
+            }
+            for &(i, j) in &args.elements {
+                let about = j.map_or("the barycentre of the others".to_string(), |j| {
+                    j.to_string()
+                });
+                match OrbitalElements::of(world.bodies(), i, j) {
+                    Some(elements) => {
+                        println!("Orbital elements of {} about {}: {}", i, about, elements)
+                    }
+                    None => eprintln!("No orbital elements of {} about {}", i, about),
+                }
+            }
+            if args.print_bodies || world.bodies().len() <= args.summary_threshold {
+                world.bodies().iter().for_each(|body| print_body(body, g));
+            } else {
+                print_summary(world.bodies(), g);
+            }
+            println!("State hash: {:016x}", hash);
+        }
+        Format::Vtk => match &args.report {
+            Some(path) => {
+                let mut file = compress::create(path, args.compress, args.fsync)
+                    .context("unable to create the report file")?;
+                vtk::write(&mut file, world.time(), world.bodies())
+                    .context("unable to write the report")?;
+                file.finish().context("unable to write the report")?;
+            }
+            None => vtk::write(std::io::stdout().lock(), world.time(), world.bodies())
+                .context("unable to write the report")?,
+        },
+        Format::Json => {
+            let report = Report {
+                time: world.time(),
+                scale_factor: world.cosmology().map(|c| c.scale_factor()),
+                parameters: ReportParameters {
+                    sim: &args.sim,
+                    tick: args.tick,
+                    duration: args.dur,
+                    g,
+                },
+                energy: ReportChange {
+                    initial: initial_energy / g_units,
+                    end: final_energy / g_units,
+                },
+                momentum: ReportChange {
+                    initial: initial_momentum / g_units,
+                    end: final_momentum / g_units,
+                },
+                center_of_mass_drift: center_drift,
+                angular_momentum: ReportChange {
+                    initial: initial_angular_momentum / g_units,
+                    end: final_angular_momentum / g_units,
+                },
+                state_hash: format!("{:016x}", hash),
+                bodies: (world.bodies().iter())
+                    .map(|body| ReportBody::new(body, g))
+                    .collect(),
+            };
+            match &args.report {
+                Some(path) => {
+                    let mut file = compress::create(path, args.compress, args.fsync)
+                        .context("unable to create the report file")?;
+                    serde_json::to_writer_pretty(&mut file, &report)
+                        .context("unable to write the report")?;
+                    file.finish().context("unable to write the report")?;
+                }
+                None if args.stream => println!(
+                    "{}",
+                    serde_json::to_string(&report).context("unable to serialise the report")?
+                ),
+                None => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&report)
+                        .context("unable to serialise the report")?
+                ),
+            }
         }
     }
+    if let Some(expected) = args.verify_hash.filter(|&expected| expected != hash) {
+        return Err(Error::HashMismatch {
+            expected,
+            actual: hash,
+        });
+    }
+    if cancel.is_cancelled() {
+        std::process::exit(130);
+    }
+    if timed_out {
+        std::process::exit(124);
+    }
+    Ok(())
 }
@@ -0,0 +1,53 @@
+use std::fmt;
+
+use crate::simulation::Simulation;
+
+/// Receives the state of a world after its ticks, for custom logging and live visualisation
+/// without changing the integrators.
+pub trait Observer {
+    fn observe(&mut self, world: &dyn Simulation);
+}
+
+impl<F: FnMut(&dyn Simulation)> Observer for F {
+    fn observe(&mut self, world: &dyn Simulation) {
+        self(world)
+    }
+}
+
+/// Observers registered with a world, each called after every so many ticks.
+#[derive(Default)]
+pub struct Observers {
+    observers: Vec<(usize, Box<dyn Observer>)>,
+    /// Number of ticks since the world was created.
+    ticks: usize,
+}
+
+impl Observers {
+    /// Registers `observer` to be called after every `every` ticks. Panics if `every` is zero.
+    pub fn add(&mut self, every: usize, observer: impl Observer + 'static) {
+        assert!(
+            every > 0,
+            "observers must be called every one or more ticks"
+        );
+        self.observers.push((every, Box::new(observer)));
+    }
+
+    /// Counts a tick of `world` and calls the observers which are due.
+    pub fn notify(&mut self, world: &dyn Simulation) {
+        self.ticks += 1;
+        for (every, observer) in &mut self.observers {
+            if self.ticks.is_multiple_of(*every) {
+                observer.observe(world);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observers")
+            .field("observers", &self.observers.len())
+            .field("ticks", &self.ticks)
+            .finish()
+    }
+}
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+/// Named numeric parameters given on the command line as `key=value,key=value`.
+pub struct Parameters<'a> {
+    values: HashMap<&'a str, f64>,
+}
+
+impl<'a> Parameters<'a> {
+    pub fn parse(s: &'a str) -> Result<Self, String> {
+        let values = s
+            .split(',')
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                let (key, value) = p
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected key=value, found `{}`", p))?;
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("invalid value for `{}`: `{}`", key, value))?;
+                Ok((key, value))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { values })
+    }
+
+    /// Takes the value of `key`, falling back to `default` if it was not given.
+    pub fn take(&mut self, key: &str, default: Option<f64>) -> Result<f64, String> {
+        self.values
+            .remove(key)
+            .or(default)
+            .ok_or_else(|| format!("missing parameter `{}`", key))
+    }
+
+    /// Takes the value of `key` as an index, falling back to `default` if it was not given.
+    pub fn take_index(&mut self, key: &str, default: Option<usize>) -> Result<usize, String> {
+        let value = self.take(key, default.map(|i| i as f64))?;
+        if value >= 0. && value.fract() == 0. {
+            Ok(value as usize)
+        } else {
+            Err(format!("`{}` must be an index", key))
+        }
+    }
+
+    /// Checks that every parameter given was used.
+    pub fn finish(self) -> Result<(), String> {
+        match self.values.keys().next() {
+            Some(key) => Err(format!("unknown parameter `{}`", key)),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use plotters::{
+    coord::{types::RangedCoordf64, Shift},
+    prelude::*,
+    style::colors::colormaps::ViridisRGB,
+};
+
+use crate::{body::Body, trajectory::Frame, vec3::Point3};
+
+/// Plane onto which trajectories are projected.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Axes {
+    Xy,
+    Xz,
+    Yz,
+}
+
+impl Axes {
+    /// Indices and names of the horizontal and vertical coordinates.
+    fn coordinates(self) -> [(usize, &'static str); 2] {
+        match self {
+            Axes::Xy => [(0, "x"), (1, "y")],
+            Axes::Xz => [(0, "x"), (2, "z")],
+            Axes::Yz => [(1, "y"), (2, "z")],
+        }
+    }
+}
+
+/// Colouring of the bodies in an animation.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Coloring {
+    /// The colour of each body given in its initial conditions, or otherwise a distinct one
+    Body,
+    /// Colour map of Gm on a logarithmic scale
+    Mass,
+    /// Colour map of speed
+    Speed,
+}
+
+/// Options for rendering a trajectory as an animation.
+pub struct Animation {
+    pub axes: Axes,
+    pub fps: u32,
+    /// Magnification about the centre of the whole trajectory.
+    pub zoom: f64,
+    /// Number of previous outputs over which each body's path is drawn behind it, unless the body
+    /// has its own trail length.
+    pub trail: usize,
+    pub coloring: Coloring,
+}
+
+const SIZE: (u32, u32) = (800, 800);
+
+/// Size of line charts, which are wider than they are tall.
+const CHART_SIZE: (u32, u32) = (800, 400);
+
+/// Radius in pixels of the dots of bodies without their own size.
+const DOT_SIZE: u32 = 3;
+
+/// Renders the trajectories of bodies, projected onto `axes` with equal scales, to an SVG image if
+/// the path has a `.svg` extension or otherwise a PNG image. Each body's path is drawn in its own
+/// colour as for [`Coloring::Body`], with a dot of its size at its last position.
+pub fn render(path: &Path, frames: &[Frame], axes: Axes) -> Result<(), Box<dyn Error>> {
+    let view = View::new(frames, axes, 1.)?;
+    let trajectories = paths(frames);
+    if path.extension().is_some_and(|extension| extension == "svg") {
+        let root = SVGBackend::new(path, SIZE).into_drawing_area();
+        draw_paths(&root, &view, &trajectories)
+    } else {
+        let root = BitMapBackend::new(path, SIZE).into_drawing_area();
+        draw_paths(&root, &view, &trajectories)
+    }
+}
+
+/// Renders the trajectories of bodies as for [`render`] to an SVG document.
+pub fn render_svg(frames: &[Frame], axes: Axes) -> Result<String, Box<dyn Error>> {
+    let view = View::new(frames, axes, 1.)?;
+    let trajectories = paths(frames);
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, SIZE).into_drawing_area();
+        draw_paths(&root, &view, &trajectories)?;
+    }
+    Ok(svg)
+}
+
+/// Series of points drawn by [`line_chart`], with its name for the legend.
+pub type Series = (String, Vec<(f64, f64)>);
+
+/// Draws each of `series` as a line in its own colour, with a legend of their names, to an SVG
+/// document with a `caption` and axes labelled `x` and `y`. Points which aren't finite are left out.
+pub fn line_chart(
+    caption: &str,
+    x: &str,
+    y: &str,
+    series: &[Series],
+) -> Result<String, Box<dyn Error>> {
+    let finite = || {
+        (series.iter())
+            .flat_map(|(_, points)| points)
+            .filter(|(x, y)| x.is_finite() && y.is_finite())
+    };
+    let range = |values: &mut dyn Iterator<Item = f64>| {
+        let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        // Widen empty and flat ranges so that the axes can still be drawn
+        let margin = ((max - min) * 0.05).max(max.abs().max(1e-300) * 1e-6);
+        (min - margin)..(max + margin)
+    };
+    let x_range = range(&mut finite().map(|&(x, _)| x));
+    let y_range = range(&mut finite().map(|&(_, y)| y));
+    if x_range.start > x_range.end {
+        return Err(format!("{} has no points", caption).into());
+    }
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, CHART_SIZE).into_drawing_area();
+        root.fill(&WHITE)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(caption, ("sans-serif", 20))
+            .margin(20)
+            .x_label_area_size(40)
+            .y_label_area_size(80)
+            .build_cartesian_2d(x_range, y_range)?;
+        chart
+            .configure_mesh()
+            .x_desc(x)
+            .y_desc(y)
+            .x_label_formatter(&|x| format!("{:.3e}", x))
+            .y_label_formatter(&|y| format!("{:.3e}", y))
+            .draw()?;
+        for (k, (name, points)) in series.iter().enumerate() {
+            let color = Palette99::pick(k).to_rgba();
+            let points = (points.iter().copied()).filter(|(x, y)| x.is_finite() && y.is_finite());
+            chart
+                .draw_series(LineSeries::new(points, color.stroke_width(1)))?
+                .label(name)
+                .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color));
+        }
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+        root.present()?;
+    }
+    Ok(svg)
+}
+
+/// Renders a trajectory as an animation with one frame per output, encoded as MP4 with `ffmpeg`
+/// if the path has a `.mp4` extension or otherwise as a GIF.
+pub fn animate(path: &Path, frames: &[Frame], animation: &Animation) -> Result<(), Box<dyn Error>> {
+    let view = View::new(frames, animation.axes, animation.zoom)?;
+    let colors = Colors::new(frames, animation.coloring);
+    if path.extension().is_some_and(|extension| extension == "mp4") {
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args([
+                "-y",
+                "-loglevel",
+                "error",
+                "-f",
+                "rawvideo",
+                "-pixel_format",
+                "rgb24",
+            ])
+            .args(["-video_size", &format!("{}x{}", SIZE.0, SIZE.1)])
+            .args(["-framerate", &animation.fps.to_string(), "-i", "-"])
+            .args(["-pix_fmt", "yuv420p"])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("unable to run ffmpeg, which encodes MP4 files: {}", e))?;
+        let mut stdin = ffmpeg.stdin.take().ok_or("unable to write to ffmpeg")?;
+        let mut buffer = vec![0; 3 * (SIZE.0 * SIZE.1) as usize];
+        for k in 0..frames.len() {
+            {
+                let root = BitMapBackend::with_buffer(&mut buffer, SIZE).into_drawing_area();
+                draw_frame(&root, &view, frames, k, animation, &colors)?;
+                root.present()?;
+            }
+            stdin.write_all(&buffer)?;
+        }
+        drop(stdin);
+        if !ffmpeg.wait()?.success() {
+            return Err("ffmpeg failed to encode the animation".into());
+        }
+    } else {
+        let delay = 1000 / animation.fps.max(1);
+        let root = BitMapBackend::gif(path, SIZE, delay)?.into_drawing_area();
+        for k in 0..frames.len() {
+            draw_frame(&root, &view, frames, k, animation, &colors)?;
+            root.present()?;
+        }
+    }
+    Ok(())
+}
+
+/// Region of the projection plane which is drawn.
+struct View {
+    axes: [(usize, &'static str); 2],
+    center: (f64, f64),
+    /// Half the width and height, which are equal so that orbits keep their shape.
+    half: f64,
+}
+
+impl View {
+    /// View covering every position in `frames` with a margin, magnified by `zoom`.
+    fn new(frames: &[Frame], axes: Axes, zoom: f64) -> Result<Self, Box<dyn Error>> {
+        let axes = axes.coordinates();
+        let (mut min, mut max) = (
+            (f64::INFINITY, f64::INFINITY),
+            (f64::NEG_INFINITY, f64::NEG_INFINITY),
+        );
+        for (_, body) in frames.iter().flat_map(|frame| &frame.bodies) {
+            let (x, y) = (body.position[axes[0].0], body.position[axes[1].0]);
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        if min.0 > max.0 {
+            return Err("trajectory has no positions".into());
+        }
+        Ok(Self {
+            axes,
+            center: ((min.0 + max.0) / 2., (min.1 + max.1) / 2.),
+            half: 0.55 * (max.0 - min.0).max(max.1 - min.1).max(f64::MIN_POSITIVE) / zoom,
+        })
+    }
+
+    fn project(&self, position: Point3) -> (f64, f64) {
+        (position[self.axes[0].0], position[self.axes[1].0])
+    }
+
+    /// Fills `root` and draws labelled axes, with a `caption` if given.
+    fn chart<'a, DB: DrawingBackend>(
+        &self,
+        root: &'a DrawingArea<DB, Shift>,
+        caption: Option<String>,
+    ) -> Result<ChartContext<'a, DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>, Box<dyn Error>>
+    where
+        DB::ErrorType: 'static,
+    {
+        root.fill(&WHITE)?;
+        let mut builder = ChartBuilder::on(root);
+        if let Some(caption) = caption {
+            builder.caption(caption, ("sans-serif", 20));
+        }
+        let mut chart = builder
+            .margin(20)
+            .x_label_area_size(40)
+            .y_label_area_size(80)
+            .build_cartesian_2d(
+                self.center.0 - self.half..self.center.0 + self.half,
+                self.center.1 - self.half..self.center.1 + self.half,
+            )?;
+        chart
+            .configure_mesh()
+            .x_desc(self.axes[0].1)
+            .y_desc(self.axes[1].1)
+            .x_label_formatter(&|x| format!("{:.3e}", x))
+            .y_label_formatter(&|y| format!("{:.3e}", y))
+            .draw()?;
+        Ok(chart)
+    }
+}
+
+/// Colours of bodies, with the range of the colour map covering the whole trajectory.
+struct Colors {
+    coloring: Coloring,
+    min: f64,
+    max: f64,
+}
+
+impl Colors {
+    fn new(frames: &[Frame], coloring: Coloring) -> Self {
+        let mut colors = Self {
+            coloring,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        };
+        for (_, body) in frames.iter().flat_map(|frame| &frame.bodies) {
+            if let Some(value) = colors.value(body) {
+                colors.min = colors.min.min(value);
+                colors.max = colors.max.max(value);
+            }
+        }
+        colors
+    }
+
+    /// Value of the body on the colour map, which is None for bodies of zero mass coloured by mass.
+    fn value(&self, body: &Body) -> Option<f64> {
+        match self.coloring {
+            Coloring::Body => None,
+            Coloring::Mass => (body.mass > 0.).then(|| body.mass.log10()),
+            Coloring::Speed => Some(body.velocity.length()),
+        }
+    }
+
+    fn of(&self, i: usize, body: &Body) -> RGBColor {
+        if let Coloring::Body = self.coloring {
+            if let Some(color) = body.color.as_deref().and_then(parse_color) {
+                return color;
+            }
+            let (r, g, b) = Palette99::pick(i).rgb();
+            return RGBColor(r, g, b);
+        }
+        match self.value(body) {
+            Some(_) if self.max <= self.min => ViridisRGB.get_color(0.5),
+            Some(value) => ViridisRGB.get_color_normalized(value, self.min, self.max),
+            None => RGBColor(128, 128, 128),
+        }
+    }
+}
+
+fn dot_size(body: &Body) -> u32 {
+    body.size.unwrap_or(DOT_SIZE)
+}
+
+/// Colour given as `#rrggbb`, or None if it isn't in that form.
+fn parse_color(text: &str) -> Option<RGBColor> {
+    let hex = text.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |k: usize| u8::from_str_radix(hex.get(k..k + 2)?, 16).ok();
+    Some(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Colour, dot size and positions of each body in `frames`, by index.
+fn paths(frames: &[Frame]) -> BTreeMap<usize, (RGBColor, u32, Vec<Point3>)> {
+    let colors = Colors::new(frames, Coloring::Body);
+    let mut trajectories: BTreeMap<usize, (RGBColor, u32, Vec<Point3>)> = BTreeMap::new();
+    for (i, body) in frames.iter().flat_map(|frame| &frame.bodies) {
+        let (_, _, positions) = trajectories
+            .entry(*i)
+            .or_insert_with(|| (colors.of(*i, body), dot_size(body), Vec::new()));
+        positions.push(body.position);
+    }
+    trajectories
+}
+
+fn draw_paths<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    view: &View,
+    trajectories: &BTreeMap<usize, (RGBColor, u32, Vec<Point3>)>,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let mut chart = view.chart(root, None)?;
+    for (color, size, positions) in trajectories.values() {
+        let projected = positions.iter().map(|&p| view.project(p));
+        chart.draw_series(LineSeries::new(projected, color.stroke_width(1)))?;
+        if let Some(&last) = positions.last() {
+            chart.draw_series([Circle::new(view.project(last), *size, color.filled())])?;
+        }
+    }
+    root.present()?;
+    Ok(())
+}
+
+/// Draws the `k`th frame, with the trails of bodies which are still present over their own trail
+/// lengths or that of the animation.
+fn draw_frame<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    view: &View,
+    frames: &[Frame],
+    k: usize,
+    animation: &Animation,
+    colors: &Colors,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let frame = &frames[k];
+    let mut chart = view.chart(root, Some(format!("t = {:e}", frame.time)))?;
+    let trail_of = |body: &Body| body.trail.map_or(animation.trail, |trail| trail as usize);
+    let longest = frame.bodies.iter().map(|(_, body)| trail_of(body)).max();
+    if let Some(longest) = longest.filter(|&longest| longest > 0) {
+        let first = k.saturating_sub(longest);
+        // Positions of each body with the numbers of their frames
+        let mut trails: BTreeMap<usize, Vec<(usize, Point3)>> = BTreeMap::new();
+        for (l, earlier) in frames[first..=k].iter().enumerate() {
+            for (i, body) in &earlier.bodies {
+                trails
+                    .entry(*i)
+                    .or_default()
+                    .push((first + l, body.position));
+            }
+        }
+        for (i, body) in &frame.bodies {
+            let trail = (trails.remove(i).unwrap_or_default().into_iter())
+                .filter(|&(l, _)| l + trail_of(body) >= k)
+                .map(|(_, position)| view.project(position));
+            let color = colors.of(*i, body).mix(0.5);
+            chart.draw_series(LineSeries::new(trail, color.stroke_width(1)))?;
+        }
+    }
+    chart.draw_series(frame.bodies.iter().map(|(i, body)| {
+        let color = colors.of(*i, body).filled();
+        Circle::new(view.project(body.position), dot_size(body), color)
+    }))?;
+    Ok(())
+}
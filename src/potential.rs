@@ -0,0 +1,164 @@
+use std::str::FromStr;
+
+use crate::{
+    body::Body,
+    force::Force,
+    parameters::Parameters,
+    summation::kahan_sum,
+    vec3::{Point3, Vec3},
+};
+
+/// External analytic potentials in which bodies move, in addition to their mutual gravity. All
+/// masses are in Gm units, and all potentials except point masses are centred on the origin.
+#[derive(Clone, Debug)]
+pub enum Potential {
+    /// Point mass at an arbitrary position.
+    PointMass { mass: f64, position: Point3 },
+    /// Point mass at the origin.
+    Kepler { mass: f64 },
+    /// Isotropic harmonic oscillator, Φ = ω²r²/2.
+    Harmonic { omega: f64 },
+    /// Miyamoto-Nagai disk with scale length `a` and scale height `b`, symmetric about the z axis.
+    MiyamotoNagai { mass: f64, a: f64, b: f64 },
+    /// Navarro-Frenk-White halo with scale mass 4πρₛrₛ³ and scale radius `rs`.
+    Nfw { mass: f64, rs: f64 },
+    /// Logarithmic halo Φ = v₀²/2 ln(r꜀² + x² + y² + z²/q²).
+    Logarithmic { v0: f64, rc: f64, q: f64 },
+}
+
+impl Potential {
+    /// Potential Φ at `position`, zero at infinity except for the harmonic and logarithmic
+    /// potentials.
+    pub fn potential(&self, position: Point3) -> f64 {
+        match *self {
+            Self::PointMass { mass, position: p } => -mass / (position - p).length(),
+            Self::Kepler { mass } => -mass / position.length(),
+            Self::Harmonic { omega } => omega * omega * position.length_squared() / 2.,
+            Self::MiyamotoNagai { mass, a, b } => {
+                let zeta = (position.z() * position.z() + b * b).sqrt();
+                -mass
+                    / (position.x() * position.x()
+                        + position.y() * position.y()
+                        + (a + zeta).powi(2))
+                    .sqrt()
+            }
+            Self::Nfw { mass, rs } => {
+                let r = position.length();
+                -mass * (1. + r / rs).ln() / r
+            }
+            Self::Logarithmic { v0, rc, q } => {
+                let s = rc * rc
+                    + position.x() * position.x()
+                    + position.y() * position.y()
+                    + position.z() * position.z() / (q * q);
+                v0 * v0 / 2. * s.ln()
+            }
+        }
+    }
+
+    pub fn acceleration(&self, position: Point3) -> Vec3 {
+        match *self {
+            Self::PointMass { mass, position: p } => {
+                let r = position - p;
+                (-mass / r.length().powi(3)) * r
+            }
+            Self::Kepler { mass } => (-mass / position.length().powi(3)) * position,
+            Self::Harmonic { omega } => (-omega * omega) * position,
+            Self::MiyamotoNagai { mass, a, b } => {
+                let zeta = (position.z() * position.z() + b * b).sqrt();
+                let d3 = (position.x() * position.x()
+                    + position.y() * position.y()
+                    + (a + zeta).powi(2))
+                .powf(1.5);
+                Vec3::new(
+                    -mass * position.x() / d3,
+                    -mass * position.y() / d3,
+                    -mass * position.z() * (a + zeta) / (zeta * d3),
+                )
+            }
+            Self::Nfw { mass, rs } => {
+                let r = position.length();
+                let x = r / rs;
+                let enclosed = (1. + x).ln() - x / (1. + x);
+                (-mass * enclosed / r.powi(3)) * position
+            }
+            Self::Logarithmic { v0, rc, q } => {
+                let s = rc * rc
+                    + position.x() * position.x()
+                    + position.y() * position.y()
+                    + position.z() * position.z() / (q * q);
+                (-v0 * v0 / s) * Vec3::new(position.x(), position.y(), position.z() / (q * q))
+            }
+        }
+    }
+}
+
+impl Force for Potential {
+    fn acceleration(&self, i: usize, bodies: &[Body]) -> Vec3 {
+        Potential::acceleration(self, bodies[i].position)
+    }
+
+    fn potential_energy(&self, bodies: &[Body]) -> f64 {
+        kahan_sum(
+            bodies
+                .iter()
+                .filter(|body| body.is_active())
+                .map(|body| body.mass * self.potential(body.position)),
+        )
+    }
+
+    fn is_slow(&self) -> bool {
+        true
+    }
+}
+
+/// Parses potentials of the form `name:key=value,key=value`, e.g. `nfw:mass=1e12,rs=20`.
+impl FromStr for Potential {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, parameters) = s.split_once(':').unwrap_or((s, ""));
+        let mut parameters = Parameters::parse(parameters)?;
+        let mut take = |key: &str, default: Option<f64>| {
+            parameters
+                .take(key, default)
+                .map_err(|e| format!("{} for {} potential", e, name))
+        };
+
+        let potential = match name {
+            "point-mass" => Self::PointMass {
+                mass: take("mass", None)?,
+                position: Point3::new(
+                    take("x", Some(0.))?,
+                    take("y", Some(0.))?,
+                    take("z", Some(0.))?,
+                ),
+            },
+            "kepler" => Self::Kepler {
+                mass: take("mass", None)?,
+            },
+            "harmonic" => Self::Harmonic {
+                omega: take("omega", None)?,
+            },
+            "miyamoto-nagai" => Self::MiyamotoNagai {
+                mass: take("mass", None)?,
+                a: take("a", None)?,
+                b: take("b", None)?,
+            },
+            "nfw" => Self::Nfw {
+                mass: take("mass", None)?,
+                rs: take("rs", None)?,
+            },
+            "logarithmic" => Self::Logarithmic {
+                v0: take("v0", None)?,
+                rc: take("rc", None)?,
+                q: take("q", Some(1.))?,
+            },
+            _ => return Err(format!("unknown potential `{}`", name)),
+        };
+        parameters
+            .finish()
+            .map_err(|e| format!("{} for {} potential", e, name))?;
+        Ok(potential)
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    body::Body,
+    units::Units,
+    vec3::{Point3, Vec3},
+};
+
+/// Well-known systems which can be simulated without an initial conditions file.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Preset {
+    /// The Sun and eight planets at J2000 from JPL Horizons, in SI units
+    SolarSystem,
+    /// Chenciner and Montgomery's figure-eight choreography of three equal masses, in natural units
+    FigureEight,
+    /// The Sun, and the Earth and Moon on circular orbits, in SI units
+    SunEarthMoon,
+    /// Burrau's problem of masses 3, 4 and 5 at rest at the vertices of a 3-4-5 triangle, which
+    /// ends with an ejection, in natural units
+    #[value(alias = "burrau")]
+    Pythagorean,
+}
+
+/// Name, position (km), velocity (km/s) and Gm (km³/s²) of the bodies of the solar system at J2000.
+const SOLAR_SYSTEM: [(&str, [f64; 3], [f64; 3], f64); 9] = [
+    ("Sun", [0., 0., 0.], [0., 0., 0.], 132712440041.93938),
+    (
+        "Mercury",
+        [
+            -2.10526210724407e7,
+            -6.64066381225343e7,
+            -3.49244594657772e6,
+        ],
+        [
+            3.665298704187096e1,
+            -1.228983806940175e1,
+            -4.36817303624359e0,
+        ],
+        22031.86855,
+    ),
+    (
+        "Venus",
+        [
+            -1.07505550271985e8,
+            -3.366520666522362e6,
+            6.159219789239045e6,
+        ],
+        [
+            8.891597859686224e-1,
+            -3.515920774137907e1,
+            -5.318594228644749e-1,
+        ],
+        324858.592,
+    ),
+    (
+        "Earth",
+        [
+            -2.521092855899356e7,
+            1.449279195838006e8,
+            -6.164165719002485e2,
+        ],
+        [
+            -2.983983333677879e1,
+            -5.207633902410673e0,
+            6.168441184239981e-5,
+        ],
+        398600.435436,
+    ),
+    (
+        "Mars",
+        [
+            2.079950549836171e8,
+            -3.1430097139425e6,
+            -5.178781243488785e6,
+        ],
+        [
+            1.295003552976381e0,
+            2.629442066947002e1,
+            5.190097459233893e-1,
+        ],
+        42828.375214,
+    ),
+    (
+        "Jupiter",
+        [
+            5.989091645401344e8,
+            4.391225866604841e8,
+            -1.523251063025475e7,
+        ],
+        [
+            -7.901937516136118e0,
+            1.116317703172796e1,
+            1.30673214871428e-1,
+        ],
+        126686531.900,
+    ),
+    (
+        "Saturn",
+        [
+            9.587063371733198e8,
+            9.825652104588115e8,
+            -5.522065631225652e7,
+        ],
+        [
+            -7.428885680409909e0,
+            6.738814240733793e0,
+            1.776643606866641e-1,
+        ],
+        37931206.234,
+    ),
+    (
+        "Uranus",
+        [
+            2.158774699724352e9,
+            -2.054825151185744e9,
+            -3.562361168065417e7,
+        ],
+        [
+            4.637648534301329e0,
+            4.627193109110802e0,
+            -4.285052612262108e-2,
+        ],
+        5793951.256,
+    ),
+    (
+        "Neptune",
+        [
+            2.514853282370434e9,
+            -3.738847414715512e9,
+            1.903959877100039e7,
+        ],
+        [
+            4.465682523947062e0,
+            3.076493760667651e0,
+            -1.657059897537549e-1,
+        ],
+        6835099.97,
+    ),
+];
+
+impl Preset {
+    /// Unit system of the positions, velocities and masses of the bodies.
+    pub fn units(self) -> Units {
+        match self {
+            Self::SolarSystem | Self::SunEarthMoon => Units::Si,
+            Self::FigureEight | Self::Pythagorean => Units::Natural,
+        }
+    }
+
+    /// Bodies of the system, with masses in Gm units.
+    pub fn bodies(self) -> Vec<Body> {
+        match self {
+            Self::SolarSystem => (SOLAR_SYSTEM.iter())
+                .map(|&(name, [x, y, z], [vx, vy, vz], mass)| Body {
+                    name: Some(name.to_string()),
+                    ..Body::new(
+                        1e3 * Point3::new(x, y, z),
+                        1e3 * Vec3::new(vx, vy, vz),
+                        1e9 * mass,
+                    )
+                })
+                .collect(),
+            Self::FigureEight => {
+                let position = Point3::new(0.97000436, -0.24308753, 0.);
+                let velocity = Vec3::new(-0.93240737, -0.86473146, 0.);
+                vec![
+                    Body::new(position, -0.5 * velocity, 1.),
+                    Body::new(-position, -0.5 * velocity, 1.),
+                    Body::new(Point3::new(0., 0., 0.), velocity, 1.),
+                ]
+            }
+            Self::SunEarthMoon => {
+                let (sun, earth, moon) = (1.32712440018e20, 3.986004418e14, 4.9028e12);
+                let (au, lunar_distance) = (1.495978707e11, 3.844e8);
+                // The barycentre of the Earth and Moon orbits the Sun, and they orbit each other
+                let barycentre = Point3::new(au, 0., 0.);
+                let barycentre_velocity = Vec3::new(0., ((sun + earth + moon) / au).sqrt(), 0.);
+                let separation = Vec3::new(lunar_distance, 0., 0.);
+                let relative_velocity = Vec3::new(0., ((earth + moon) / lunar_distance).sqrt(), 0.);
+                let moon_fraction = moon / (earth + moon);
+                let named = |name: &str, position, velocity, mass| Body {
+                    name: Some(name.to_string()),
+                    ..Body::new(position, velocity, mass)
+                };
+                vec![
+                    named("Sun", Point3::new(0., 0., 0.), Vec3::new(0., 0., 0.), sun),
+                    named(
+                        "Earth",
+                        barycentre - moon_fraction * separation,
+                        barycentre_velocity - moon_fraction * relative_velocity,
+                        earth,
+                    ),
+                    named(
+                        "Moon",
+                        barycentre + (1. - moon_fraction) * separation,
+                        barycentre_velocity + (1. - moon_fraction) * relative_velocity,
+                        moon,
+                    ),
+                ]
+            }
+            Self::Pythagorean => vec![
+                Body::new(Point3::new(1., 3., 0.), Vec3::new(0., 0., 0.), 3.),
+                Body::new(Point3::new(-2., -1., 0.), Vec3::new(0., 0., 0.), 4.),
+                Body::new(Point3::new(1., -1., 0.), Vec3::new(0., 0., 0.), 5.),
+            ],
+        }
+    }
+}
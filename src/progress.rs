@@ -0,0 +1,55 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+
+use crate::simulation::Simulation;
+
+/// Minimum wall-clock time between updates of the energy error, which is expensive to compute.
+const MESSAGE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Progress bar of a run on stderr, with the tick rate, estimated time remaining and relative energy
+/// error. Nothing is drawn unless stderr is a terminal.
+pub struct ProgressDisplay {
+    bar: ProgressBar,
+    initial_energy: f64,
+    last_message: Instant,
+}
+
+impl ProgressDisplay {
+    /// Progress bar for a run of `ticks` ticks.
+    pub fn new(ticks: u64, initial_energy: f64) -> Self {
+        let style = ProgressStyle::with_template(
+            "{wide_bar} {percent:>3}% {human_pos}/{human_len} ticks, {rate}, ETA {eta} {msg}",
+        )
+        .expect("Progress bar template is valid")
+        .with_key("rate", |state: &ProgressState, w: &mut dyn fmt::Write| {
+            write!(w, "{:.0} ticks/s", state.per_sec()).unwrap()
+        });
+        Self {
+            bar: ProgressBar::new(ticks).with_style(style),
+            initial_energy,
+            last_message: Instant::now(),
+        }
+    }
+
+    /// Counts a tick of `world`. `energy_correction` is subtracted from the total energy, as for
+    /// the final report.
+    pub fn update(&mut self, world: &impl Simulation, energy_correction: f64) {
+        self.bar.inc(1);
+        if self.last_message.elapsed() < MESSAGE_INTERVAL || self.bar.is_hidden() {
+            return;
+        }
+        self.last_message = Instant::now();
+        let error = (world.total_energy() - energy_correction - self.initial_energy)
+            / self.initial_energy.abs();
+        self.bar.set_message(format!("energy error {:.3e}", error));
+    }
+
+    /// Removes the progress bar so that the report is printed on a clean terminal.
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
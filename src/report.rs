@@ -0,0 +1,621 @@
+//! Reports on the output files of runs, which gather their parameters, conservation, diagnostics
+//! and trajectories into a single HTML page or Markdown file, and comparisons of several runs.
+//!
+//! The files of a run are read from a directory under the names [`SUMMARY`], [`DIAGNOSTICS`] and
+//! [`TRAJECTORY`], as written by `run --format json --report <dir>/report.json --diag-out
+//! <dir>/diagnostics.csv --out <dir>/trajectory.csv`. Any of them may be missing or compressed.
+
+use std::{
+    error::Error,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    analysis::OrbitalElements,
+    body::{Body, State},
+    compress::{self, AtomicFile},
+    diagnostics::{self, DiagnosticsRow},
+    plot::{self, Axes, Series},
+    trajectory::{self, Frame},
+    vec3::{Point3, Vec3},
+};
+
+/// Name of the final report of a run, written with `--format json`.
+pub const SUMMARY: &str = "report.json";
+/// Name of the diagnostics file of a run.
+pub const DIAGNOSTICS: &str = "diagnostics.csv";
+/// Name of the trajectory file of a run.
+pub const TRAJECTORY: &str = "trajectory.csv";
+
+/// Final report of a run, as written by `run --report` with `--format json`.
+#[derive(Deserialize)]
+pub struct Summary {
+    pub time: f64,
+    pub parameters: Parameters,
+    pub energy: Change<f64>,
+    pub momentum: Change<Vec3>,
+    pub center_of_mass_drift: Vec3,
+    pub angular_momentum: Change<Vec3>,
+    pub state_hash: String,
+    pub bodies: Vec<Body>,
+}
+
+#[derive(Deserialize)]
+pub struct Parameters {
+    /// Name of the integrator.
+    pub sim: String,
+    pub tick: f64,
+    pub duration: f64,
+    /// Gravitational constant, if known. Quantities are in Gm units otherwise.
+    #[serde(rename = "G")]
+    pub g: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct Change<T> {
+    pub initial: T,
+    #[serde(rename = "final")]
+    pub end: T,
+}
+
+/// Outputs of a run which were found in its directory.
+pub struct Run {
+    /// Name of the directory, by which the run is known in reports.
+    pub name: String,
+    pub summary: Option<Summary>,
+    pub diagnostics: Vec<DiagnosticsRow>,
+    pub frames: Vec<Frame>,
+}
+
+impl Run {
+    /// Reads the outputs of the run in `directory`, which must have at least one of them.
+    pub fn read(directory: &Path) -> Result<Self, Box<dyn Error>> {
+        let name = directory.file_name().unwrap_or(directory.as_os_str());
+        let unreadable =
+            |path: &Path, e: &dyn Error| format!("unable to read {}: {}", path.display(), e);
+        let summary = match find(directory, SUMMARY) {
+            Some(path) => Some(
+                serde_json::from_reader(compress::open(&path)?)
+                    .map_err(|e| unreadable(&path, &e))?,
+            ),
+            None => None,
+        };
+        let diagnostics = match find(directory, DIAGNOSTICS) {
+            Some(path) => {
+                diagnostics::read(compress::open(&path)?).map_err(|e| unreadable(&path, &e))?
+            }
+            None => Vec::new(),
+        };
+        let frames = match find(directory, TRAJECTORY) {
+            Some(path) => {
+                trajectory::read(compress::open(&path)?).map_err(|e| unreadable(&path, &e))?
+            }
+            None => Vec::new(),
+        };
+        if summary.is_none() && diagnostics.is_empty() && frames.is_empty() {
+            return Err(format!(
+                "{} has no {}, {} or {}",
+                directory.display(),
+                SUMMARY,
+                DIAGNOSTICS,
+                TRAJECTORY
+            )
+            .into());
+        }
+        Ok(Self {
+            name: name.to_string_lossy().into_owned(),
+            summary,
+            diagnostics,
+            frames,
+        })
+    }
+}
+
+impl Run {
+    /// Value of each diagnostics row against time.
+    fn history(&self, value: fn(&DiagnosticsRow) -> f64) -> Vec<(f64, f64)> {
+        (self.diagnostics.iter())
+            .map(|row| (row.time, value(row)))
+            .collect()
+    }
+
+    /// Elements of the orbit of body `i` about body `j`, or about the barycentre of the others, at
+    /// each output of the trajectory at which they are present.
+    fn elements(&self, i: usize, j: Option<usize>) -> Vec<(f64, OrbitalElements)> {
+        let absent = Body {
+            state: State::Removed,
+            ..Body::new(Point3::default(), Vec3::default(), 0.)
+        };
+        (self.frames.iter())
+            .filter_map(|frame| {
+                // Trajectories only hold active bodies, so the others are filled in as inactive
+                let count = frame.bodies.iter().map(|&(k, _)| k + 1).max()?;
+                let mut bodies = vec![absent.clone(); count];
+                for (k, body) in &frame.bodies {
+                    bodies[*k] = body.clone();
+                }
+                OrbitalElements::of(&bodies, i, j).map(|elements| (frame.time, elements))
+            })
+            .collect()
+    }
+}
+
+/// Path of the file `name` in `directory`, possibly compressed, if there is one.
+fn find(directory: &Path, name: &str) -> Option<PathBuf> {
+    (["", ".gz", ".zst"].iter())
+        .map(|extension| directory.join(format!("{}{}", name, extension)))
+        .find(|path| path.is_file())
+}
+
+/// Writes a report on `run` to `path`, as a self-contained HTML page if it has an `.html`
+/// extension, and otherwise as Markdown with its plots written beside it as SVG files.
+pub fn write(path: &Path, run: &Run) -> Result<(), Box<dyn Error>> {
+    let mut document = Document::new(path, &format!("Report on {}", run.name));
+    if let Some(summary) = &run.summary {
+        let parameters = &summary.parameters;
+        let active = summary
+            .bodies
+            .iter()
+            .filter(|body| body.is_active())
+            .count();
+        document.heading("Parameters");
+        document.table(
+            &["Parameter", "Value"],
+            &[
+                vec!["Integrator".to_string(), parameters.sim.clone()],
+                vec!["Tick".to_string(), format!("{:e}", parameters.tick)],
+                vec!["Duration".to_string(), format!("{:e}", parameters.duration)],
+                vec![
+                    "G".to_string(),
+                    parameters
+                        .g
+                        .map_or("unknown, so quantities are in Gm units".to_string(), |g| {
+                            format!("{:e}", g)
+                        }),
+                ],
+                vec!["Final time".to_string(), format!("{:e}", summary.time)],
+                vec![
+                    "Bodies".to_string(),
+                    format!("{} active of {}", active, summary.bodies.len()),
+                ],
+            ],
+        );
+        document.heading("Conservation");
+        let energy = &summary.energy;
+        document.table(
+            &["Quantity", "Initial", "Final", "Change"],
+            &[
+                vec![
+                    "Energy".to_string(),
+                    format!("{:e}", energy.initial),
+                    format!("{:e}", energy.end),
+                    format!(
+                        "{:e} relative",
+                        (energy.end - energy.initial) / energy.initial.abs()
+                    ),
+                ],
+                change_row("Momentum", &summary.momentum),
+                change_row("Angular momentum", &summary.angular_momentum),
+            ],
+        );
+        document.paragraph(&format!(
+            "Centre of mass drift: [{:e}]. State hash: {}.",
+            summary.center_of_mass_drift, summary.state_hash
+        ));
+    }
+    if let Some(last) = run.diagnostics.last() {
+        document.heading("Diagnostics");
+        let drift = energy_drift(&run.diagnostics);
+        document.table(
+            &["Quantity", "Final value"],
+            &[
+                vec!["Time".to_string(), format!("{:e}", last.time)],
+                vec![
+                    "Energy drift".to_string(),
+                    format!("{:e}", drift.last().map_or(0., |&(_, drift)| drift)),
+                ],
+                vec!["Virial ratio".to_string(), last.virial_ratio.to_string()],
+                vec![
+                    "10%, 50% and 90% Lagrangian radii".to_string(),
+                    format!(
+                        "{:e}, {:e}, {:e}",
+                        last.lagrangian_radius_10,
+                        last.lagrangian_radius_50,
+                        last.lagrangian_radius_90
+                    ),
+                ],
+            ],
+        );
+        document.figure(
+            "Energy relative to its initial value",
+            plot::line_chart(
+                "Energy drift",
+                "time",
+                "relative drift",
+                &[(run.name.clone(), drift)],
+            )?,
+        );
+        document.figure(
+            "Radii about the density centre enclosing 10%, 50% and 90% of the mass",
+            plot::line_chart(
+                "Lagrangian radii",
+                "time",
+                "radius",
+                &[
+                    (
+                        "10%".to_string(),
+                        run.history(|row| row.lagrangian_radius_10),
+                    ),
+                    (
+                        "50%".to_string(),
+                        run.history(|row| row.lagrangian_radius_50),
+                    ),
+                    (
+                        "90%".to_string(),
+                        run.history(|row| row.lagrangian_radius_90),
+                    ),
+                ],
+            )?,
+        );
+        document.figure(
+            "Ratio of kinetic to potential energy, which is 0.5 in virial equilibrium",
+            plot::line_chart(
+                "Virial ratio",
+                "time",
+                "virial ratio",
+                &[(run.name.clone(), run.history(|row| row.virial_ratio))],
+            )?,
+        );
+    }
+    if let (Some(first), Some(last)) = (run.frames.first(), run.frames.last()) {
+        document.heading("Trajectories");
+        document.paragraph(&format!(
+            "{} outputs from t = {:e} to t = {:e}.",
+            run.frames.len(),
+            first.time,
+            last.time
+        ));
+        for (axes, plane) in [(Axes::Xy, "xy"), (Axes::Xz, "xz")] {
+            document.figure(
+                &format!("Paths of the bodies in the {} plane", plane),
+                plot::render_svg(&run.frames, axes)?,
+            );
+        }
+    }
+    Ok(document.finish()?)
+}
+
+/// Writes a comparison of `runs` to `path`, as HTML or Markdown as for [`write`]. A table gives the
+/// final values of each run and their differences from the first, and plots overlay their energy
+/// drift, Lagrangian radii and virial ratios, and the orbital elements of the pairs of bodies in
+/// `elements` over their trajectories.
+pub fn compare(
+    path: &Path,
+    runs: &[Run],
+    elements: &[(usize, Option<usize>)],
+) -> Result<(), Box<dyn Error>> {
+    let names: Vec<&str> = runs.iter().map(|run| run.name.as_str()).collect();
+    let mut document = Document::new(path, &format!("Comparison of {}", names.join(", ")));
+    document.heading("Differences");
+    let mut header = vec!["Quantity"];
+    header.extend(&names);
+    let rows = [
+        text_row(runs, "Integrator", |run| {
+            run.summary.as_ref().map(|s| s.parameters.sim.clone())
+        }),
+        number_row(runs, "Tick", |run| {
+            run.summary.as_ref().map(|s| s.parameters.tick)
+        }),
+        number_row(runs, "Final time", |run| {
+            run.summary.as_ref().map(|s| s.time)
+        }),
+        number_row(runs, "Relative energy drift", |run| {
+            run.summary
+                .as_ref()
+                .map(|s| (s.energy.end - s.energy.initial) / s.energy.initial.abs())
+        }),
+        number_row(runs, "Change in angular momentum", |run| {
+            run.summary
+                .as_ref()
+                .map(|s| (s.angular_momentum.end - s.angular_momentum.initial).length())
+        }),
+        number_row(runs, "Centre of mass drift", |run| {
+            run.summary
+                .as_ref()
+                .map(|s| s.center_of_mass_drift.length())
+        }),
+        number_row(runs, "Virial ratio", |run| {
+            run.diagnostics.last().map(|row| row.virial_ratio)
+        }),
+        number_row(runs, "10% Lagrangian radius", |run| {
+            run.diagnostics.last().map(|row| row.lagrangian_radius_10)
+        }),
+        number_row(runs, "50% Lagrangian radius", |run| {
+            run.diagnostics.last().map(|row| row.lagrangian_radius_50)
+        }),
+        number_row(runs, "90% Lagrangian radius", |run| {
+            run.diagnostics.last().map(|row| row.lagrangian_radius_90)
+        }),
+        text_row(runs, "State hash", |run| {
+            run.summary.as_ref().map(|s| s.state_hash.clone())
+        }),
+    ];
+    document.table(&header, &rows);
+    document.paragraph(
+        "Values of the final report and the last diagnostics of each run, with their differences \
+         from the first run in parentheses. Changes in vectors are given by their lengths.",
+    );
+
+    let with_diagnostics: Vec<&Run> = (runs.iter())
+        .filter(|run| !run.diagnostics.is_empty())
+        .collect();
+    if !with_diagnostics.is_empty() {
+        document.heading("Diagnostics");
+        let overlay = |value: fn(&DiagnosticsRow) -> f64| -> Vec<Series> {
+            (with_diagnostics.iter())
+                .map(|run| (run.name.clone(), run.history(value)))
+                .collect()
+        };
+        let drifts: Vec<Series> = (with_diagnostics.iter())
+            .map(|run| (run.name.clone(), energy_drift(&run.diagnostics)))
+            .collect();
+        document.figure(
+            "Energy of each run relative to its initial value",
+            plot::line_chart("Energy drift", "time", "relative drift", &drifts)?,
+        );
+        for (fraction, value) in [
+            (
+                "10%",
+                (|row| row.lagrangian_radius_10) as fn(&DiagnosticsRow) -> f64,
+            ),
+            ("50%", |row| row.lagrangian_radius_50),
+            ("90%", |row| row.lagrangian_radius_90),
+        ] {
+            document.figure(
+                &format!(
+                    "Radius about the density centre enclosing {} of the mass",
+                    fraction
+                ),
+                plot::line_chart(
+                    &format!("{} Lagrangian radius", fraction),
+                    "time",
+                    "radius",
+                    &overlay(value),
+                )?,
+            );
+        }
+        document.figure(
+            "Ratio of kinetic to potential energy, which is 0.5 in virial equilibrium",
+            plot::line_chart(
+                "Virial ratio",
+                "time",
+                "virial ratio",
+                &overlay(|row| row.virial_ratio),
+            )?,
+        );
+    }
+
+    for &(i, j) in elements {
+        let about = j.map_or("the barycentre of the others".to_string(), |j| {
+            format!("body {}", j)
+        });
+        let histories: Vec<(&str, Vec<(f64, OrbitalElements)>)> = (runs.iter())
+            .map(|run| (run.name.as_str(), run.elements(i, j)))
+            .filter(|(_, history)| !history.is_empty())
+            .collect();
+        if histories.is_empty() {
+            document.paragraph(&format!(
+                "No trajectory has the orbit of body {} about {}.",
+                i, about
+            ));
+            continue;
+        }
+        document.heading(&format!("Orbit of body {} about {}", i, about));
+        for (name, element) in [
+            (
+                "Semi-major axis",
+                (|e| e.semi_major_axis) as fn(&OrbitalElements) -> f64,
+            ),
+            ("Eccentricity", |e| e.eccentricity),
+            ("Inclination", |e| e.inclination.to_degrees()),
+        ] {
+            let series: Vec<Series> = (histories.iter())
+                .map(|(run, history)| {
+                    let points = history.iter().map(|(t, e)| (*t, element(e)));
+                    (run.to_string(), points.collect())
+                })
+                .collect();
+            let unit = if name == "Inclination" {
+                " in degrees"
+            } else {
+                ""
+            };
+            document.figure(
+                &format!("{} of body {} about {}{}", name, i, about, unit),
+                plot::line_chart(name, "time", &name.to_lowercase(), &series)?,
+            );
+        }
+    }
+    Ok(document.finish()?)
+}
+
+/// Row of the comparison table giving the text of each run, and whether it matches the first.
+fn text_row(runs: &[Run], name: &str, value: impl Fn(&Run) -> Option<String>) -> Vec<String> {
+    let first = runs.first().and_then(&value);
+    let mut row = vec![name.to_string()];
+    for (k, run) in runs.iter().enumerate() {
+        row.push(match value(run) {
+            Some(text) if k == 0 => text,
+            Some(text) if Some(&text) == first.as_ref() => format!("{} (same)", text),
+            Some(text) => format!("{} (differs)", text),
+            None => "-".to_string(),
+        });
+    }
+    row
+}
+
+/// Row of the comparison table giving the value of each run, and its difference from the first.
+fn number_row(runs: &[Run], name: &str, value: impl Fn(&Run) -> Option<f64>) -> Vec<String> {
+    let first = runs.first().and_then(&value);
+    let mut row = vec![name.to_string()];
+    for (k, run) in runs.iter().enumerate() {
+        row.push(match (value(run), first) {
+            (Some(value), Some(first)) if k > 0 => {
+                format!("{:e} ({:+e})", value, value - first)
+            }
+            (Some(value), _) => format!("{:e}", value),
+            (None, _) => "-".to_string(),
+        });
+    }
+    row
+}
+
+/// Row of the conservation table for a vector quantity.
+fn change_row(name: &str, change: &Change<Vec3>) -> Vec<String> {
+    vec![
+        name.to_string(),
+        format!("[{:e}]", change.initial),
+        format!("[{:e}]", change.end),
+        format!("[{:e}]", change.end - change.initial),
+    ]
+}
+
+/// Change in energy at each row relative to the first, or the absolute change if it is zero.
+fn energy_drift(rows: &[DiagnosticsRow]) -> Vec<(f64, f64)> {
+    let initial = rows.first().map_or(0., |row| row.energy);
+    let scale = if initial != 0. { initial.abs() } else { 1. };
+    (rows.iter())
+        .map(|row| (row.time, (row.energy - initial) / scale))
+        .collect()
+}
+
+/// Report being assembled as HTML or Markdown.
+struct Document {
+    path: PathBuf,
+    html: bool,
+    title: String,
+    body: String,
+    /// SVG files of the figures of a Markdown report, with their contents.
+    figures: Vec<(PathBuf, String)>,
+}
+
+impl Document {
+    fn new(path: &Path, title: &str) -> Self {
+        let html = path
+            .extension()
+            .is_some_and(|extension| extension == "html");
+        let mut document = Self {
+            path: path.to_path_buf(),
+            html,
+            title: title.to_string(),
+            body: String::new(),
+            figures: Vec::new(),
+        };
+        if html {
+            document.body += &format!("<h1>{}</h1>\n", escape(title));
+        } else {
+            document.body += &format!("# {}\n\n", title);
+        }
+        document
+    }
+
+    fn heading(&mut self, text: &str) {
+        if self.html {
+            self.body += &format!("<h2>{}</h2>\n", escape(text));
+        } else {
+            self.body += &format!("## {}\n\n", text);
+        }
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        if self.html {
+            self.body += &format!("<p>{}</p>\n", escape(text));
+        } else {
+            self.body += &format!("{}\n\n", text);
+        }
+    }
+
+    fn table(&mut self, header: &[&str], rows: &[Vec<String>]) {
+        if self.html {
+            let row = |cells: &mut dyn Iterator<Item = &str>, tag: &str| {
+                let cells: String = cells
+                    .map(|cell| format!("<{0}>{1}</{0}>", tag, escape(cell)))
+                    .collect();
+                format!("<tr>{}</tr>\n", cells)
+            };
+            self.body += "<table>\n";
+            self.body += &row(&mut header.iter().copied(), "th");
+            for cells in rows {
+                self.body += &row(&mut cells.iter().map(String::as_str), "td");
+            }
+            self.body += "</table>\n";
+        } else {
+            let row = |cells: &mut dyn Iterator<Item = &str>| {
+                let cells: Vec<String> = cells.map(|cell| cell.replace('|', "\\|")).collect();
+                format!("| {} |\n", cells.join(" | "))
+            };
+            self.body += &row(&mut header.iter().copied());
+            self.body += &format!("|{}\n", "---|".repeat(header.len()));
+            for cells in rows {
+                self.body += &row(&mut cells.iter().map(String::as_str));
+            }
+            self.body += "\n";
+        }
+    }
+
+    /// Adds an SVG figure, which is embedded in HTML reports and written to a file named after the
+    /// report for Markdown ones.
+    fn figure(&mut self, caption: &str, svg: String) {
+        if self.html {
+            self.body += &format!(
+                "<figure>\n{}\n<figcaption>{}</figcaption>\n</figure>\n",
+                svg,
+                escape(caption)
+            );
+            return;
+        }
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = format!("{}-{}.svg", stem, self.figures.len() + 1);
+        self.body += &format!("![{}]({})\n\n", caption, name);
+        self.figures.push((self.path.with_file_name(name), svg));
+    }
+
+    fn finish(self) -> io::Result<()> {
+        for (path, svg) in &self.figures {
+            let mut file = AtomicFile::create(path, false)?;
+            file.write_all(svg.as_bytes())?;
+            file.finish()?;
+        }
+        let mut file = AtomicFile::create(&self.path, false)?;
+        if self.html {
+            write!(
+                file,
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+                 <style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+                escape(&self.title),
+                STYLE,
+                self.body
+            )?;
+        } else {
+            file.write_all(self.body.as_bytes())?;
+        }
+        file.finish()
+    }
+}
+
+/// Style sheet of HTML reports.
+const STYLE: &str = "body { font-family: sans-serif; max-width: 50em; margin: auto; } \
+    table { border-collapse: collapse; } \
+    th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; } \
+    figure { margin: 1em 0; } svg { max-width: 100%; height: auto; }";
+
+/// Escapes text for HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
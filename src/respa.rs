@@ -0,0 +1,210 @@
+use serde::{Serialize, Serializer};
+
+use crate::{
+    body::{self, Body},
+    boundary::SimulationBox,
+    collision::{self, CollisionMode},
+    cosmology::Cosmology,
+    event::{Event, Events},
+    force::{self, Force},
+    observer::Observers,
+    simulation::{Simulation, WorldState},
+    vec3::Vec3,
+};
+
+/// Two-level leapfrog (RESPA), in which slowly varying forces such as external potentials are
+/// only evaluated at the start and end of each tick, while the remaining forces are integrated
+/// with shorter substeps.
+#[derive(Debug)]
+pub struct World {
+    bodies: Vec<Body>,
+    forces: Vec<Box<dyn Force>>,
+    /// Number of substeps per tick for the fast forces.
+    substeps: usize,
+    collision_mode: Option<CollisionMode>,
+    restitution: f64,
+    events: Events,
+    simulation_box: Option<SimulationBox>,
+    cosmology: Option<Cosmology>,
+    observers: Observers,
+    /// Id of the next body to be added.
+    next_id: usize,
+    time: f64,
+}
+
+impl World {
+    pub fn new(mut bodies: Vec<Body>, forces: Vec<Box<dyn Force>>, substeps: usize) -> Self {
+        let next_id = body::assign_ids(&mut bodies);
+        Self {
+            bodies,
+            forces,
+            substeps,
+            collision_mode: None,
+            restitution: 1.,
+            events: Events::default(),
+            simulation_box: None,
+            cosmology: None,
+            observers: Observers::default(),
+            next_id,
+            time: 0.,
+        }
+    }
+
+    /// Accelerations from the forces which are evaluated every substep, including the comoving
+    /// terms of any cosmology.
+    fn fast_accelerations(&self) -> Vec<Vec3> {
+        let forces = self.forces.iter().filter(|f| !f.is_slow());
+        let mut accelerations = force::accelerations(forces, &self.bodies);
+        if let Some(cosmology) = &self.cosmology {
+            cosmology.comoving_accelerations(&self.bodies, &mut accelerations);
+        }
+        accelerations
+    }
+
+    /// Accelerations from the forces which are evaluated once per tick.
+    fn slow_accelerations(&self) -> Vec<Vec3> {
+        force::accelerations(self.forces.iter().filter(|f| f.is_slow()), &self.bodies)
+    }
+
+    fn kick(&mut self, accelerations: Vec<Vec3>, duration: f64) {
+        for (body, acceleration) in self.bodies.iter_mut().zip(accelerations) {
+            body.velocity += acceleration * duration;
+        }
+    }
+}
+
+/// Serialised as its [`SavedWorld`](crate::simulation::SavedWorld).
+impl Serialize for World {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.save().serialize(serializer)
+    }
+}
+
+impl Simulation for World {
+    fn tick(&mut self, tick_duration: f64) {
+        let substep = tick_duration / self.substeps as f64;
+        // Half kick from the slow forces
+        self.kick(self.slow_accelerations(), tick_duration / 2.);
+        // Kick-drift-kick leapfrog with the fast forces, reusing the accelerations at the end of
+        // each substep for the start of the next
+        let mut accelerations = self.fast_accelerations();
+        for _ in 0..self.substeps {
+            self.kick(accelerations, substep / 2.);
+            for body in self.bodies.iter_mut().filter(|body| body.is_mobile()) {
+                body.position += body.velocity * substep;
+            }
+            self.confine_bodies();
+            if let Some(cosmology) = &mut self.cosmology {
+                cosmology.advance(substep);
+            }
+            accelerations = self.fast_accelerations();
+            self.kick(accelerations.clone(), substep / 2.);
+        }
+        // Half kick from the slow forces at the new positions
+        self.kick(self.slow_accelerations(), tick_duration / 2.);
+        self.time += tick_duration;
+        // Apply stochastic velocity kicks
+        let kicks = force::kicks(&mut self.forces, &self.bodies, tick_duration);
+        for (body, kick) in self.bodies.iter_mut().zip(kicks) {
+            body.velocity += kick;
+        }
+        // Evolve masses
+        for body in &mut self.bodies {
+            body.evolve_mass(tick_duration);
+        }
+        // Resolve collisions
+        let periodic = self.simulation_box.and_then(|b| b.periodic());
+        if let Some(mode) = self.collision_mode {
+            let collisions = collision::resolve(
+                mode,
+                self.restitution,
+                &mut self.bodies,
+                self.time,
+                periodic,
+            );
+            for collision in collisions {
+                self.events.emit(Event::Collision(collision));
+            }
+        }
+        self.events.detect(self.time, &self.bodies, periodic);
+        self.notify_observers();
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn bodies(&self) -> &Vec<Body> {
+        &self.bodies
+    }
+
+    fn bodies_mut(&mut self) -> &mut Vec<Body> {
+        &mut self.bodies
+    }
+
+    fn add_body(&mut self, mut body: Body) -> usize {
+        body.id = self.next_id;
+        self.next_id += 1;
+        self.bodies.push(body);
+        self.next_id - 1
+    }
+
+    fn forces(&self) -> &[Box<dyn Force>] {
+        &self.forces
+    }
+
+    fn scheme(&self) -> &'static str {
+        "RESPA: v += δt/2 a_slow(x), then for each substep h = δt/n: v += h/2 a_fast(x), x += h v, v += h/2 a_fast(x), then v += δt/2 a_slow(x), where slow forces are external potentials"
+    }
+
+    fn set_collision_mode(&mut self, mode: CollisionMode, restitution: f64) {
+        self.collision_mode = Some(mode);
+        self.restitution = restitution;
+    }
+
+    fn events(&self) -> &Events {
+        &self.events
+    }
+
+    fn events_mut(&mut self) -> &mut Events {
+        &mut self.events
+    }
+
+    fn simulation_box(&self) -> Option<SimulationBox> {
+        self.simulation_box
+    }
+
+    fn set_simulation_box(&mut self, simulation_box: SimulationBox) {
+        self.simulation_box = Some(simulation_box);
+    }
+
+    fn cosmology(&self) -> Option<Cosmology> {
+        self.cosmology
+    }
+
+    fn set_cosmology(&mut self, cosmology: Cosmology) {
+        self.cosmology = Some(cosmology);
+    }
+
+    fn world_state(&self) -> WorldState {
+        WorldState {
+            time: self.time,
+            started: false,
+            tick: 0.,
+            next_id: self.next_id,
+            cosmology: self.cosmology,
+            rngs: force::rngs(&self.forces),
+        }
+    }
+
+    fn restore_world_state(&mut self, state: WorldState) {
+        self.time = state.time;
+        self.next_id = state.next_id;
+        self.cosmology = state.cosmology;
+        force::restore_rngs(&mut self.forces, state.rngs);
+    }
+
+    fn observers_mut(&mut self) -> &mut Observers {
+        &mut self.observers
+    }
+}
@@ -0,0 +1,100 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc, sync::Arc};
+
+use crate::{
+    body::Body,
+    event::Detected,
+    observer::Observer,
+    simulation::{SavedWorld, Simulation},
+};
+
+/// Copy of the state of a world at one time, which the world can be rewound to with
+/// [`Simulation::restore`]. Clones share the copy, so they are cheap.
+#[derive(Clone, Debug)]
+pub struct Snapshot(Arc<Contents>);
+
+#[derive(Debug)]
+struct Contents {
+    saved: SavedWorld,
+    detected: Detected,
+}
+
+impl Snapshot {
+    pub(crate) fn new(saved: SavedWorld, detected: Detected) -> Self {
+        Self(Arc::new(Contents { saved, detected }))
+    }
+
+    pub fn time(&self) -> f64 {
+        self.0.saved.state.time
+    }
+
+    pub fn bodies(&self) -> &[Body] {
+        &self.0.saved.bodies
+    }
+
+    pub fn saved(&self) -> &SavedWorld {
+        &self.0.saved
+    }
+
+    pub(crate) fn detected(&self) -> &Detected {
+        &self.0.detected
+    }
+}
+
+/// Ring buffer of the latest snapshots of a world, taken automatically as an observer. Clones share
+/// the buffer, so one can be registered with the world and the other kept to rewind it with, such
+/// as to bisect for the time of an event.
+#[derive(Clone, Debug)]
+pub struct History {
+    snapshots: Rc<RefCell<VecDeque<Snapshot>>>,
+    capacity: usize,
+}
+
+impl History {
+    /// Creates a history which keeps the latest `capacity` snapshots. Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "histories must keep one or more snapshots");
+        Self {
+            snapshots: Rc::new(RefCell::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Snapshots kept, from earliest to latest.
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        self.snapshots.borrow().iter().cloned().collect()
+    }
+
+    pub fn latest(&self) -> Option<Snapshot> {
+        self.snapshots.borrow().back().cloned()
+    }
+
+    /// Latest snapshot kept from strictly before `time`.
+    pub fn before(&self, time: f64) -> Option<Snapshot> {
+        (self.snapshots.borrow().iter())
+            .rev()
+            .find(|snapshot| snapshot.time() < time)
+            .cloned()
+    }
+
+    /// Adds a snapshot, dropping the earliest if the history is full.
+    pub fn push(&self, snapshot: Snapshot) {
+        let mut snapshots = self.snapshots.borrow_mut();
+        if snapshots.len() == self.capacity {
+            snapshots.pop_front();
+        }
+        snapshots.push_back(snapshot);
+    }
+
+    /// Drops every snapshot after `time`, as when a world has been rewound to it.
+    pub fn truncate_after(&self, time: f64) {
+        self.snapshots
+            .borrow_mut()
+            .retain(|snapshot| snapshot.time() <= time);
+    }
+}
+
+impl Observer for History {
+    fn observe(&mut self, world: &dyn Simulation) {
+        self.push(world.snapshot());
+    }
+}
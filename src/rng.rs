@@ -0,0 +1,61 @@
+use std::f64::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+/// Small seedable xoshiro256** generator, so runs are reproducible from a seed on every platform.
+/// Serialised as its state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Rng {
+    s: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Expand the seed with splitmix64, as recommended by the xoshiro authors
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+        Self {
+            s: [next(), next(), next(), next()],
+        }
+    }
+
+    /// Generator continuing from a state returned by `state`.
+    pub fn from_state(s: [u64; 4]) -> Self {
+        Self { s }
+    }
+
+    pub fn state(&self) -> [u64; 4] {
+        self.s
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+
+    /// Uniformly distributed in [0, 1).
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal deviate (Box-Muller).
+    pub fn normal(&mut self) -> f64 {
+        let u = 1. - self.uniform();
+        let v = self.uniform();
+        (-2. * u.ln()).sqrt() * (TAU * v).cos()
+    }
+}
@@ -0,0 +1,264 @@
+use std::f64::consts::TAU;
+
+use crate::{
+    body::Body,
+    boundary::PeriodicBox,
+    force::{Ewald, Force, Gravity, MagneticField},
+    forward_euler, leapfrog,
+    potential::Potential,
+    rng::Rng,
+    simulation::Simulation,
+    symplectic_euler,
+    vec3::{Point3, Vec3},
+};
+
+/// Runs conservation, two-body and known-solution checks on this machine, printing each result
+/// against its tolerance. Returns whether every check passed.
+pub fn run() -> bool {
+    let checks = [
+        (
+            "leapfrog circular orbit returns after one period",
+            circular_orbit(leapfrog::World::new(binary(), gravity())),
+            1e-4,
+        ),
+        (
+            "symplectic Euler circular orbit returns after one period",
+            circular_orbit(symplectic_euler::World::new(binary(), gravity())),
+            1e-2,
+        ),
+        (
+            "forward Euler circular orbit returns after one period",
+            circular_orbit(forward_euler::World::new(binary(), gravity())),
+            0.1,
+        ),
+        (
+            "leapfrog conserves energy of an eccentric orbit",
+            energy_drift(leapfrog::World::new(eccentric(), gravity())),
+            1e-8,
+        ),
+        (
+            "gravity conserves momentum of a three-body system",
+            momentum_drift(leapfrog::World::new(three_body(), gravity())),
+            1e-12,
+        ),
+        ("harmonic potential matches x = cos ωt", harmonic(), 1e-5),
+        (
+            "Boris push conserves speed in a magnetic field",
+            gyration(),
+            1e-12,
+        ),
+        (
+            "Ewald force vanishes at half a box separation",
+            ewald_symmetry(),
+            1e-6,
+        ),
+        (
+            "compensated gravity sums 10⁴ pulls which cancel to zero",
+            mirrored_pulls(true),
+            1e-14,
+        ),
+        (
+            "compensated gravity is 10 times more accurate than plain",
+            mirrored_pulls(true) / mirrored_pulls(false),
+            0.1,
+        ),
+        ("repeated runs are bit-for-bit identical", determinism(), 0.),
+        (
+            "deterministic gravity matches the reference state hash",
+            reference_hash(),
+            0.,
+        ),
+    ];
+    let mut passed = true;
+    for (name, error, tolerance) in checks {
+        let pass = error <= tolerance;
+        passed &= pass;
+        println!(
+            "{} {}: error {:e} (tolerance {:e})",
+            if pass { "PASS" } else { "FAIL" },
+            name,
+            error,
+            tolerance
+        );
+    }
+    passed
+}
+
+fn gravity() -> Vec<Box<dyn Force>> {
+    vec![Box::new(Gravity::default())]
+}
+
+/// Test particle on a circular orbit of unit radius and period 2π around a fixed unit mass.
+fn binary() -> Vec<Body> {
+    let mut centre = Body::new(Point3::ZERO, Vec3::ZERO, 1.);
+    centre.fixed = true;
+    let mut particle = Body::new(Point3::new(1., 0., 0.), Vec3::new(0., 1., 0.), 0.);
+    particle.test = true;
+    vec![centre, particle]
+}
+
+/// Equal mass binary with eccentricity 0.5.
+fn eccentric() -> Vec<Body> {
+    let v = 0.5 * 0.5f64.sqrt();
+    vec![
+        Body::new(Point3::new(0.5, 0., 0.), Vec3::new(0., v, 0.), 1.),
+        Body::new(Point3::new(-0.5, 0., 0.), Vec3::new(0., -v, 0.), 1.),
+    ]
+}
+
+fn three_body() -> Vec<Body> {
+    vec![
+        Body::new(Point3::new(1., 0., 0.), Vec3::new(0., 0.3, 0.1), 1.),
+        Body::new(Point3::new(-0.5, 0.8, 0.), Vec3::new(-0.2, 0., 0.), 2.),
+        Body::new(Point3::new(0., -1., 0.5), Vec3::new(0.1, -0.1, 0.), 0.5),
+    ]
+}
+
+fn evolve(world: &mut impl Simulation, duration: f64, tick_duration: f64) {
+    let ticks = (duration / tick_duration).round() as usize;
+    for _ in 0..ticks {
+        world.tick(tick_duration);
+    }
+}
+
+fn circular_orbit(mut world: impl Simulation) -> f64 {
+    evolve(&mut world, TAU, TAU / 10000.);
+    (world.bodies()[1].position - Point3::new(1., 0., 0.)).length()
+}
+
+fn energy_drift(mut world: impl Simulation) -> f64 {
+    let initial = world.total_energy();
+    evolve(&mut world, 10., 1e-4);
+    ((world.total_energy() - initial) / initial).abs()
+}
+
+fn momentum_drift(mut world: impl Simulation) -> f64 {
+    let initial = world.total_momentum();
+    evolve(&mut world, 1., 1e-3);
+    (world.total_momentum() - initial).length()
+}
+
+fn harmonic() -> f64 {
+    let omega = 2.;
+    let bodies = vec![Body::new(Point3::new(1., 0., 0.), Vec3::ZERO, 0.)];
+    let forces: Vec<Box<dyn Force>> = vec![Box::new(Potential::Harmonic { omega })];
+    let mut world = leapfrog::World::new(bodies, forces);
+    evolve(&mut world, 1., 1e-4);
+    // Leapfrog positions are at whole ticks
+    (world.bodies()[0].position.x() - (omega * world.time()).cos()).abs()
+}
+
+fn gyration() -> f64 {
+    let mut body = Body::new(Point3::new(1., 0., 0.), Vec3::new(0., 1., 0.), 1.);
+    body.charge = 1.;
+    let forces: Vec<Box<dyn Force>> = vec![Box::new(MagneticField {
+        field: Vec3::new(0., 0., 1.),
+        g: 1.,
+    })];
+    let mut world = leapfrog::World::new(vec![body], forces);
+    evolve(&mut world, 100., 0.1);
+    (world.bodies()[0].velocity.length() - 1.).abs()
+}
+
+fn ewald_symmetry() -> f64 {
+    let ewald = Ewald::new(PeriodicBox { size: 1. });
+    let a = Body::new(Point3::new(0.25, 0.1, -0.2), Vec3::ZERO, 1.);
+    let b = Body::new(Point3::new(-0.25, 0.1, -0.2), Vec3::ZERO, 1.);
+    ewald.pairwise(&a, &b).length()
+}
+
+/// Acceleration of a test particle at the centre of 10⁴ unit masses on a unit sphere, in mirrored
+/// pairs so that it is exactly zero, which measures the rounding error of the sum in units of the
+/// pull of one mass.
+fn mirrored_pulls(compensated: bool) -> f64 {
+    let mut rng = Rng::new(1);
+    let sphere: Vec<Point3> = (0..5000)
+        .map(|_| Vec3::unitv(&Vec3::new(rng.normal(), rng.normal(), rng.normal())))
+        .collect();
+    let mut particle = Body::new(Point3::ZERO, Vec3::ZERO, 0.);
+    particle.test = true;
+    let mut bodies = vec![particle];
+    // Each half on its own, so that the partial sums grow before they cancel
+    bodies.extend(sphere.iter().map(|&p| Body::new(p, Vec3::ZERO, 1.)));
+    bodies.extend(sphere.iter().map(|&p| Body::new(-p, Vec3::ZERO, 1.)));
+    let gravity = Gravity {
+        compensated,
+        ..Default::default()
+    };
+    gravity.acceleration(0, &bodies).length()
+}
+
+/// State hash of a three-body run with deterministic gravity, which every machine with strict
+/// IEEE 754 arithmetic reproduces.
+const REFERENCE_HASH: u64 = 0xae5c4df71c299a70;
+
+fn reference_hash() -> f64 {
+    if deterministic_hash() == REFERENCE_HASH {
+        0.
+    } else {
+        1.
+    }
+}
+
+/// State hash of the reference run.
+fn deterministic_hash() -> u64 {
+    let gravity = Gravity {
+        deterministic: true,
+        ..Default::default()
+    };
+    let mut world = leapfrog::World::new(three_body(), vec![Box::new(gravity)]);
+    evolve(&mut world, 1., 1e-3);
+    world.state_hash()
+}
+
+fn determinism() -> f64 {
+    let hash = || {
+        let mut world = leapfrog::World::new(three_body(), gravity());
+        evolve(&mut world, 1., 1e-3);
+        world.state_hash()
+    };
+    if hash() == hash() {
+        0.
+    } else {
+        1.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn deterministic_gravity_reproduces_the_reference_hash() {
+        assert_eq!(deterministic_hash(), REFERENCE_HASH);
+    }
+
+    #[test]
+    fn repeated_runs_are_identical() {
+        assert_eq!(deterministic_hash(), deterministic_hash());
+        assert_eq!(determinism(), 0.);
+    }
+
+    #[test]
+    fn runs_on_other_threads_are_identical() {
+        let hashes: Vec<u64> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..4).map(|_| scope.spawn(deterministic_hash)).collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+        assert!(hashes.iter().all(|&hash| hash == REFERENCE_HASH));
+    }
+
+    #[test]
+    fn compensated_gravity_is_more_accurate_than_plain() {
+        let (compensated, plain) = (mirrored_pulls(true), mirrored_pulls(false));
+        assert!(compensated < 1e-14, "compensated error {:e}", compensated);
+        assert!(
+            compensated * 10. < plain,
+            "compensated error {:e} against plain {:e}",
+            compensated,
+            plain
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{body::Body, simulation::Simulation};
+
+/// Reruns the simulation once per initial condition component with that component perturbed by a
+/// relative `step`, and prints how far each perturbation moves the final state, most sensitive
+/// first.
+///
+/// Deviations are measured in units of the RMS initial distance and speed of the bodies, so a
+/// sensitivity of 1 means the final state moves as much as the initial state was perturbed.
+pub fn analyze<S: Simulation>(
+    new_world: impl Fn(Vec<Body>) -> S,
+    bodies: &[Body],
+    duration: f64,
+    tick_duration: f64,
+    step: f64,
+) {
+    let simulate = |bodies: Vec<Body>| {
+        let mut world = new_world(bodies);
+        while world.time() < duration {
+            world.tick(tick_duration);
+        }
+        world.bodies().clone()
+    };
+    let length = rms(bodies, |body| body.position.length());
+    let speed = rms(bodies, |body| body.velocity.length());
+    let mass = rms(bodies, |body| body.mass);
+
+    let reference = simulate(bodies.to_vec());
+
+    let components = [
+        ("x", length),
+        ("y", length),
+        ("z", length),
+        ("vx", speed),
+        ("vy", speed),
+        ("vz", speed),
+        ("m", mass),
+    ];
+    let mut sensitivities = Vec::new();
+    for i in 0..bodies.len() {
+        for (k, (name, scale)) in components.into_iter().enumerate() {
+            let mut perturbed = bodies.to_vec();
+            *component(&mut perturbed[i], k) += step * scale;
+            let sensitivity = deviation(&simulate(perturbed), &reference, length, speed) / step;
+            sensitivities.push((sensitivity, i, name));
+        }
+    }
+    sensitivities.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    println!(
+        "Sensitivity of the final state at t = {} to a relative perturbation of {:e}",
+        duration, step
+    );
+    for (sensitivity, i, name) in sensitivities {
+        println!("  body {} {}: {:e}", i, name, sensitivity);
+    }
+}
+
+/// Root mean square of `f` over the bodies.
+pub(crate) fn rms(bodies: &[Body], f: impl Fn(&Body) -> f64) -> f64 {
+    (bodies.iter().map(|body| f(body).powi(2)).sum::<f64>() / bodies.len() as f64).sqrt()
+}
+
+/// Phase space distance between two states of the same bodies, with positions in units of `length`
+/// and velocities in units of `speed`, or infinity if any body has a different state because a
+/// collision was resolved differently.
+pub(crate) fn deviation(perturbed: &[Body], reference: &[Body], length: f64, speed: f64) -> f64 {
+    if perturbed
+        .iter()
+        .zip(reference)
+        .any(|(a, b)| a.state != b.state)
+    {
+        return f64::INFINITY;
+    }
+    perturbed
+        .iter()
+        .zip(reference)
+        .map(|(a, b)| {
+            (a.position - b.position).length_squared() / (length * length)
+                + (a.velocity - b.velocity).length_squared() / (speed * speed)
+        })
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// The `k`th initial condition component of `body`: position, then velocity, then mass.
+fn component(body: &mut Body, k: usize) -> &mut f64 {
+    match k {
+        0..=2 => &mut body.position[k],
+        3..=5 => &mut body.velocity[k - 3],
+        _ => &mut body.mass,
+    }
+}
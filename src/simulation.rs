@@ -0,0 +1,526 @@
+#[cfg(feature = "io")]
+use std::io;
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "io")]
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    flag,
+};
+
+use crate::{
+    body::{Body, State},
+    boundary::SimulationBox,
+    collision::CollisionMode,
+    cosmology::Cosmology,
+    event::{Event, Events},
+    force::Force,
+    observer::{Observer, Observers},
+    rewind::{History, Snapshot},
+    rng::Rng,
+    summation::kahan_sum,
+    vec3::{Point3, Vec3},
+};
+
+/// Flag for cooperatively cancelling a run, which can be cloned and cancelled from another thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests that runs using this token stop after their current tick.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Cancels this token when the process is interrupted with Ctrl-C or terminated. If it already
+    /// has been, the process exits immediately instead.
+    #[cfg(feature = "io")]
+    pub fn cancel_on_interrupt(&self) -> io::Result<()> {
+        for signal in [SIGINT, SIGTERM] {
+            flag::register_conditional_shutdown(signal, 130, Arc::clone(&self.0))?;
+            flag::register(signal, Arc::clone(&self.0))?;
+        }
+        Ok(())
+    }
+}
+
+/// Progress of a run, reported after each tick.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    /// Fraction of the run completed, from 0 to 1.
+    pub fraction: f64,
+    pub time: f64,
+    /// Mean number of ticks per second of wall-clock time since the run started.
+    pub ticks_per_second: f64,
+}
+
+/// State of a world after a tick, as yielded by [`Run`].
+#[derive(Clone, Debug)]
+pub struct Step {
+    ticks: u64,
+    time: f64,
+    bodies: Vec<Body>,
+}
+
+impl Step {
+    /// Number of ticks of the run so far, including this one.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn bodies(&self) -> &[Body] {
+        &self.bodies
+    }
+
+    pub fn into_bodies(self) -> Vec<Body> {
+        self.bodies
+    }
+}
+
+/// Iterator which ticks a world each time it is advanced and yields a copy of its state after the
+/// tick, so that runs can be sampled, stopped and collected with iterator adapters, e.g.
+/// `world.run(tick).take_while(|step| step.time() < duration)`. It never ends by itself.
+pub struct Run<'a, S: ?Sized> {
+    world: &'a mut S,
+    tick_duration: f64,
+    ticks: u64,
+}
+
+impl<'a, S: Simulation + ?Sized> Run<'a, S> {
+    pub fn new(world: &'a mut S, tick_duration: f64) -> Self {
+        Self {
+            world,
+            tick_duration,
+            ticks: 0,
+        }
+    }
+
+    /// The world being run, in its state after the last tick.
+    pub fn world(&self) -> &S {
+        self.world
+    }
+}
+
+impl<S: Simulation + ?Sized> Iterator for Run<'_, S> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        self.world.tick(self.tick_duration);
+        self.ticks += 1;
+        Some(Step {
+            ticks: self.ticks,
+            time: self.world.time(),
+            bodies: self.world.bodies().clone(),
+        })
+    }
+}
+
+/// State of a world besides its bodies and the configuration it was built with, which checkpoints
+/// save so that runs can be resumed exactly.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorldState {
+    pub time: f64,
+    /// Whether the integrator has offset velocities by half a tick, as leapfrog does before its
+    /// first tick.
+    pub started: bool,
+    /// Duration of the last tick, half of which leapfrog velocities lead the positions by, or zero
+    /// for integrators which don't need it.
+    #[serde(default)]
+    pub tick: f64,
+    /// Id given to the next body added.
+    pub next_id: usize,
+    pub cosmology: Option<Cosmology>,
+    /// Generators of the stochastic forces, in order.
+    pub rngs: Vec<Rng>,
+}
+
+/// Bodies of a world along with its state, which is what worlds are serialised as. Forces and
+/// the rest of the configuration can't be serialised in general, so a world is deserialised by
+/// building it with the same configuration as it was and restoring one of these into it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedWorld {
+    #[serde(flatten)]
+    pub state: WorldState,
+    pub bodies: Vec<Body>,
+}
+
+impl SavedWorld {
+    /// Replaces the bodies and state of `world` with these, keeping the ids of the bodies.
+    pub fn restore(self, world: &mut (impl Simulation + ?Sized)) {
+        *world.bodies_mut() = self.bodies;
+        world.restore_world_state(self.state);
+    }
+}
+
+/// Behaviour shared by the worlds of every integrator.
+pub trait Simulation {
+    fn tick(&mut self, tick_duration: f64);
+
+    fn time(&self) -> f64;
+
+    fn bodies(&self) -> &Vec<Body>;
+
+    fn bodies_mut(&mut self) -> &mut Vec<Body>;
+
+    fn forces(&self) -> &[Box<dyn Force>];
+
+    /// Description of the update equations of the integrator, for explaining steps.
+    fn scheme(&self) -> &'static str;
+
+    /// Enables detection and resolution of collisions between bodies with a radius. The
+    /// coefficient of `restitution` is used for bouncing collisions.
+    fn set_collision_mode(&mut self, mode: CollisionMode, restitution: f64);
+
+    /// Index of the body with `id`, if it is in this world.
+    fn index_of(&self, id: usize) -> Option<usize> {
+        self.bodies().iter().position(|body| body.id == id)
+    }
+
+    /// Adds `body` to this world with a new id, which is returned. Ids are never reused, even once
+    /// their bodies have been removed.
+    fn add_body(&mut self, body: Body) -> usize;
+
+    /// Removes the body with `id` from this world, returning it if there was one. Bodies after it
+    /// move down an index, but keep their ids, by which forces refer to them. Unlike bodies which merge or escape during a run,
+    /// which are kept with their state recording what happened to them, removed bodies no longer
+    /// appear in outputs.
+    fn remove_body(&mut self, id: usize) -> Option<Body> {
+        let i = self.index_of(id)?;
+        Some(self.bodies_mut().remove(i))
+    }
+
+    /// Events of this world, including collisions and any escapes and close encounters it has
+    /// been set to detect.
+    fn events(&self) -> &Events;
+
+    fn events_mut(&mut self) -> &mut Events;
+
+    /// Takes the events which occurred since this was last called.
+    fn take_events(&mut self) -> Vec<Event> {
+        self.events_mut().take()
+    }
+
+    fn simulation_box(&self) -> Option<SimulationBox>;
+
+    /// Confines the bodies to a box. In a periodic box, pairwise interactions built with the same
+    /// box use the minimum image.
+    fn set_simulation_box(&mut self, simulation_box: SimulationBox);
+
+    fn cosmology(&self) -> Option<Cosmology>;
+
+    /// Integrates in comoving coordinates against the expanding background of `cosmology`.
+    fn set_cosmology(&mut self, cosmology: Cosmology);
+
+    fn world_state(&self) -> WorldState;
+
+    /// Restores state saved by `world_state` to a world built with the same configuration.
+    fn restore_world_state(&mut self, state: WorldState);
+
+    /// Bodies and state of this world, for serialising it.
+    fn save(&self) -> SavedWorld {
+        SavedWorld {
+            state: self.world_state(),
+            bodies: self.bodies().clone(),
+        }
+    }
+
+    /// Observers called after ticks of this world.
+    fn observers_mut(&mut self) -> &mut Observers;
+
+    /// Registers `observer` to be called with this world after every `every` ticks. Panics if
+    /// `every` is zero.
+    fn add_observer(&mut self, every: usize, observer: impl Observer + 'static)
+    where
+        Self: Sized,
+    {
+        self.observers_mut().add(every, observer);
+    }
+
+    /// Registers a closure to be called with this world after every `every` ticks. Panics if
+    /// `every` is zero.
+    fn on_tick(&mut self, every: usize, f: impl FnMut(&dyn Simulation) + 'static)
+    where
+        Self: Sized,
+    {
+        self.add_observer(every, f);
+    }
+
+    /// Calls the observers which are due, which every integrator does at the end of each tick.
+    fn notify_observers(&mut self)
+    where
+        Self: Sized,
+    {
+        let mut observers = std::mem::take(self.observers_mut());
+        observers.notify(self);
+        *self.observers_mut() = observers;
+    }
+
+    /// Copy of the state of this world which it can be rewound to with `restore`. Unlike `save`,
+    /// it includes which events have been detected.
+    fn snapshot(&self) -> Snapshot {
+        Snapshot::new(self.save(), self.events().detected())
+    }
+
+    /// Rewinds this world to `snapshot`, which must be of a world with the same configuration.
+    /// Observers aren't rewound, so a history kept of this world should be truncated to the time
+    /// of the snapshot with [`History::truncate_after`].
+    fn restore(&mut self, snapshot: &Snapshot) {
+        snapshot.saved().clone().restore(self);
+        self.events_mut()
+            .restore_detected(snapshot.detected().clone());
+    }
+
+    /// Takes a snapshot after every `every` ticks, keeping the latest `capacity` of them in the
+    /// returned history. Panics if either is zero.
+    fn keep_history(&mut self, every: usize, capacity: usize) -> History
+    where
+        Self: Sized,
+    {
+        let history = History::new(capacity);
+        self.add_observer(every, history.clone());
+        history
+    }
+
+    /// Iterator over the states of this world after each of its ticks from now on.
+    fn run(&mut self, tick_duration: f64) -> Run<'_, Self>
+    where
+        Self: Sized,
+    {
+        Run::new(self, tick_duration)
+    }
+
+    /// Ticks until the simulation time reaches `end` or `cancel` is cancelled, calling `progress`
+    /// after every tick. Returns whether `end` was reached.
+    fn run_until(
+        &mut self,
+        end: f64,
+        tick_duration: f64,
+        cancel: &CancellationToken,
+        progress: &mut dyn FnMut(Progress),
+    ) -> bool {
+        let start = self.time();
+        let started = Instant::now();
+        let mut ticks = 0;
+        while self.time() < end {
+            if cancel.is_cancelled() {
+                return false;
+            }
+            self.tick(tick_duration);
+            ticks += 1;
+            progress(Progress {
+                fraction: ((self.time() - start) / (end - start)).min(1.),
+                time: self.time(),
+                ticks_per_second: ticks as f64 / started.elapsed().as_secs_f64(),
+            });
+        }
+        true
+    }
+
+    /// Moves bodies which have left the box, if any, back into it.
+    fn confine_bodies(&mut self) {
+        if let Some(simulation_box) = self.simulation_box() {
+            for body in self.bodies_mut() {
+                simulation_box.confine(body);
+            }
+        }
+    }
+
+    /// Bodies with velocities at the same time as their positions, which is what the velocities
+    /// of the diagnostics below are taken from. Integrators whose velocities are staggered from
+    /// their positions, like leapfrog's at half ticks, override this to synchronise them, since
+    /// pairing positions with velocities from half a tick away makes energy errors first order in
+    /// the tick rather than the integrator's own order.
+    fn synchronised_bodies(&self) -> Cow<'_, [Body]> {
+        Cow::Borrowed(self.bodies())
+    }
+
+    /// Kinetic energy of the active bodies, in units of G times energy like the Gm masses. This and
+    /// the other conserved quantities are summed with Kahan compensation, so that their drift
+    /// isn't swamped by rounding in large systems.
+    fn kinetic_energy(&self) -> f64 {
+        kahan_sum(
+            self.synchronised_bodies()
+                .iter()
+                .filter(|body| body.is_active())
+                .map(|body| body.mass * body.velocity.length_squared() / 2.),
+        )
+    }
+
+    /// Potential energy of the active bodies due to every force with a potential, in units of G
+    /// times energy.
+    fn potential_energy(&self) -> f64 {
+        kahan_sum(
+            self.forces()
+                .iter()
+                .map(|force| force.potential_energy(self.bodies())),
+        )
+    }
+
+    fn total_energy(&self) -> f64 {
+        self.kinetic_energy() + self.potential_energy()
+    }
+
+    /// Virial ratio 2T/|U| of kinetic to potential energy, which is 1 for a system in equilibrium.
+    fn virial_ratio(&self) -> f64 {
+        2. * self.kinetic_energy() / self.potential_energy().abs()
+    }
+
+    /// Mean velocity of the active bodies, weighting each equally.
+    fn mean_velocity(&self) -> Vec3 {
+        let bodies = self.synchronised_bodies();
+        let active = bodies.iter().filter(|body| body.is_active());
+        let count = active.clone().count();
+        active.map(|body| body.velocity).sum::<Vec3>() / count as f64
+    }
+
+    /// Mean speed of the active bodies relative to their mean velocity.
+    fn mean_speed(&self) -> f64 {
+        let mean = self.mean_velocity();
+        let speeds: Vec<f64> = self
+            .synchronised_bodies()
+            .iter()
+            .filter(|body| body.is_active())
+            .map(|body| (body.velocity - mean).length())
+            .collect();
+        speeds.iter().sum::<f64>() / speeds.len() as f64
+    }
+
+    /// One-dimensional velocity dispersion of the active bodies, the root mean square deviation
+    /// from their mean velocity divided by √3.
+    fn velocity_dispersion(&self) -> f64 {
+        let mean = self.mean_velocity();
+        let squares: Vec<f64> = self
+            .synchronised_bodies()
+            .iter()
+            .filter(|body| body.is_active())
+            .map(|body| (body.velocity - mean).length_squared())
+            .collect();
+        (squares.iter().sum::<f64>() / (3. * squares.len() as f64)).sqrt()
+    }
+
+    /// Total momentum of the active bodies, in units of G times momentum like the Gm masses.
+    fn total_momentum(&self) -> Vec3 {
+        kahan_sum(
+            self.synchronised_bodies()
+                .iter()
+                .filter(|body| body.is_active())
+                .map(|body| body.mass * body.velocity),
+        )
+    }
+
+    fn total_mass(&self) -> f64 {
+        kahan_sum(
+            self.bodies()
+                .iter()
+                .filter(|body| body.is_active())
+                .map(|body| body.mass),
+        )
+    }
+
+    /// Centre of mass of the active bodies.
+    fn center_of_mass(&self) -> Point3 {
+        let moment = kahan_sum(
+            self.bodies()
+                .iter()
+                .filter(|body| body.is_active())
+                .map(|body| body.mass * body.position),
+        );
+        moment / self.total_mass()
+    }
+
+    /// Total angular momentum of the active bodies about their barycentre, in units of G times
+    /// angular momentum like the Gm masses.
+    fn angular_momentum(&self) -> Vec3 {
+        let center = self.center_of_mass();
+        let velocity = self.total_momentum() / self.total_mass();
+        kahan_sum(
+            self.synchronised_bodies()
+                .iter()
+                .filter(|body| body.is_active())
+                .map(|body| {
+                    body.mass * Vec3::cross(&(body.position - center), &(body.velocity - velocity))
+                }),
+        )
+    }
+
+    /// Converts into the Galilean rest frame of the body at index i. Panics if there is no such
+    /// body.
+    fn transform_to_rest_frame(&mut self, i: usize) {
+        let reference = &self.bodies()[i];
+        let (r_position, r_velocity) = (reference.position, reference.velocity);
+        for body in self.bodies_mut() {
+            body.position -= r_position;
+            body.velocity -= r_velocity;
+        }
+    }
+
+    /// FNV-1a hash of the exact bit patterns of the time, every property of every body which
+    /// affects the simulation, the scale factor of any cosmology and the states of the generators
+    /// of stochastic forces, so that runs can be checked to reproduce each other bit-for-bit.
+    /// Names, colours and ids are left out.
+    fn state_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut write = |bits: u64| {
+            for byte in bits.to_le_bytes() {
+                hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+            }
+        };
+        write(self.time().to_bits());
+        for body in self.bodies() {
+            for v in [body.position, body.velocity] {
+                write(v.x().to_bits());
+                write(v.y().to_bits());
+                write(v.z().to_bits());
+            }
+            for value in [
+                body.mass,
+                body.radius,
+                body.mass_rate,
+                body.charge,
+                body.softening,
+            ] {
+                write(value.to_bits());
+            }
+            let state = match body.state {
+                State::Active => 0,
+                State::Merged { into } => 1 | (into as u64) << 2,
+                State::Removed => 2,
+                State::Escaped { time } => {
+                    write(time.to_bits());
+                    3
+                }
+            };
+            write(body.test as u64 | (body.fixed as u64) << 1 | state << 2);
+        }
+        let state = self.world_state();
+        if let Some(cosmology) = state.cosmology {
+            write(cosmology.scale_factor().to_bits());
+        }
+        for rng in state.rngs {
+            for word in rng.state() {
+                write(word);
+            }
+        }
+        hash
+    }
+}
@@ -0,0 +1,33 @@
+use crate::body::Body;
+
+/// Sets the softening length of every active body to `factor` times the distance to its
+/// `neighbours`th nearest active neighbour, so that gravity is resolved more finely where bodies
+/// are denser.
+pub fn adapt(bodies: &mut [Body], neighbours: usize, factor: f64) {
+    let softenings: Vec<Option<f64>> = bodies
+        .iter()
+        .enumerate()
+        .map(|(i, body)| {
+            if !body.is_active() {
+                return None;
+            }
+            let mut distances: Vec<f64> = bodies
+                .iter()
+                .enumerate()
+                .filter(|(j, other)| i != *j && other.is_active())
+                .map(|(_, other)| (body.position - other.position).length())
+                .collect();
+            if distances.len() < neighbours {
+                return None;
+            }
+            let (_, distance, _) =
+                distances.select_nth_unstable_by(neighbours - 1, |a, b| a.total_cmp(b));
+            Some(factor * *distance)
+        })
+        .collect();
+    for (body, softening) in bodies.iter_mut().zip(softenings) {
+        if let Some(softening) = softening {
+            body.softening = softening;
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Compensated summation, for sums of many terms whose rounding errors would otherwise accumulate.
+
+use std::ops::{Add, AddAssign, Sub};
+
+/// Running sum with Kahan compensation, which carries the rounding error of each addition into
+/// the next, so that the error of a sum of n terms is a few ulps of the sum of their magnitudes
+/// rather than growing with n. Vectors are compensated component by component. It uses only
+/// addition and subtraction, so it is as deterministic as a plain sum.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KahanSum<T> {
+    sum: T,
+    /// Rounding error of the additions so far, to be subtracted from the next term.
+    compensation: T,
+}
+
+impl<T: Copy + Default + Add<Output = T> + Sub<Output = T>> KahanSum<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn total(&self) -> T {
+        self.sum
+    }
+}
+
+impl<T: Copy + Add<Output = T> + Sub<Output = T>> AddAssign<T> for KahanSum<T> {
+    fn add_assign(&mut self, term: T) {
+        let y = term - self.compensation;
+        let t = self.sum + y;
+        self.compensation = (t - self.sum) - y;
+        self.sum = t;
+    }
+}
+
+/// Sum of `terms` with Kahan compensation.
+pub fn kahan_sum<T: Copy + Default + Add<Output = T> + Sub<Output = T>>(
+    terms: impl IntoIterator<Item = T>,
+) -> T {
+    let mut sum = KahanSum::new();
+    for term in terms {
+        sum += term;
+    }
+    sum.total()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vec3;
+
+    /// One followed by a million terms each below half an ulp of one, which a plain sum drops.
+    fn terms() -> impl Iterator<Item = f64> {
+        std::iter::once(1.).chain(std::iter::repeat_n(1e-16, 1_000_000))
+    }
+
+    #[test]
+    fn recovers_terms_a_plain_sum_drops() {
+        let exact = 1. + 1e-10;
+        let plain: f64 = terms().sum();
+        let compensated = kahan_sum(terms());
+        assert_eq!(plain, 1.);
+        assert!((compensated - exact).abs() <= 2. * f64::EPSILON);
+    }
+
+    #[test]
+    fn compensates_vectors_by_component() {
+        let compensated = kahan_sum(terms().map(|t| Vec3::new(t, -t, 0.)));
+        assert!((compensated.x() - (1. + 1e-10)).abs() <= 2. * f64::EPSILON);
+        assert_eq!(compensated.y(), -compensated.x());
+        assert_eq!(compensated.z(), 0.);
+    }
+
+    #[test]
+    fn running_sum_matches_kahan_sum() {
+        let mut sum = KahanSum::new();
+        for term in terms() {
+            sum += term;
+        }
+        assert_eq!(sum.total(), kahan_sum(terms()));
+    }
+}
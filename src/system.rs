@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::{is_false, is_zero, Body},
+    elements::Elements,
+    vec3::{Point3, Vec3},
+};
+
+/// Initial conditions in TOML or JSON, with optional names and colours for the bodies and settings
+/// for the whole system. For example
+///
+/// ```toml
+/// G = 6.6743e-11
+///
+/// [[bodies]]
+/// name = "Sun"
+/// position = [0, 0, 0]
+/// mass = 1.9885e30
+/// color = "#ffcc00"
+/// size = 6
+///
+/// [[bodies]]
+/// name = "Earth"
+/// position = [1.496e11, 0, 0]
+/// velocity = [0, 2.978e4, 0]
+/// mass = 5.972e24
+/// radius = 6.371e6
+/// ```
+///
+/// Instead of a position and velocity, a body may be given the Keplerian elements of its orbit
+/// around a named body before it, or by default around the barycentre of all the bodies before it,
+/// which gives Jacobi coordinates for hierarchical systems. For example
+///
+/// ```toml
+/// [[bodies]]
+/// name = "Moon"
+/// mass = 7.342e22
+/// orbit = { around = "Earth", a = 3.844e8, e = 0.0549, i = 5.145 }
+/// ```
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct System {
+    /// Gravitational constant by which masses are converted to Gm.
+    #[serde(rename = "G", skip_serializing_if = "Option::is_none")]
+    pub g: Option<f64>,
+    /// Plummer softening length of bodies which don't have their own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub softening: Option<f64>,
+    pub bodies: Vec<SystemBody>,
+}
+
+/// Body of a `System`, with the same properties as the CSV input.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SystemBody {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Position, which is required unless an `orbit` is given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<[f64; 3]>,
+    /// Velocity, which is zero by default and can't be given with an `orbit`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<[f64; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orbit: Option<Orbit>,
+    pub mass: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub radius: f64,
+    /// Colour for plotting, such as `#ffcc00`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Radius in pixels of the body's dot in plots.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u32>,
+    /// Number of previous frames over which the body's path is drawn behind it in animations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trail: Option<u32>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub test: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub fixed: bool,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub mass_rate: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub charge: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub softening: Option<f64>,
+}
+
+/// Keplerian orbit of a `SystemBody`, with angles in degrees.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Orbit {
+    /// Name of the primary, which must come earlier in the system. The barycentre of every earlier
+    /// body is orbited if there is none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub around: Option<String>,
+    /// Semi-major axis, negative for hyperbolic orbits.
+    pub a: f64,
+    #[serde(default)]
+    pub e: f64,
+    /// Inclination.
+    #[serde(default)]
+    pub i: f64,
+    /// Longitude of the ascending node.
+    #[serde(default)]
+    pub node: f64,
+    /// Argument of periapsis.
+    #[serde(default)]
+    pub periapsis: f64,
+    /// Mean anomaly.
+    #[serde(default)]
+    pub anomaly: f64,
+}
+
+impl System {
+    pub fn from_toml(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    pub fn from_json(s: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(s)
+    }
+
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Bodies of the system, with masses (and their rates of change) converted to Gm with the
+    /// gravitational constant `g`.
+    pub fn bodies(&self, g: f64) -> Result<Vec<Body>, String> {
+        let states = self.states(g)?;
+        Ok((self.bodies.iter().zip(states))
+            .map(|(body, (position, velocity))| Body {
+                radius: body.radius,
+                test: body.test,
+                fixed: body.fixed,
+                mass_rate: body.mass_rate * g,
+                charge: body.charge,
+                softening: body.softening.or(self.softening).unwrap_or(0.),
+                name: body.name.clone(),
+                color: body.color.clone(),
+                size: body.size,
+                trail: body.trail,
+                ..Body::new(position, velocity, body.mass * g)
+            })
+            .collect())
+    }
+
+    /// The same system with every orbit replaced by the position and velocity it gives.
+    pub fn to_cartesian(&self, g: f64) -> Result<Self, String> {
+        let states = self.states(g)?;
+        let bodies = (self.bodies.iter().zip(states))
+            .map(|(body, (position, velocity))| SystemBody {
+                position: Some([position.x(), position.y(), position.z()]),
+                velocity: Some([velocity.x(), velocity.y(), velocity.z()]),
+                orbit: None,
+                ..body.clone()
+            })
+            .collect();
+        Ok(Self {
+            g: self.g,
+            softening: self.softening,
+            bodies,
+        })
+    }
+
+    /// Positions and velocities of the bodies, with orbits converted from their elements.
+    fn states(&self, g: f64) -> Result<Vec<(Point3, Vec3)>, String> {
+        let mut states: Vec<(Point3, Vec3)> = Vec::with_capacity(self.bodies.len());
+        for (k, body) in self.bodies.iter().enumerate() {
+            let label = body.name.clone().unwrap_or_else(|| format!("body {}", k));
+            let state = match (&body.orbit, body.position) {
+                (None, Some([x, y, z])) => {
+                    let [vx, vy, vz] = body.velocity.unwrap_or_default();
+                    (Point3::new(x, y, z), Vec3::new(vx, vy, vz))
+                }
+                (Some(orbit), None) if body.velocity.is_none() => {
+                    // Gm, position and velocity of the primary
+                    let (mass, position, velocity) = match &orbit.around {
+                        Some(name) => {
+                            let j = (self.bodies[..k].iter())
+                                .position(|other| other.name.as_ref() == Some(name))
+                                .ok_or_else(|| {
+                                    format!("{} orbits {:?}, which isn't before it", label, name)
+                                })?;
+                            (self.bodies[j].mass * g, states[j].0, states[j].1)
+                        }
+                        None => {
+                            let mass: f64 = self.bodies[..k].iter().map(|b| b.mass * g).sum();
+                            let moment = |f: fn(&(Point3, Vec3)) -> Vec3| -> Vec3 {
+                                (self.bodies[..k].iter().zip(&states))
+                                    .map(|(b, state)| b.mass * g * f(state))
+                                    .sum()
+                            };
+                            (
+                                mass,
+                                moment(|state| state.0) / mass,
+                                moment(|state| state.1) / mass,
+                            )
+                        }
+                    };
+                    if mass <= 0. || mass.is_nan() {
+                        return Err(format!("{} orbits nothing with mass", label));
+                    }
+                    let elements = Elements {
+                        a: orbit.a,
+                        e: orbit.e,
+                        i: orbit.i.to_radians(),
+                        node: orbit.node.to_radians(),
+                        periapsis: orbit.periapsis.to_radians(),
+                        anomaly: orbit.anomaly.to_radians(),
+                    };
+                    let (relative_position, relative_velocity) = elements
+                        .to_cartesian(mass + body.mass * g)
+                        .map_err(|e| format!("invalid orbit of {}: {}", label, e))?;
+                    (position + relative_position, velocity + relative_velocity)
+                }
+                _ => {
+                    return Err(format!(
+                        "{} needs either a position and optional velocity, or an orbit",
+                        label
+                    ))
+                }
+            };
+            states.push(state);
+        }
+        Ok(states)
+    }
+}
@@ -0,0 +1,136 @@
+use std::{f64::consts::TAU, ops::RangeInclusive};
+
+use crate::{body::Body, elements::Elements};
+
+/// Satellite from a two-line element set, with the mean elements relative to the Earth's equator.
+#[derive(Clone, Debug)]
+pub struct Tle {
+    pub name: String,
+    /// Days since 1 January 2000.
+    pub epoch: f64,
+    /// Mean motion in revolutions per day.
+    pub mean_motion: f64,
+    /// Elements in degrees, with a semi-major axis of zero since it depends on the Earth's Gm.
+    pub elements: Elements,
+}
+
+/// Parses two-line element sets, each optionally preceded by a line with the satellite's name.
+/// Errors give the line number of the problem.
+pub fn parse(text: &str) -> Result<Vec<Tle>, String> {
+    let lines: Vec<(usize, &str)> = (text.lines().enumerate())
+        .map(|(i, line)| (i + 1, line.trim_end()))
+        .filter(|(_, line)| !line.trim().is_empty())
+        .collect();
+    let mut tles = Vec::new();
+    let mut k = 0;
+    while k < lines.len() {
+        let name = match lines[k].1.starts_with("1 ") {
+            true => None,
+            false => {
+                k += 1;
+                Some(lines[k - 1].1.trim().to_string())
+            }
+        };
+        let (Some(&(n1, line1)), Some(&(n2, line2))) = (lines.get(k), lines.get(k + 1)) else {
+            return Err(format!(
+                "line {}: incomplete element set",
+                lines[lines.len() - 1].0
+            ));
+        };
+        k += 2;
+        check(n1, line1, '1')?;
+        check(n2, line2, '2')?;
+        // Columns are numbered from 1
+        let field = |number: usize, line: &str, columns: RangeInclusive<usize>| {
+            let value = line[columns.start() - 1..*columns.end()].trim();
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("line {}: invalid value `{}`", number, value))
+        };
+
+        let year = field(n1, line1, 19..=20)?;
+        let year = if year < 57. {
+            2000. + year
+        } else {
+            1900. + year
+        };
+        let day = field(n1, line1, 21..=32)?;
+        let epoch = days_from_2000(year as i64) + day - 1.;
+        let mean_motion = field(n2, line2, 53..=63)?;
+        if mean_motion <= 0. {
+            return Err(format!("line {}: mean motion must be positive", n2));
+        }
+        let elements = Elements {
+            a: 0.,
+            e: field(n2, line2, 27..=33).map(|e| e * 1e-7)?,
+            i: field(n2, line2, 9..=16)?,
+            node: field(n2, line2, 18..=25)?,
+            periapsis: field(n2, line2, 35..=42)?,
+            anomaly: field(n2, line2, 44..=51)?,
+        };
+        let name = name.unwrap_or_else(|| line1[2..7].trim().to_string());
+        tles.push(Tle {
+            name,
+            epoch,
+            mean_motion,
+            elements,
+        });
+    }
+    Ok(tles)
+}
+
+/// Checks the line number, length and modulo 10 checksum of a line of an element set.
+fn check(number: usize, line: &str, expected: char) -> Result<(), String> {
+    if !line.starts_with(expected) || line.len() < 69 || !line.is_ascii() {
+        return Err(format!(
+            "line {}: expected line {} of an element set",
+            number, expected
+        ));
+    }
+    let sum: u32 = (line[..68].chars())
+        .map(|c| match c {
+            '-' => 1,
+            _ => c.to_digit(10).unwrap_or(0),
+        })
+        .sum();
+    match line[68..69].parse::<u32>() {
+        Ok(checksum) if checksum == sum % 10 => Ok(()),
+        _ => Err(format!("line {}: checksum is wrong", number)),
+    }
+}
+
+/// Days from 1 January 2000 to 1 January of `year`.
+fn days_from_2000(year: i64) -> f64 {
+    let leap_days =
+        |y: i64| (y - 1).div_euclid(4) - (y - 1).div_euclid(100) + (y - 1).div_euclid(400);
+    (365 * (year - 2000) + leap_days(year) - leap_days(2000)) as f64
+}
+
+/// Test particles for the satellites, orbiting `earth` on Keplerian orbits which are advanced to
+/// the latest epoch among them. `day` is the length of a day in the units of the simulation.
+pub fn satellites(tles: &[Tle], earth: &Body, day: f64) -> Result<Vec<Body>, String> {
+    let epoch = tles
+        .iter()
+        .map(|tle| tle.epoch)
+        .fold(f64::NEG_INFINITY, f64::max);
+    (tles.iter())
+        .map(|tle| {
+            let n = TAU * tle.mean_motion / day;
+            let elements = Elements {
+                a: (earth.mass / (n * n)).cbrt(),
+                i: tle.elements.i.to_radians(),
+                node: tle.elements.node.to_radians(),
+                periapsis: tle.elements.periapsis.to_radians(),
+                anomaly: tle.elements.anomaly.to_radians() + n * (epoch - tle.epoch) * day,
+                ..tle.elements
+            };
+            let (position, velocity) = (elements.to_cartesian(earth.mass))
+                .map_err(|e| format!("invalid orbit of {}: {}", tle.name, e))?;
+            Ok(Body {
+                test: true,
+                name: Some(tle.name.clone()),
+                ..Body::new(earth.position + position, earth.velocity + velocity, 0.)
+            })
+        })
+        .collect()
+}
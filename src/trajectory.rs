@@ -0,0 +1,359 @@
+use std::io::Read;
+#[cfg(feature = "io")]
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    body::Body,
+    vec3::{Point3, Vec3},
+};
+#[cfg(feature = "io")]
+use crate::{
+    compress::{self, AtomicFile, Compression, OutputFile},
+    vtk,
+};
+
+/// Writes the trajectories of the active bodies to a CSV file in long format, with one row per body
+/// per output time, or to a numbered series of uncompressed VTK files if the path has a `.vtk`
+/// extension. The CSV file only appears at its path once finished.
+///
+/// CSV files have the columns `time`, `body`, `pos_x`, `pos_y`, `pos_z`, `vel_x`, `vel_y`, `vel_z`
+/// and `mass`, followed by `color`, `size` and `trail` once any body written has one. A column
+/// which first appears partway through a run, such as for a body added with its own colour, is
+/// added to the header and left empty in the rows written before it, so that the finished file
+/// always has a single header. Readers fill in columns missing from older files with defaults.
+#[cfg(feature = "io")]
+pub struct TrajectoryWriter {
+    output: Output,
+    schedule: OutputSchedule,
+    /// Whether to sync each file to disk before it replaces its path.
+    sync: bool,
+}
+
+/// Decides which ticks are written by an output at a fixed interval of simulation time.
+pub struct OutputSchedule {
+    /// Simulation time between outputs, or None to write every tick.
+    interval: Option<f64>,
+    /// Number of outputs written so far.
+    outputs: u64,
+}
+
+impl OutputSchedule {
+    pub fn new(interval: Option<f64>) -> Self {
+        Self {
+            interval,
+            outputs: 0,
+        }
+    }
+
+    /// Returns the number of the output due at `time`, if any, on the first tick at or within half
+    /// a tick of each multiple of the interval.
+    pub fn due(&mut self, time: f64, tick: f64) -> Option<u64> {
+        if let Some(interval) = self.interval {
+            if time + tick / 2. < self.outputs as f64 * interval {
+                return None;
+            }
+            // Skip any outputs which fell within a single tick
+            self.outputs = ((time + tick / 2.) / interval).floor() as u64;
+        }
+        self.outputs += 1;
+        Some(self.outputs - 1)
+    }
+}
+
+#[cfg(feature = "io")]
+enum Output {
+    Csv(Box<CsvOutput>),
+    /// VTK files named after this path with the output number appended to the stem.
+    Vtk(PathBuf),
+}
+
+#[derive(Deserialize)]
+struct TrajectoryRow {
+    time: f64,
+    body: usize,
+    pos_x: f64,
+    pos_y: f64,
+    pos_z: f64,
+    vel_x: f64,
+    vel_y: f64,
+    vel_z: f64,
+    /// Gm, as used internally. Missing from files written before it was added.
+    #[serde(default)]
+    mass: f64,
+    /// Colour of the body for plotting, if it has one. Missing from files written before it was
+    /// added.
+    #[serde(default)]
+    color: Option<String>,
+    /// Radius in pixels and trail length of the body for plotting, if it has them. Missing from
+    /// files written before they were added.
+    #[serde(default)]
+    size: Option<u32>,
+    #[serde(default)]
+    trail: Option<u32>,
+}
+
+#[cfg(feature = "io")]
+impl TrajectoryWriter {
+    pub fn create(
+        path: impl AsRef<Path>,
+        interval: Option<f64>,
+        compression: Option<Compression>,
+        sync: bool,
+    ) -> Result<Self, csv::Error> {
+        let path = path.as_ref();
+        let output = if path.extension().is_some_and(|extension| extension == "vtk") {
+            Output::Vtk(path.to_path_buf())
+        } else {
+            Output::Csv(Box::new(CsvOutput {
+                writer: csv::Writer::from_writer(compress::create(path, compression, sync)?),
+                path: path.to_path_buf(),
+                compression,
+                columns: None,
+            }))
+        };
+        Ok(Self {
+            output,
+            schedule: OutputSchedule::new(interval),
+            sync,
+        })
+    }
+
+    /// Writes the bodies at `time` if an output is due.
+    pub fn record(&mut self, time: f64, tick: f64, bodies: &[Body]) -> Result<(), csv::Error> {
+        let Some(output) = self.schedule.due(time, tick) else {
+            return Ok(());
+        };
+        let csv = match &mut self.output {
+            Output::Csv(csv) => csv,
+            Output::Vtk(path) => {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let path = path.with_file_name(format!("{}_{:04}.vtk", stem, output));
+                let mut file = AtomicFile::create(path, self.sync)?;
+                vtk::write(&mut file, time, bodies)?;
+                file.finish()?;
+                return Ok(());
+            }
+        };
+        let rows: Vec<TrajectoryRow> = (bodies.iter().enumerate())
+            .filter(|(_, body)| body.is_active())
+            .map(|(i, body)| TrajectoryRow {
+                time,
+                body: i,
+                pos_x: body.position.x(),
+                pos_y: body.position.y(),
+                pos_z: body.position.z(),
+                vel_x: body.velocity.x(),
+                vel_y: body.velocity.y(),
+                vel_z: body.velocity.z(),
+                mass: body.mass,
+                color: body.color.clone(),
+                size: body.size,
+                trail: body.trail,
+            })
+            .collect();
+        csv.write(&rows, self.sync)?;
+        // Keep the temporary file complete so far in case the run is interrupted
+        csv.writer.flush()?;
+        Ok(())
+    }
+
+    /// Flushes the CSV file and replaces its path with it.
+    pub fn finish(self) -> Result<(), csv::Error> {
+        if let Output::Csv(csv) = self.output {
+            csv.writer
+                .into_inner()
+                .map_err(|e| e.into_error())?
+                .finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Trajectory CSV file, with the optional columns which have been written so far.
+#[cfg(feature = "io")]
+struct CsvOutput {
+    writer: csv::Writer<OutputFile>,
+    path: PathBuf,
+    compression: Option<Compression>,
+    /// None until the header is written with the first output.
+    columns: Option<Columns>,
+}
+
+#[cfg(feature = "io")]
+impl CsvOutput {
+    /// Appends `rows`, first widening the header if any of them has a new optional column.
+    fn write(&mut self, rows: &[TrajectoryRow], sync: bool) -> Result<(), csv::Error> {
+        let columns = Columns::of(rows).union(self.columns.unwrap_or_default());
+        match self.columns {
+            None => self.writer.write_record(columns.header())?,
+            Some(written) if written != columns => self.rewrite(columns, sync)?,
+            Some(_) => {}
+        }
+        self.columns = Some(columns);
+        for row in rows {
+            self.writer.write_record(columns.record(row))?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the temporary file with the rows written so far under a header with `columns`.
+    /// New columns are rare, so the rows are read back into memory rather than copied through
+    /// another file.
+    fn rewrite(&mut self, columns: Columns, sync: bool) -> Result<(), csv::Error> {
+        self.writer.flush()?;
+        let path = compress::compressed_path(&self.path, self.compression);
+        let rows: Vec<TrajectoryRow> =
+            csv::Reader::from_reader(compress::open(compress::temporary_path(path))?)
+                .deserialize()
+                .collect::<Result<_, _>>()?;
+        let file = compress::create(&self.path, self.compression, sync)?;
+        self.writer = csv::Writer::from_writer(file);
+        self.writer.write_record(columns.header())?;
+        for row in &rows {
+            self.writer.write_record(columns.record(row))?;
+        }
+        Ok(())
+    }
+}
+
+/// Optional columns of a trajectory CSV file, which are only written once a body has a value for
+/// them.
+#[cfg(feature = "io")]
+#[derive(Clone, Copy, Default, PartialEq)]
+struct Columns {
+    color: bool,
+    size: bool,
+    trail: bool,
+}
+
+#[cfg(feature = "io")]
+impl Columns {
+    fn of(rows: &[TrajectoryRow]) -> Self {
+        Self {
+            color: rows.iter().any(|row| row.color.is_some()),
+            size: rows.iter().any(|row| row.size.is_some()),
+            trail: rows.iter().any(|row| row.trail.is_some()),
+        }
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            color: self.color || other.color,
+            size: self.size || other.size,
+            trail: self.trail || other.trail,
+        }
+    }
+
+    fn header(self) -> Vec<&'static str> {
+        let mut header = vec![
+            "time", "body", "pos_x", "pos_y", "pos_z", "vel_x", "vel_y", "vel_z", "mass",
+        ];
+        for (column, name) in [
+            (self.color, "color"),
+            (self.size, "size"),
+            (self.trail, "trail"),
+        ] {
+            if column {
+                header.push(name);
+            }
+        }
+        header
+    }
+
+    /// Fields of `row`, with values formatted to read back exactly and missing ones left empty.
+    fn record(self, row: &TrajectoryRow) -> Vec<String> {
+        let mut record = vec![format!("{:?}", row.time), row.body.to_string()];
+        let values = [
+            row.pos_x, row.pos_y, row.pos_z, row.vel_x, row.vel_y, row.vel_z, row.mass,
+        ];
+        record.extend(values.map(|value| format!("{:?}", value)));
+        if self.color {
+            record.push(row.color.clone().unwrap_or_default());
+        }
+        let number = |value: Option<u32>| value.map_or(String::new(), |value| value.to_string());
+        if self.size {
+            record.push(number(row.size));
+        }
+        if self.trail {
+            record.push(number(row.trail));
+        }
+        record
+    }
+}
+
+/// Active bodies at one output of a trajectory.
+pub struct Frame {
+    pub time: f64,
+    /// Index of each body in the simulation, with its position, velocity, Gm and plotting style.
+    pub bodies: Vec<(usize, Body)>,
+}
+
+/// Reads a trajectory CSV file written by a `TrajectoryWriter`, returning its outputs in order.
+pub fn read(reader: impl Read) -> Result<Vec<Frame>, csv::Error> {
+    let mut frames: Vec<Frame> = Vec::new();
+    for row in csv::Reader::from_reader(reader).deserialize() {
+        let row: TrajectoryRow = row?;
+        let body = Body {
+            color: row.color,
+            size: row.size,
+            trail: row.trail,
+            ..Body::new(
+                Point3::new(row.pos_x, row.pos_y, row.pos_z),
+                Vec3::new(row.vel_x, row.vel_y, row.vel_z),
+                row.mass,
+            )
+        };
+        match frames.last_mut() {
+            Some(frame) if frame.time == row.time => frame.bodies.push((row.body, body)),
+            _ => frames.push(Frame {
+                time: row.time,
+                bodies: vec![(row.body, body)],
+            }),
+        }
+    }
+    Ok(frames)
+}
+
+#[cfg(all(test, feature = "io"))]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+
+    #[test]
+    fn columns_first_written_partway_widen_the_whole_file() {
+        let directory = std::env::temp_dir().join(format!("rs-nbody-trajectory-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("trajectory.csv");
+        let mut bodies = vec![Body::new(Point3::new(1., 0., 0.), Vec3::default(), 1.)];
+        let mut writer =
+            TrajectoryWriter::create(&path, None, Some(Compression::Gzip), false).unwrap();
+        writer.record(0., 1., &bodies).unwrap();
+        bodies.push(Body {
+            color: Some("#ff0000".to_string()),
+            size: Some(6),
+            ..Body::new(Point3::new(2., 0., 0.), Vec3::default(), 1e-3)
+        });
+        writer.record(1., 1., &bodies).unwrap();
+        writer.finish().unwrap();
+
+        let mut contents = String::new();
+        let mut file = compress::open(directory.join("trajectory.csv.gz")).unwrap();
+        file.read_to_string(&mut contents).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "time,body,pos_x,pos_y,pos_z,vel_x,vel_y,vel_z,mass,color,size",
+                "0.0,0,1.0,0.0,0.0,0.0,0.0,0.0,1.0,,",
+                "1.0,0,1.0,0.0,0.0,0.0,0.0,0.0,1.0,,",
+                "1.0,1,2.0,0.0,0.0,0.0,0.0,0.0,0.001,#ff0000,6",
+            ]
+        );
+        let frames = read(contents.as_bytes()).unwrap();
+        assert_eq!(frames[1].bodies[1].1.size, Some(6));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
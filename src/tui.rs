@@ -0,0 +1,129 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::Color,
+    symbols::Marker,
+    widgets::{
+        canvas::{Canvas, Points},
+        Block, Paragraph,
+    },
+    DefaultTerminal,
+};
+
+use crate::simulation::Simulation;
+
+/// Minimum wall-clock time between redraws.
+const FRAME: Duration = Duration::from_millis(50);
+
+/// Live view of a running simulation in the terminal, showing the active bodies projected onto the
+/// xy plane with the time, tick rate and energy drift. The terminal is restored when it is dropped.
+pub struct LiveView {
+    terminal: DefaultTerminal,
+    initial_energy: f64,
+    /// Largest distance from the centre of mass along either axis, which only grows so that bodies
+    /// stay in view without the scale jumping around.
+    extent: f64,
+    last_draw: Instant,
+    ticks_since_draw: usize,
+    ticks_per_second: f64,
+}
+
+impl LiveView {
+    pub fn new(initial_energy: f64) -> io::Result<Self> {
+        Ok(Self {
+            terminal: ratatui::try_init()?,
+            initial_energy,
+            extent: 0.,
+            last_draw: Instant::now(),
+            ticks_since_draw: 0,
+            ticks_per_second: 0.,
+        })
+    }
+
+    /// Counts a tick of `world` and redraws the view if a frame is due. `energy_correction` is
+    /// subtracted from the total energy, as for the final report. Returns false if the user has
+    /// asked to stop the simulation with q, Esc or Ctrl-C.
+    pub fn update(&mut self, world: &impl Simulation, energy_correction: f64) -> io::Result<bool> {
+        self.ticks_since_draw += 1;
+        let elapsed = self.last_draw.elapsed();
+        if elapsed < FRAME {
+            return Ok(true);
+        }
+        self.ticks_per_second = self.ticks_since_draw as f64 / elapsed.as_secs_f64();
+        self.ticks_since_draw = 0;
+        self.last_draw = Instant::now();
+
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press
+                    && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) || ctrl_c)
+                {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let center = world.center_of_mass();
+        let points: Vec<(f64, f64)> = (world.bodies().iter())
+            .filter(|body| body.is_active())
+            .map(|body| {
+                (
+                    body.position.x() - center.x(),
+                    body.position.y() - center.y(),
+                )
+            })
+            .collect();
+        for &(x, y) in &points {
+            self.extent = self.extent.max(x.abs()).max(y.abs());
+        }
+        let extent = 1.1 * self.extent.max(f64::MIN_POSITIVE);
+        let drift = (world.total_energy() - energy_correction - self.initial_energy)
+            / self.initial_energy.abs();
+        let status = format!(
+            "t = {:.6e}   {:.0} ticks/s   energy drift = {:.3e}   {} bodies   q to stop",
+            world.time(),
+            self.ticks_per_second,
+            drift,
+            points.len()
+        );
+
+        self.terminal.draw(|frame| {
+            let [view, status_line] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+            // Terminal cells are about twice as tall as they are wide
+            let aspect = 0.5 * view.width as f64 / view.height.max(1) as f64;
+            let (half_width, half_height) = if aspect >= 1. {
+                (aspect * extent, extent)
+            } else {
+                (extent, extent / aspect.max(f64::MIN_POSITIVE))
+            };
+            let canvas = Canvas::default()
+                .block(Block::bordered().title(" x-y about the centre of mass "))
+                .marker(Marker::Braille)
+                .x_bounds([-half_width, half_width])
+                .y_bounds([-half_height, half_height])
+                .paint(|context| {
+                    context.draw(&Points {
+                        coords: &points,
+                        color: Color::Yellow,
+                    })
+                });
+            frame.render_widget(canvas, view);
+            frame.render_widget(Paragraph::new(status.as_str()), status_line);
+        })?;
+        Ok(true)
+    }
+}
+
+impl Drop for LiveView {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
@@ -0,0 +1,52 @@
+/// Unit systems for input and output, which determine the gravitational constant used to convert
+/// masses into the Gm values used internally.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Units {
+    /// Metres, kilograms and seconds.
+    Si,
+    /// Astronomical units, solar masses and Julian years.
+    AuMsunYr,
+    /// Kiloparsecs, solar masses and megayears.
+    Galactic,
+    /// G = 1.
+    Natural,
+}
+
+impl Units {
+    pub fn gravitational_constant(self) -> f64 {
+        match self {
+            Self::Si => 6.67430e-11,
+            // Square of the Gaussian gravitational constant, converted from days to years
+            Self::AuMsunYr => (0.01720209895f64 * 365.25).powi(2),
+            Self::Galactic => 4.498502151469554e-12,
+            Self::Natural => 1.,
+        }
+    }
+
+    pub fn speed_of_light(self) -> f64 {
+        match self {
+            Self::Si => 299792458.,
+            Self::AuMsunYr => 299792458. * 365.25 * 86400. / 149597870700.,
+            Self::Galactic => 299792458. * 365.25 * 86400. * 1e6 / 3.085677581491367e19,
+            Self::Natural => 1.,
+        }
+    }
+
+    /// Unit of length in metres, which is taken to be a metre for natural units.
+    pub fn length(self) -> f64 {
+        match self {
+            Self::Si | Self::Natural => 1.,
+            Self::AuMsunYr => 149597870700.,
+            Self::Galactic => 3.085677581491367e19,
+        }
+    }
+
+    /// Unit of time in seconds, which is taken to be a second for natural units.
+    pub fn time(self) -> f64 {
+        match self {
+            Self::Si | Self::Natural => 1.,
+            Self::AuMsunYr => 365.25 * 86400.,
+            Self::Galactic => 365.25 * 86400. * 1e6,
+        }
+    }
+}
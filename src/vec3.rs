@@ -1,10 +1,14 @@
 use std::{
     fmt::{Display, Formatter, LowerExp, Result},
     iter::Sum,
-    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+    ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
-#[derive(Copy, Clone, Default, Debug)]
+use serde::{Deserialize, Serialize};
+
+/// Serialised as an array of its components.
+#[derive(Copy, Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(from = "[f64; 3]", into = "[f64; 3]")]
 pub struct Vec3 {
     e: [f64; 3],
 }
@@ -57,6 +61,32 @@ pub type Point3 = Vec3;
 
 // The remainder is boilerplate implementing basic operations for vectors/points.
 
+impl From<[f64; 3]> for Vec3 {
+    fn from(e: [f64; 3]) -> Self {
+        Self { e }
+    }
+}
+
+impl From<Vec3> for [f64; 3] {
+    fn from(v: Vec3) -> Self {
+        v.e
+    }
+}
+
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &f64 {
+        &self.e[i]
+    }
+}
+
+impl IndexMut<usize> for Vec3 {
+    fn index_mut(&mut self, i: usize) -> &mut f64 {
+        &mut self.e[i]
+    }
+}
+
 impl Display for Vec3 {
     fn fmt(&self, f: &mut Formatter) -> Result {
         write!(f, "{} {} {}", self.e[0], self.e[1], self.e[2])
@@ -155,6 +185,6 @@ impl Sum for Vec3 {
         for i in iter {
             total += i;
         }
-        return total;
+        total
     }
 }
@@ -0,0 +1,44 @@
+use std::io::{self, Write};
+
+use crate::body::Body;
+
+/// Writes the active bodies at `time` as a legacy ASCII VTK PolyData point cloud, which ParaView
+/// and VisIt can load directly. Points carry their velocity, Gm and index in `bodies`, and the time
+/// is stored as `TIME` field data so that numbered series of files animate.
+pub fn write(mut writer: impl Write, time: f64, bodies: &[Body]) -> io::Result<()> {
+    let active: Vec<(usize, &Body)> = (bodies.iter().enumerate())
+        .filter(|(_, body)| body.is_active())
+        .collect();
+    let n = active.len();
+    writeln!(writer, "# vtk DataFile Version 3.0")?;
+    writeln!(writer, "rs-nbody snapshot at t = {}", time)?;
+    writeln!(writer, "ASCII")?;
+    writeln!(writer, "DATASET POLYDATA")?;
+    writeln!(writer, "FIELD FieldData 1")?;
+    writeln!(writer, "TIME 1 1 double")?;
+    writeln!(writer, "{:e}", time)?;
+    writeln!(writer, "POINTS {} double", n)?;
+    for (_, body) in &active {
+        writeln!(writer, "{:e}", body.position)?;
+    }
+    writeln!(writer, "VERTICES {} {}", n, 2 * n)?;
+    for k in 0..n {
+        writeln!(writer, "1 {}", k)?;
+    }
+    writeln!(writer, "POINT_DATA {}", n)?;
+    writeln!(writer, "VECTORS velocity double")?;
+    for (_, body) in &active {
+        writeln!(writer, "{:e}", body.velocity)?;
+    }
+    writeln!(writer, "SCALARS gm double 1")?;
+    writeln!(writer, "LOOKUP_TABLE default")?;
+    for (_, body) in &active {
+        writeln!(writer, "{:e}", body.mass)?;
+    }
+    writeln!(writer, "SCALARS index int 1")?;
+    writeln!(writer, "LOOKUP_TABLE default")?;
+    for (i, _) in &active {
+        writeln!(writer, "{}", i)?;
+    }
+    writer.flush()
+}
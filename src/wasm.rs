@@ -0,0 +1,101 @@
+//! JavaScript API for driving simulations in browsers, built into a WebAssembly module with the
+//! `wasm` feature. Vectors cross into JavaScript as flat arrays of their components, `[x₀, y₀, z₀,
+//! x₁, y₁, z₁, …]`, and masses as Gm.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    body::Body,
+    force::{Force, Gravity},
+    leapfrog,
+    simulation::Simulation,
+    vec3::Vec3,
+};
+
+/// Bodies under Newtonian gravity, integrated with leapfrog at a fixed tick. Ticks don't allocate,
+/// so that stepping every animation frame doesn't churn memory.
+#[wasm_bindgen]
+pub struct Simulator {
+    world: leapfrog::World,
+    tick: f64,
+}
+
+#[wasm_bindgen]
+impl Simulator {
+    /// Creates a simulator from the positions, velocities and Gm masses of the bodies, with every
+    /// body given the Plummer `softening` length.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        positions: &[f64],
+        velocities: &[f64],
+        masses: &[f64],
+        tick: f64,
+        softening: f64,
+    ) -> Result<Simulator, JsError> {
+        let n = masses.len();
+        if positions.len() != 3 * n || velocities.len() != 3 * n {
+            return Err(JsError::new(
+                "positions and velocities must have three components per mass",
+            ));
+        }
+        if !(tick.is_finite() && tick > 0.) {
+            return Err(JsError::new("the tick must be positive"));
+        }
+        let bodies = (0..n)
+            .map(|i| Body {
+                softening,
+                ..Body::new(vector(positions, i), vector(velocities, i), masses[i])
+            })
+            .collect();
+        let forces: Vec<Box<dyn Force>> = vec![Box::new(Gravity::default())];
+        Ok(Self {
+            world: leapfrog::World::new(bodies, forces),
+            tick,
+        })
+    }
+
+    /// Advances the simulation by `ticks` ticks.
+    pub fn step(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.world.tick(self.tick);
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> f64 {
+        self.world.time()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.world.bodies().len()
+    }
+
+    pub fn positions(&self) -> Vec<f64> {
+        components(self.world.bodies(), |body| body.position)
+    }
+
+    pub fn velocities(&self) -> Vec<f64> {
+        components(self.world.bodies(), |body| body.velocity)
+    }
+
+    /// Total energy, in units of G times energy like the masses.
+    pub fn energy(&self) -> f64 {
+        self.world.total_energy()
+    }
+}
+
+fn vector(components: &[f64], i: usize) -> Vec3 {
+    Vec3::new(
+        components[3 * i],
+        components[3 * i + 1],
+        components[3 * i + 2],
+    )
+}
+
+fn components(bodies: &[Body], f: impl Fn(&Body) -> Vec3) -> Vec<f64> {
+    bodies
+        .iter()
+        .flat_map(|body| <[f64; 3]>::from(f(body)))
+        .collect()
+}
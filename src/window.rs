@@ -0,0 +1,128 @@
+use std::{collections::VecDeque, path::PathBuf};
+
+use serde::Serialize;
+
+use crate::{body::Body, compress::AtomicFile, event::Event};
+
+/// Records high-cadence snapshots around events, keeping a rolling buffer of recent ticks so that
+/// each event's CSV file covers the window both before and after it. Each file only appears at its
+/// path once its window has been written.
+pub struct EventRecorder {
+    /// Number of ticks recorded either side of an event.
+    window: usize,
+    directory: PathBuf,
+    history: VecDeque<(f64, Vec<Body>)>,
+    /// Open event files, with the number of ticks still to be written to each.
+    recordings: Vec<(csv::Writer<AtomicFile>, usize)>,
+    events: usize,
+    /// Whether to sync each file to disk before it replaces its path.
+    sync: bool,
+}
+
+#[derive(Serialize)]
+struct SnapshotRow {
+    time: f64,
+    body: usize,
+    pos_x: f64,
+    pos_y: f64,
+    pos_z: f64,
+    vel_x: f64,
+    vel_y: f64,
+    vel_z: f64,
+    /// Gm, as used internally.
+    mass: f64,
+}
+
+impl EventRecorder {
+    pub fn new(window: usize, directory: PathBuf, sync: bool) -> Self {
+        Self {
+            window,
+            directory,
+            history: VecDeque::with_capacity(window + 1),
+            recordings: Vec::new(),
+            events: 0,
+            sync,
+        }
+    }
+
+    /// Records the state of the bodies after a tick in which `events` occurred, printing each event
+    /// and starting a new event file for each collision or close encounter.
+    pub fn record(
+        &mut self,
+        time: f64,
+        bodies: &[Body],
+        events: &[Event],
+    ) -> Result<(), csv::Error> {
+        self.history.push_back((time, bodies.to_vec()));
+        if self.history.len() > self.window + 1 {
+            self.history.pop_front();
+        }
+        for (writer, remaining) in &mut self.recordings {
+            write_snapshot(writer, time, bodies)?;
+            *remaining -= 1;
+        }
+        let (finished, recordings) = self
+            .recordings
+            .drain(..)
+            .partition(|(_, remaining)| *remaining == 0);
+        self.recordings = recordings;
+        for (writer, _) in finished {
+            finish(writer)?;
+        }
+
+        for event in events {
+            if let Event::Escape(_) = event {
+                println!("{}", event);
+                continue;
+            }
+            self.events += 1;
+            let path = self.directory.join(format!("event-{}.csv", self.events));
+            println!("{}, recording to {}", event, path.display());
+            let mut writer = csv::Writer::from_writer(AtomicFile::create(path, self.sync)?);
+            for (time, bodies) in &self.history {
+                write_snapshot(&mut writer, *time, bodies)?;
+            }
+            if self.window > 0 {
+                writer.flush()?;
+                self.recordings.push((writer, self.window));
+            } else {
+                finish(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finishes the files of events whose windows extend past the end of the run.
+    pub fn finish(self) -> Result<(), csv::Error> {
+        for (writer, _) in self.recordings {
+            finish(writer)?;
+        }
+        Ok(())
+    }
+}
+
+fn finish(writer: csv::Writer<AtomicFile>) -> Result<(), csv::Error> {
+    writer.into_inner().map_err(|e| e.into_error())?.finish()?;
+    Ok(())
+}
+
+fn write_snapshot(
+    writer: &mut csv::Writer<AtomicFile>,
+    time: f64,
+    bodies: &[Body],
+) -> Result<(), csv::Error> {
+    for (i, body) in bodies.iter().enumerate() {
+        writer.serialize(SnapshotRow {
+            time,
+            body: i,
+            pos_x: body.position.x(),
+            pos_y: body.position.y(),
+            pos_z: body.position.z(),
+            vel_x: body.velocity.x(),
+            vel_y: body.velocity.y(),
+            vel_z: body.velocity.z(),
+            mass: body.mass,
+        })?;
+    }
+    Ok(())
+}